@@ -0,0 +1,52 @@
+/**
+ * Serializes the cleaned page for reflowable formats (EPUB, HTML, Markdown).
+ * Keeps only the main content and the site stylesheets, all URLs are made absolute.
+ */
+function domCapture() {
+  const main =
+    document.querySelector("main, article, [role='main']") || document.body;
+  const content = main.cloneNode(true);
+
+  content
+    .querySelectorAll("script, noscript, iframe, template, object, embed")
+    .forEach((e) => e.remove());
+
+  // Properties resolve relative URLs, attributes keep them as written
+  const images = main.querySelectorAll("img");
+  content.querySelectorAll("img").forEach((img, i) => {
+    const source = images[i];
+    img.setAttribute("src", source.currentSrc || source.src);
+    img.removeAttribute("srcset");
+    img.removeAttribute("sizes");
+    img.removeAttribute("loading");
+  });
+  content.querySelectorAll("picture source").forEach((e) => e.remove());
+  content.querySelectorAll("a[href]").forEach((a) => {
+    try {
+      a.setAttribute("href", new URL(a.getAttribute("href"), document.baseURI).href);
+    } catch (e) {
+      a.removeAttribute("href");
+    }
+  });
+
+  const stylesheets = Array.from(
+    document.querySelectorAll("link[rel~='stylesheet'][href]"),
+  )
+    .map((link) => `<link rel="stylesheet" href="${link.href.replace(/"/g, "&quot;")}">`)
+    .join("\n");
+
+  const title = document.createElement("title");
+  title.textContent = document.title;
+
+  return `<!DOCTYPE html>
+<html lang="${document.documentElement.lang}">
+<head>
+<meta charset="utf-8">
+${title.outerHTML}
+${stylesheets}
+</head>
+<body>
+${content.tagName === "BODY" ? content.innerHTML : content.outerHTML}
+</body>
+</html>`;
+}
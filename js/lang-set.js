@@ -1,9 +1,9 @@
 /**
  * Sets the document language for accessibility and PDF generation.
- * Defaults to 'en' if no language is already specified.
+ * Defaults to the given language (or 'en') if no language is already specified.
  */
-function langSet() {
-  document.documentElement.lang = document.documentElement.lang || "en";
+function langSet(fallback) {
+  document.documentElement.lang = document.documentElement.lang || fallback || "en";
   return document.documentElement.lang;
 }
 
@@ -1,4 +1,4 @@
-async function pageWait() {
+async function pageWait(imageTimeout = 3000) {
   // DOM loaded
   if (document.readyState !== "complete") {
     await new Promise((r) => addEventListener("load", r, { once: true }));
@@ -18,7 +18,7 @@ async function pageWait() {
         new Promise((resolve) => {
           if (img.complete && img.naturalWidth > 0) return resolve();
 
-          const timer = setTimeout(resolve, 3000);
+          const timer = setTimeout(resolve, imageTimeout);
 
           img.addEventListener(
             "load",
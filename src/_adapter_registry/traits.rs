@@ -4,6 +4,8 @@ use anyhow::Result;
 use chromiumoxide::Browser;
 use chromiumoxide::page::Page;
 
+use crate::readiness::Readiness;
+
 #[async_trait::async_trait]
 pub trait ResourceDetector: Send + Sync + Debug {
     fn detect_fast(&self, _html: &str) -> bool {
@@ -23,6 +25,10 @@ pub trait ResourceAdapter: Send + Sync + Debug + 'static {
     async fn after_page(&self, _page: &Page) -> Result<()> {
         Ok(())
     }
+    /// When a page of the site is ready to be printed, e.g. single-page apps wait for hydration
+    fn readiness(&self) -> Readiness {
+        Readiness::Document
+    }
 }
 
 pub trait ResourceAdapterWithDetector: ResourceAdapter + Default {
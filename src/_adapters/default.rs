@@ -5,7 +5,6 @@ use chromiumoxide::Page;
 use crate::_adapter_registry::traits::ResourceAdapter;
 
 const PAGE_CLEANUP_JS: &str = include_str!("../../js/page-cleanup.js");
-const LANG_SET_JS: &str = include_str!("../../js/lang-set.js");
 const ICONIFY_ICON: &str = include_str!("../../js/iconify-icon.js");
 
 #[derive(Default, Debug)]
@@ -28,8 +27,6 @@ impl ResourceAdapter for DefaultAdapter {
             }
         }
 
-        page.evaluate(LANG_SET_JS).await?;
-
         page.evaluate(ICONIFY_ICON).await?;
 
         Ok(())
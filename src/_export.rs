@@ -0,0 +1,72 @@
+pub mod assets;
+pub mod capture;
+pub mod epub;
+pub mod html;
+pub mod markdown;
+mod to_markdown;
+mod xhtml;
+mod zip_writer;
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::_pdf_utils::metadata::DocumentMetadata;
+use crate::toc::TocNode;
+
+/// Format of the produced document
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Tagged PDF printed by Chromium
+    #[default]
+    Pdf,
+    /// EPUB 3 e-book built from the cleaned page content
+    Epub,
+    /// Single self-contained HTML file with a section per page
+    Html,
+    /// mdBook sources: a Markdown file per page, `SUMMARY.md` and downloaded images
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Extension of the output file, empty for formats written to a directory
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Epub => "epub",
+            OutputFormat::Html => "html",
+            OutputFormat::Markdown => "",
+        }
+    }
+
+    ///
+    /// Replace the default `.pdf` extension of the output with the one of the format
+    ///
+    pub fn output_path(&self, output: &Path) -> PathBuf {
+        let is_pdf = output
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("pdf"));
+        if is_pdf || output.extension().is_none() {
+            output.with_extension(self.extension())
+        } else {
+            output.to_path_buf()
+        }
+    }
+}
+
+///
+/// Write the captured pages in a reflowable format
+///
+pub async fn export(
+    format: OutputFormat,
+    toc: &[TocNode],
+    output: &Path,
+    metadata: &DocumentMetadata,
+) -> Result<()> {
+    match format {
+        OutputFormat::Pdf => anyhow::bail!("PDF is printed by the browser, not exported"),
+        OutputFormat::Epub => epub::write_epub(toc, output, metadata).await,
+        OutputFormat::Html => html::write_html(toc, output, metadata).await,
+        OutputFormat::Markdown => markdown::write_markdown(toc, output, metadata).await,
+    }
+}
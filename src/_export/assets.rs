@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+
+use base64::Engine;
+use url::Url;
+
+/// A downloaded image, stylesheet or font
+#[derive(Debug, Clone)]
+pub struct Asset {
+    /// File name inside the assets folder, e.g. `asset_0003.png`
+    pub name: String,
+    pub media_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Asset {
+    pub fn data_uri(&self) -> String {
+        format!(
+            "data:{};base64,{}",
+            self.media_type,
+            base64::engine::general_purpose::STANDARD.encode(&self.data)
+        )
+    }
+}
+
+/// Downloads page resources once, no matter how many chapters use them
+#[derive(Debug, Default)]
+pub struct AssetStore {
+    client: reqwest::Client,
+    /// Stylesheets reference their fonts and images as data URIs instead of file names
+    embed: bool,
+    /// Source URL to the index of the asset, `None` if the download failed
+    by_url: HashMap<String, Option<usize>>,
+    assets: Vec<Asset>,
+}
+
+impl AssetStore {
+    ///
+    /// Store for a single self-contained file, see `embed`
+    ///
+    pub fn embedded() -> Self {
+        Self {
+            embed: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn assets(&self) -> &[Asset] {
+        &self.assets
+    }
+
+    pub fn get(&self, index: usize) -> &Asset {
+        &self.assets[index]
+    }
+
+    ///
+    /// Download an image or font, `data:` URLs are decoded
+    ///
+    pub async fn fetch(&mut self, url: &str) -> Option<usize> {
+        if let Some(known) = self.by_url.get(url) {
+            return *known;
+        }
+
+        let result = match self.download(url).await {
+            Ok((data, media_type)) => Some(self.push(url, media_type, data)),
+            Err(e) => {
+                tracing::warn!("Failed to download {}: {}", url, e);
+                None
+            }
+        };
+        self.by_url.insert(url.to_string(), result);
+        result
+    }
+
+    ///
+    /// Download a stylesheet together with the fonts and images it references,
+    /// `url(...)` values are rewritten to the asset file names or data URIs
+    ///
+    pub async fn fetch_stylesheet(&mut self, url: &str) -> Option<usize> {
+        if let Some(known) = self.by_url.get(url) {
+            return *known;
+        }
+
+        let css = match self.download(url).await {
+            Ok((data, _)) => String::from_utf8_lossy(&data).into_owned(),
+            Err(e) => {
+                tracing::warn!("Failed to download stylesheet {}: {}", url, e);
+                self.by_url.insert(url.to_string(), None);
+                return None;
+            }
+        };
+
+        let base = Url::parse(url).ok();
+        let mut rewritten = String::with_capacity(css.len());
+        let mut rest = css.as_str();
+        while let Some(start) = rest.find("url(") {
+            let (before, after) = rest.split_at(start + 4);
+            rewritten.push_str(before);
+
+            let Some(end) = after.find(')') else {
+                rest = after;
+                break;
+            };
+            let reference = after[..end].trim().trim_matches(['"', '\'']);
+            let resolved = base
+                .as_ref()
+                .and_then(|base| base.join(reference).ok())
+                .map(|u| u.to_string());
+
+            let replacement = match resolved {
+                Some(resolved) if !reference.starts_with('#') => {
+                    self.fetch(&resolved).await.map(|index| {
+                        let asset = &self.assets[index];
+                        if self.embed {
+                            format!("\"{}\"", asset.data_uri())
+                        } else {
+                            format!("\"{}\"", asset.name)
+                        }
+                    })
+                }
+                _ => None,
+            };
+            rewritten.push_str(&replacement.unwrap_or_else(|| after[..end].to_string()));
+            rest = &after[end..];
+        }
+        rewritten.push_str(rest);
+
+        let index = self.push(url, "text/css".to_string(), rewritten.into_bytes());
+        self.by_url.insert(url.to_string(), Some(index));
+        Some(index)
+    }
+
+    fn push(&mut self, url: &str, media_type: String, data: Vec<u8>) -> usize {
+        let index = self.assets.len();
+        let extension = extension_for(&media_type, url);
+        self.assets.push(Asset {
+            name: format!("asset_{:04}.{}", index + 1, extension),
+            media_type,
+            data,
+        });
+        index
+    }
+
+    async fn download(&self, url: &str) -> anyhow::Result<(Vec<u8>, String)> {
+        if let Some(data_url) = url.strip_prefix("data:") {
+            return decode_data_url(data_url);
+        }
+
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        let header_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| {
+                v.split(';')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_lowercase()
+            })
+            .filter(|v| !v.is_empty() && v != "application/octet-stream");
+        let data = response.bytes().await?.to_vec();
+
+        let media_type = header_type.unwrap_or_else(|| media_type_for(url).to_string());
+        Ok((data, media_type))
+    }
+}
+
+fn decode_data_url(data_url: &str) -> anyhow::Result<(Vec<u8>, String)> {
+    let (header, payload) = data_url
+        .split_once(',')
+        .ok_or_else(|| anyhow::anyhow!("Malformed data URL"))?;
+    let media_type = header
+        .split(';')
+        .next()
+        .filter(|t| !t.is_empty())
+        .unwrap_or("text/plain")
+        .to_lowercase();
+
+    let data = if header.ends_with(";base64") {
+        base64::engine::general_purpose::STANDARD.decode(payload.trim())?
+    } else {
+        percent_decode(payload)
+    };
+    Ok((data, media_type))
+}
+
+fn percent_decode(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded
+}
+
+/// Known media types and their file extensions
+const MEDIA_TYPES: [(&str, &str); 12] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("image/gif", "gif"),
+    ("image/svg+xml", "svg"),
+    ("image/webp", "webp"),
+    ("image/avif", "avif"),
+    ("text/css", "css"),
+    ("font/woff2", "woff2"),
+    ("font/woff", "woff"),
+    ("font/ttf", "ttf"),
+    ("font/otf", "otf"),
+    ("application/font-woff", "woff"),
+];
+
+fn media_type_for(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_lowercase())
+        .unwrap_or_default();
+    let extension = if extension == "jpeg" {
+        "jpg".to_string()
+    } else {
+        extension
+    };
+
+    MEDIA_TYPES
+        .iter()
+        .find(|(_, e)| *e == extension)
+        .map_or("application/octet-stream", |(t, _)| t)
+}
+
+fn extension_for(media_type: &str, url: &str) -> &'static str {
+    MEDIA_TYPES
+        .iter()
+        .find(|(t, _)| *t == media_type)
+        .or_else(|| {
+            let guessed = media_type_for(url);
+            MEDIA_TYPES.iter().find(|(t, _)| *t == guessed)
+        })
+        .map_or("bin", |(_, e)| e)
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::Result;
+use chromiumoxide::page::Page;
+use scraper::{Html, Selector};
+use url::Url;
+
+use crate::_pdf_utils::split::chapter_key;
+use crate::toc::TocNode;
+
+const DOM_CAPTURE_JS: &str = include_str!("../../js/dom-capture.js");
+
+///
+/// Save the cleaned DOM of the page instead of printing it
+///
+pub async fn capture_page(page: &Page, path: &Path) -> Result<()> {
+    let html: String = page.evaluate_function(DOM_CAPTURE_JS).await?.into_value()?;
+    tokio::fs::write(path, html).await?;
+
+    Ok(())
+}
+
+/// A captured page loaded back for export
+pub struct CapturedPage {
+    pub document: Html,
+}
+
+impl CapturedPage {
+    pub fn load(path: &Path) -> Result<Self> {
+        let html = std::fs::read_to_string(path)?;
+        Ok(Self {
+            document: Html::parse_document(&html),
+        })
+    }
+
+    /// Absolute URLs of the site stylesheets
+    pub fn stylesheets(&self) -> Vec<String> {
+        self.attr_values("link[rel~='stylesheet'][href]", "href")
+    }
+
+    /// Absolute URLs of all images
+    pub fn images(&self) -> Vec<String> {
+        self.attr_values("body img[src]", "src")
+    }
+
+    /// Absolute URLs of all links
+    pub fn links(&self) -> Vec<String> {
+        self.attr_values("body a[href]", "href")
+    }
+
+    pub fn body(&self) -> scraper::ElementRef<'_> {
+        let selector = Selector::parse("body").expect("valid selector");
+        self.document
+            .select(&selector)
+            .next()
+            .unwrap_or_else(|| self.document.root_element())
+    }
+
+    pub fn has_element(&self, selector: &str) -> bool {
+        Selector::parse(selector)
+            .map(|s| self.document.select(&s).next().is_some())
+            .unwrap_or(false)
+    }
+
+    fn attr_values(&self, selector: &str, attr: &str) -> Vec<String> {
+        let selector = Selector::parse(selector).expect("valid selector");
+        let mut values: Vec<String> = self
+            .document
+            .select(&selector)
+            .filter_map(|e| e.value().attr(attr))
+            .map(str::to_string)
+            .collect();
+        values.dedup();
+        values
+    }
+}
+
+///
+/// Map the chapter key of every captured page to its position in the TOC
+///
+pub fn chapter_index(toc: &[TocNode]) -> HashMap<String, usize> {
+    let mut index = HashMap::new();
+    for (position, node) in toc.iter().enumerate() {
+        index.entry(chapter_key(&node.href)).or_insert(position);
+        if let Some(final_url) = &node.final_url {
+            index.entry(chapter_key(final_url)).or_insert(position);
+        }
+    }
+    index
+}
+
+///
+/// Rewrite links between captured pages: `link_for(chapter, fragment)` builds the target
+/// of a link to a chapter of the book, other links are left out of the map
+///
+pub fn chapter_links<F>(
+    chapters: &HashMap<String, usize>,
+    links: &[String],
+    link_for: F,
+) -> HashMap<String, String>
+where
+    F: Fn(usize, Option<&str>) -> String,
+{
+    links
+        .iter()
+        .filter_map(|link| {
+            let index = *chapters.get(&chapter_key(link))?;
+            let fragment = Url::parse(link)
+                .ok()
+                .and_then(|u| u.fragment().map(str::to_string));
+            Some((link.clone(), link_for(index, fragment.as_deref())))
+        })
+        .collect()
+}
@@ -0,0 +1,301 @@
+use std::{
+    fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::BufWriter,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use jiff::Timestamp;
+
+use super::assets::AssetStore;
+use super::capture::{CapturedPage, chapter_index, chapter_links};
+use super::xhtml::{Markup, NavEntry, Rewrite, nav_list, serialize_children};
+use super::zip_writer::ZipWriter;
+use crate::_pdf_utils::metadata::{DocumentMetadata, dominant_lang};
+use crate::escape::{escape_attr, escape_text};
+use crate::toc::TocNode;
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// A chapter written to the EPUB
+struct Chapter {
+    file_name: String,
+    title: String,
+    level: u8,
+    /// Inline SVG must be declared in the manifest
+    has_svg: bool,
+}
+
+///
+/// Package captured pages as an EPUB 3 with a navigation document built from the TOC levels
+///
+pub async fn write_epub(toc: &[TocNode], output: &Path, metadata: &DocumentMetadata) -> Result<()> {
+    let title = metadata.title.clone().unwrap_or_default();
+    let lang = metadata
+        .lang
+        .clone()
+        .or_else(|| dominant_lang(toc.iter().filter_map(|n| n.lang.as_deref())))
+        .unwrap_or_else(|| "en".to_string());
+
+    let file =
+        File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    // The mimetype must be the first entry and stay uncompressed
+    zip.add_stored("mimetype", b"application/epub+zip")?;
+    zip.add_deflated("META-INF/container.xml", CONTAINER_XML.as_bytes())?;
+
+    let chapter_files: Vec<String> = (0..toc.len())
+        .map(|i| format!("chapter_{:04}.xhtml", i + 1))
+        .collect();
+    let chapters_by_key = chapter_index(toc);
+    let mut assets = AssetStore::default();
+    let mut chapters = Vec::new();
+
+    for (index, node) in toc.iter().enumerate() {
+        let Some(path) = node.file_path.as_ref() else {
+            continue;
+        };
+        let chapter_title = node.title.clone().unwrap_or_else(|| node.href.clone());
+        println!("  📖 Packaging {}", chapter_title);
+
+        let page = CapturedPage::load(path)?;
+
+        let mut stylesheets = Vec::new();
+        for url in page.stylesheets() {
+            if let Some(asset) = assets.fetch_stylesheet(&url).await {
+                stylesheets.push(format!("assets/{}", assets.get(asset).name));
+            }
+        }
+
+        let mut urls = chapter_links(&chapters_by_key, &page.links(), |target, fragment| {
+            let file = if target == index {
+                String::new()
+            } else {
+                chapter_files[target].clone()
+            };
+            match fragment {
+                Some(fragment) => format!("{}#{}", file, fragment),
+                None if file.is_empty() => "#".to_string(),
+                None => file,
+            }
+        });
+        for src in page.images() {
+            if let Some(asset) = assets.fetch(&src).await {
+                urls.insert(src, format!("assets/{}", assets.get(asset).name));
+            }
+        }
+
+        let rewrite = Rewrite {
+            urls: &urls,
+            id_prefix: None,
+        };
+        let body = serialize_children(page.body(), Markup::Xhtml, rewrite);
+        let chapter_lang = node.lang.as_deref().unwrap_or(&lang);
+        let xhtml = chapter_document(&chapter_title, chapter_lang, &stylesheets, &body);
+        zip.add_deflated(&format!("OEBPS/{}", chapter_files[index]), xhtml.as_bytes())?;
+
+        chapters.push(Chapter {
+            file_name: chapter_files[index].clone(),
+            title: chapter_title,
+            level: node.level,
+            has_svg: page.has_element("body svg"),
+        });
+    }
+
+    if chapters.is_empty() {
+        anyhow::bail!("No pages were captured");
+    }
+
+    for asset in assets.assets() {
+        zip.add_deflated(&format!("OEBPS/assets/{}", asset.name), &asset.data)?;
+    }
+
+    zip.add_deflated(
+        "OEBPS/nav.xhtml",
+        nav_document(&title, &lang, &chapters).as_bytes(),
+    )?;
+    zip.add_deflated(
+        "OEBPS/content.opf",
+        package_document(&title, &lang, metadata, toc, &chapters, &assets).as_bytes(),
+    )?;
+    zip.finish()?;
+
+    println!(
+        "  ✅ EPUB saved to {} ({} chapters, {} assets)",
+        output.display(),
+        chapters.len(),
+        assets.assets().len()
+    );
+
+    Ok(())
+}
+
+fn chapter_document(title: &str, lang: &str, stylesheets: &[String], body: &str) -> String {
+    let links: String = stylesheets
+        .iter()
+        .map(|href| {
+            format!(
+                "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}\"/>\n",
+                escape_attr(href)
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{lang}" xml:lang="{lang}">
+<head>
+<meta charset="utf-8"/>
+<title>{title}</title>
+{links}</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        lang = escape_attr(lang),
+        title = escape_text(title),
+    )
+}
+
+fn nav_document(title: &str, lang: &str, chapters: &[Chapter]) -> String {
+    let entries: Vec<NavEntry> = chapters
+        .iter()
+        .map(|chapter| NavEntry {
+            href: &chapter.file_name,
+            title: &chapter.title,
+            level: chapter.level,
+        })
+        .collect();
+    let list = nav_list(&entries);
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="{lang}" xml:lang="{lang}">
+<head>
+<meta charset="utf-8"/>
+<title>{title}</title>
+</head>
+<body>
+<nav epub:type="toc" id="toc">
+<h1>{title}</h1>
+{list}</nav>
+</body>
+</html>
+"#,
+        lang = escape_attr(lang),
+        title = escape_text(title),
+    )
+}
+
+fn package_document(
+    title: &str,
+    lang: &str,
+    metadata: &DocumentMetadata,
+    toc: &[TocNode],
+    chapters: &[Chapter],
+    assets: &AssetStore,
+) -> String {
+    let modified = metadata
+        .date
+        .unwrap_or_else(Timestamp::now)
+        .strftime("%Y-%m-%dT%H:%M:%SZ")
+        .to_string();
+
+    let mut dc = format!(
+        "<dc:identifier id=\"book-id\">{}</dc:identifier>\n<dc:title>{}</dc:title>\n<dc:language>{}</dc:language>\n",
+        book_identifier(title, toc),
+        escape_text(title),
+        escape_text(lang)
+    );
+    let optional = [
+        ("creator", &metadata.author),
+        ("subject", &metadata.keywords),
+        ("description", &metadata.subject),
+    ];
+    for (element, value) in optional {
+        if let Some(value) = value {
+            dc.push_str(&format!(
+                "<dc:{element}>{}</dc:{element}>\n",
+                escape_text(value)
+            ));
+        }
+    }
+
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+    );
+    let mut spine = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let properties = if chapter.has_svg {
+            " properties=\"svg\""
+        } else {
+            ""
+        };
+        manifest.push_str(&format!(
+            "<item id=\"chapter-{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{}/>\n",
+            i + 1,
+            escape_attr(&chapter.file_name),
+            properties
+        ));
+        spine.push_str(&format!("<itemref idref=\"chapter-{}\"/>\n", i + 1));
+    }
+    for (i, asset) in assets.assets().iter().enumerate() {
+        manifest.push_str(&format!(
+            "<item id=\"asset-{}\" href=\"assets/{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            escape_attr(&asset.name),
+            escape_attr(&asset.media_type)
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{lang}">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{dc}<meta property="dcterms:modified">{modified}</meta>
+</metadata>
+<manifest>
+{manifest}</manifest>
+<spine>
+{spine}</spine>
+</package>
+"#,
+        lang = escape_attr(lang),
+    )
+}
+
+///
+/// Stable UUID-like identifier derived from the title and the chapter URLs
+///
+fn book_identifier(title: &str, toc: &[TocNode]) -> String {
+    let mut bytes = Vec::with_capacity(16);
+    for salt in 0u8..2 {
+        let mut hasher = DefaultHasher::new();
+        salt.hash(&mut hasher);
+        title.hash(&mut hasher);
+        for node in toc {
+            node.href.hash(&mut hasher);
+        }
+        bytes.extend_from_slice(&hasher.finish().to_be_bytes());
+    }
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "urn:uuid:{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
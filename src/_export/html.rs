@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::assets::AssetStore;
+use super::capture::{CapturedPage, chapter_index, chapter_links};
+use super::xhtml::{Markup, NavEntry, Rewrite, nav_list, serialize_children};
+use crate::_pdf_utils::metadata::{DocumentMetadata, dominant_lang};
+use crate::escape::{escape_attr, escape_text};
+use crate::toc::TocNode;
+
+/// A page written as a section of the document
+struct Section {
+    anchor: String,
+    title: String,
+    level: u8,
+    lang: Option<String>,
+    body: String,
+}
+
+///
+/// Write captured pages into one self-contained HTML file, every page becomes a `<section>`
+///
+/// Images, stylesheets and fonts are inlined as data URIs, links between pages point to
+/// the sections and ids of every page are prefixed with its anchor so they stay unique.
+///
+pub async fn write_html(toc: &[TocNode], output: &Path, metadata: &DocumentMetadata) -> Result<()> {
+    let title = metadata.title.clone().unwrap_or_default();
+    let lang = metadata
+        .lang
+        .clone()
+        .or_else(|| dominant_lang(toc.iter().filter_map(|n| n.lang.as_deref())))
+        .unwrap_or_else(|| "en".to_string());
+
+    let anchors: Vec<String> = (0..toc.len())
+        .map(|i| format!("chapter-{}", i + 1))
+        .collect();
+    let chapters_by_key = chapter_index(toc);
+    let mut assets = AssetStore::embedded();
+    // Stylesheets in the order of first use, shared by all sections
+    let mut stylesheets: Vec<usize> = Vec::new();
+    let mut sections = Vec::new();
+
+    for (index, node) in toc.iter().enumerate() {
+        let Some(path) = node.file_path.as_ref() else {
+            continue;
+        };
+        let section_title = node.title.clone().unwrap_or_else(|| node.href.clone());
+        println!("  📄 Embedding {}", section_title);
+
+        let page = CapturedPage::load(path)?;
+
+        for url in page.stylesheets() {
+            if let Some(asset) = assets.fetch_stylesheet(&url).await
+                && !stylesheets.contains(&asset)
+            {
+                stylesheets.push(asset);
+            }
+        }
+
+        let section_link = |target: usize, fragment: Option<&str>| match fragment {
+            Some(fragment) => format!("#{}-{}", anchors[target], fragment),
+            None => format!("#{}", anchors[target]),
+        };
+        let mut urls = chapter_links(&chapters_by_key, &page.links(), section_link);
+        for src in page.images() {
+            if let Some(asset) = assets.fetch(&src).await {
+                urls.insert(src, assets.get(asset).data_uri());
+            }
+        }
+
+        let id_prefix = format!("{}-", anchors[index]);
+        let rewrite = Rewrite {
+            urls: &urls,
+            id_prefix: Some(&id_prefix),
+        };
+
+        sections.push(Section {
+            anchor: anchors[index].clone(),
+            title: section_title,
+            level: node.level,
+            lang: node.lang.clone(),
+            body: serialize_children(page.body(), Markup::Html, rewrite),
+        });
+    }
+
+    if sections.is_empty() {
+        anyhow::bail!("No pages were captured");
+    }
+
+    let styles: String = stylesheets
+        .iter()
+        .map(|&index| {
+            let css =
+                String::from_utf8_lossy(&assets.get(index).data).replace("</style", "<\\/style");
+            format!("<style>\n{}\n</style>\n", css)
+        })
+        .collect();
+
+    let document = html_document(&title, &lang, metadata, &styles, &sections);
+    std::fs::write(output, document)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!(
+        "  ✅ HTML saved to {} ({} sections, {} embedded resources)",
+        output.display(),
+        sections.len(),
+        assets.assets().len()
+    );
+
+    Ok(())
+}
+
+fn html_document(
+    title: &str,
+    lang: &str,
+    metadata: &DocumentMetadata,
+    styles: &str,
+    sections: &[Section],
+) -> String {
+    let mut head = String::new();
+    let optional = [
+        ("author", &metadata.author),
+        ("description", &metadata.subject),
+        ("keywords", &metadata.keywords),
+    ];
+    for (name, value) in optional {
+        if let Some(value) = value {
+            head.push_str(&format!(
+                "<meta name=\"{}\" content=\"{}\">\n",
+                name,
+                escape_attr(value)
+            ));
+        }
+    }
+
+    let hrefs: Vec<String> = sections.iter().map(|s| format!("#{}", s.anchor)).collect();
+    let entries: Vec<NavEntry> = sections
+        .iter()
+        .zip(&hrefs)
+        .map(|(section, href)| NavEntry {
+            href,
+            title: &section.title,
+            level: section.level,
+        })
+        .collect();
+
+    let mut body = String::new();
+    for section in sections {
+        let lang = section
+            .lang
+            .as_deref()
+            .map(|lang| format!(" lang=\"{}\"", escape_attr(lang)))
+            .unwrap_or_default();
+        body.push_str(&format!(
+            "<section id=\"{}\" aria-label=\"{}\"{}>\n{}\n</section>\n",
+            section.anchor,
+            escape_attr(&section.title),
+            lang,
+            section.body
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+{head}{styles}</head>
+<body>
+<nav id="toc" aria-label="Table of contents">
+<h1>{title}</h1>
+{nav}</nav>
+{body}</body>
+</html>
+"#,
+        lang = escape_attr(lang),
+        title = escape_text(title),
+        nav = nav_list(&entries),
+    )
+}
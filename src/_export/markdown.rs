@@ -0,0 +1,199 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{Context, Result};
+
+use super::assets::AssetStore;
+use super::capture::{CapturedPage, chapter_index, chapter_links};
+use super::to_markdown::to_markdown;
+use crate::_pdf_utils::metadata::{DocumentMetadata, dominant_lang};
+use crate::toc::TocNode;
+
+/// A chapter written to its own Markdown file
+struct Chapter {
+    file_name: String,
+    title: String,
+    level: u8,
+}
+
+///
+/// Write captured pages as an mdBook: `book.toml`, `src/SUMMARY.md`, one Markdown file
+/// per page and the downloaded images in `src/assets`
+///
+pub async fn write_markdown(
+    toc: &[TocNode],
+    output: &Path,
+    metadata: &DocumentMetadata,
+) -> Result<()> {
+    let src = output.join("src");
+    fs::create_dir_all(&src).with_context(|| format!("Failed to create {}", src.display()))?;
+
+    let file_names = chapter_file_names(toc);
+    let chapters_by_key = chapter_index(toc);
+    let mut assets = AssetStore::default();
+    let mut chapters = Vec::new();
+
+    for (index, node) in toc.iter().enumerate() {
+        let Some(path) = node.file_path.as_ref() else {
+            continue;
+        };
+        let title = node.title.clone().unwrap_or_else(|| node.href.clone());
+        println!("  📝 Converting {}", title);
+
+        let page = CapturedPage::load(path)?;
+
+        let chapter_link = |target: usize, fragment: Option<&str>| {
+            let file = if target == index {
+                ""
+            } else {
+                file_names[target].as_str()
+            };
+            match fragment {
+                Some(fragment) => format!("{}#{}", file, fragment),
+                None => file_names[target].clone(),
+            }
+        };
+        let mut urls = chapter_links(&chapters_by_key, &page.links(), chapter_link);
+        for src in page.images() {
+            if let Some(asset) = assets.fetch(&src).await {
+                urls.insert(src, format!("assets/{}", assets.get(asset).name));
+            }
+        }
+
+        let mut markdown = to_markdown(page.body(), &urls);
+        if !page.has_element("body h1") {
+            markdown = format!("# {}\n\n{}", title, markdown);
+        }
+        markdown.push('\n');
+
+        let file_path = src.join(&file_names[index]);
+        fs::write(&file_path, markdown)
+            .with_context(|| format!("Failed to write {}", file_path.display()))?;
+
+        chapters.push(Chapter {
+            file_name: file_names[index].clone(),
+            title,
+            level: node.level,
+        });
+    }
+
+    if chapters.is_empty() {
+        anyhow::bail!("No pages were captured");
+    }
+
+    if !assets.assets().is_empty() {
+        let assets_dir = src.join("assets");
+        fs::create_dir_all(&assets_dir)?;
+        for asset in assets.assets() {
+            fs::write(assets_dir.join(&asset.name), &asset.data)?;
+        }
+    }
+
+    fs::write(src.join("SUMMARY.md"), summary(&chapters))?;
+
+    let lang = metadata
+        .lang
+        .clone()
+        .or_else(|| dominant_lang(toc.iter().filter_map(|n| n.lang.as_deref())))
+        .unwrap_or_else(|| "en".to_string());
+    fs::write(output.join("book.toml"), book_toml(metadata, &lang))?;
+
+    println!(
+        "  ✅ Markdown saved to {} ({} chapters, {} assets)",
+        output.display(),
+        chapters.len(),
+        assets.assets().len()
+    );
+
+    Ok(())
+}
+
+///
+/// File names from the chapter titles, in the order of the TOC and unique within the book
+///
+fn chapter_file_names(toc: &[TocNode]) -> Vec<String> {
+    let mut used = HashSet::new();
+    // Reserved by mdBook
+    used.insert("SUMMARY".to_string());
+
+    toc.iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let mut slug = slugify(node.title.as_deref().unwrap_or_default());
+            if slug.is_empty() {
+                slug = format!("chapter-{}", i + 1);
+            }
+
+            let mut name = slug.clone();
+            let mut suffix = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{}-{}", slug, suffix);
+                suffix += 1;
+            }
+            format!("{}.md", name)
+        })
+        .collect()
+}
+
+fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 60 {
+            break;
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+///
+/// mdBook `SUMMARY.md`, a chapter is nested at most one level deeper than the previous one
+///
+fn summary(chapters: &[Chapter]) -> String {
+    let mut out = String::from("# Summary\n\n");
+    let mut depth = 0usize;
+    for (i, chapter) in chapters.iter().enumerate() {
+        depth = (chapter.level as usize).min(if i == 0 { 0 } else { depth + 1 });
+        let title = chapter.title.replace('[', "\\[").replace(']', "\\]");
+        out.push_str(&format!(
+            "{}- [{}]({})\n",
+            "    ".repeat(depth),
+            title,
+            chapter.file_name
+        ));
+    }
+    out
+}
+
+fn book_toml(metadata: &DocumentMetadata, lang: &str) -> String {
+    let mut out = String::from("[book]\n");
+    if let Some(title) = &metadata.title {
+        out.push_str(&format!("title = {}\n", toml_string(title)));
+    }
+    if let Some(author) = &metadata.author {
+        out.push_str(&format!("authors = [{}]\n", toml_string(author)));
+    }
+    if let Some(subject) = &metadata.subject {
+        out.push_str(&format!("description = {}\n", toml_string(subject)));
+    }
+    out.push_str(&format!("language = {}\n", toml_string(lang)));
+    out.push_str("src = \"src\"\n");
+    out
+}
+
+fn toml_string(value: &str) -> String {
+    let escaped: String = value
+        .chars()
+        .flat_map(|c| match c {
+            '"' => vec!['\\', '"'],
+            '\\' => vec!['\\', '\\'],
+            '\n' => vec!['\\', 'n'],
+            c if c.is_control() => vec![],
+            c => vec![c],
+        })
+        .collect();
+    format!("\"{}\"", escaped)
+}
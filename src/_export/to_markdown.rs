@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+
+use scraper::{ElementRef, Node};
+
+/// Elements that start a new block, everything else is rendered inline
+const BLOCK_ELEMENTS: [&str; 32] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+
+/// Elements without readable content
+const SKIPPED_ELEMENTS: [&str; 8] = [
+    "script", "style", "template", "svg", "button", "input", "select", "textarea",
+];
+
+/// A rendered block, lists are kept tight inside list items
+struct Block {
+    text: String,
+    is_list: bool,
+}
+
+///
+/// Convert the children of an element to Markdown, `src` of images and `href` of links are replaced from `urls`
+///
+pub fn to_markdown(element: ElementRef, urls: &HashMap<String, String>) -> String {
+    let converter = Converter { urls };
+    let blocks = converter.blocks(element);
+    join_blocks(&blocks, "\n\n")
+}
+
+struct Converter<'a> {
+    urls: &'a HashMap<String, String>,
+}
+
+impl Converter<'_> {
+    fn blocks(&self, element: ElementRef) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut inline = String::new();
+
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => inline.push_str(&escape(&collapse_whitespace(text))),
+                Node::Element(value) => {
+                    let Some(child) = ElementRef::wrap(child) else {
+                        continue;
+                    };
+                    let name = value.name();
+                    if SKIPPED_ELEMENTS.contains(&name) {
+                        continue;
+                    }
+                    if BLOCK_ELEMENTS.contains(&name) {
+                        flush_paragraph(&mut inline, &mut blocks);
+                        if let Some(block) = self.block(child) {
+                            blocks.push(block);
+                        }
+                    } else {
+                        inline.push_str(&self.inline(child));
+                    }
+                }
+                _ => {}
+            }
+        }
+        flush_paragraph(&mut inline, &mut blocks);
+
+        blocks
+    }
+
+    fn block(&self, element: ElementRef) -> Option<Block> {
+        let name = element.value().name();
+        let text = match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                let text = self.heading_text(element);
+                if text.is_empty() {
+                    return None;
+                }
+                format!("{} {}", "#".repeat(level), text)
+            }
+            "p" | "dt" | "figcaption" | "summary" => self.inline_children(element),
+            "pre" => code_block(element),
+            "ul" | "ol" => {
+                return self.list(element).map(|text| Block {
+                    text,
+                    is_list: true,
+                });
+            }
+            "blockquote" => prefix_lines(&join_blocks(&self.blocks(element), "\n\n"), "> "),
+            "table" => self.table(element)?,
+            "hr" => "---".to_string(),
+            _ => join_blocks(&self.blocks(element), "\n\n"),
+        };
+
+        let text = text.trim_matches('\n').trim_end().to_string();
+        (!text.is_empty()).then_some(Block {
+            text,
+            is_list: false,
+        })
+    }
+
+    fn inline(&self, element: ElementRef) -> String {
+        let value = element.value();
+        match value.name() {
+            "br" => "  \n".to_string(),
+            "img" => {
+                let Some(src) = value.attr("src") else {
+                    return String::new();
+                };
+                let src = self.urls.get(src).map_or(src, String::as_str);
+                format!(
+                    "![{}]({})",
+                    escape(value.attr("alt").unwrap_or_default()),
+                    link_destination(src)
+                )
+            }
+            "strong" | "b" => wrap(&self.inline_children(element), "**"),
+            "em" | "i" => wrap(&self.inline_children(element), "*"),
+            "del" | "s" | "strike" => wrap(&self.inline_children(element), "~~"),
+            "code" | "kbd" | "samp" => code_span(&element.text().collect::<String>()),
+            "a" => {
+                let text = self.inline_children(element);
+                match value.attr("href") {
+                    Some(href) if !text.trim().is_empty() => {
+                        let href = self.urls.get(href).map_or(href, String::as_str);
+                        format!("[{}]({})", text.trim(), link_destination(href))
+                    }
+                    _ => text,
+                }
+            }
+            name if SKIPPED_ELEMENTS.contains(&name) => String::new(),
+            _ => self.inline_children(element),
+        }
+    }
+
+    ///
+    /// Render all content of an element on one line, blocks inside inline elements are flattened
+    ///
+    fn inline_children(&self, element: ElementRef) -> String {
+        let mut out = String::new();
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => out.push_str(&escape(&collapse_whitespace(text))),
+                Node::Element(_) => {
+                    if let Some(child) = ElementRef::wrap(child) {
+                        out.push_str(&self.inline(child));
+                    }
+                }
+                _ => {}
+            }
+        }
+        collapse_spaces(&out)
+    }
+
+    ///
+    /// Heading text without links, sites often wrap headings in self-links
+    ///
+    fn heading_text(&self, element: ElementRef) -> String {
+        let text = self.inline_children(element);
+        strip_links(&text).replace("  \n", " ").trim().to_string()
+    }
+
+    fn list(&self, element: ElementRef) -> Option<String> {
+        let ordered = element.value().name() == "ol";
+        let mut number: usize = element
+            .value()
+            .attr("start")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+
+        let mut items = Vec::new();
+        for item in element.child_elements() {
+            if item.value().name() != "li" {
+                continue;
+            }
+            let marker = if ordered {
+                format!("{}. ", number)
+            } else {
+                "- ".to_string()
+            };
+            number += 1;
+
+            let blocks = self.blocks(item);
+            let mut content = String::new();
+            for (i, block) in blocks.iter().enumerate() {
+                if i > 0 {
+                    content.push_str(if block.is_list { "\n" } else { "\n\n" });
+                }
+                content.push_str(&block.text);
+            }
+            let indent = " ".repeat(marker.len());
+            let content = prefix_lines(&content, &indent);
+            items.push(format!(
+                "{}{}",
+                marker,
+                &content[indent.len().min(content.len())..]
+            ));
+        }
+
+        (!items.is_empty()).then(|| items.join("\n"))
+    }
+
+    fn table(&self, element: ElementRef) -> Option<String> {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        for child in element.child_elements() {
+            let row_elements: Vec<ElementRef> = match child.value().name() {
+                "tr" => vec![child],
+                "thead" | "tbody" | "tfoot" => child
+                    .child_elements()
+                    .filter(|e| e.value().name() == "tr")
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for row in row_elements {
+                let cells = row
+                    .child_elements()
+                    .filter(|e| matches!(e.value().name(), "td" | "th"))
+                    .map(|cell| {
+                        self.inline_children(cell)
+                            .replace("  \n", "<br>")
+                            .replace('\n', " ")
+                            .replace('|', "\\|")
+                            .trim()
+                            .to_string()
+                    })
+                    .collect();
+                rows.push(cells);
+            }
+        }
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return None;
+        }
+
+        let format_row = |cells: &[String]| {
+            let mut line = String::from("|");
+            for i in 0..columns {
+                line.push(' ');
+                line.push_str(cells.get(i).map_or("", String::as_str));
+                line.push_str(" |");
+            }
+            line
+        };
+
+        let mut lines = vec![format_row(&rows[0])];
+        lines.push(format!("|{}", " --- |".repeat(columns)));
+        lines.extend(rows[1..].iter().map(|row| format_row(row)));
+        Some(lines.join("\n"))
+    }
+}
+
+fn flush_paragraph(inline: &mut String, blocks: &mut Vec<Block>) {
+    let text = collapse_spaces(inline).trim().to_string();
+    if !text.is_empty() {
+        blocks.push(Block {
+            text,
+            is_list: false,
+        });
+    }
+    inline.clear();
+}
+
+fn join_blocks(blocks: &[Block], separator: &str) -> String {
+    blocks
+        .iter()
+        .map(|block| block.text.as_str())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+///
+/// Fenced code block, the language comes from a `language-*` or `lang-*` class of `pre` or `code`
+///
+fn code_block(element: ElementRef) -> String {
+    let code = element
+        .child_elements()
+        .find(|e| e.value().name() == "code");
+    let language = std::iter::once(element)
+        .chain(code)
+        .flat_map(|e| e.value().classes())
+        .find_map(|class| {
+            class
+                .strip_prefix("language-")
+                .or_else(|| class.strip_prefix("lang-"))
+        })
+        .unwrap_or_default();
+
+    let text: String = element.text().collect();
+    let text = text.trim_end_matches('\n');
+    let mut fence = "```".to_string();
+    while text.contains(&fence) {
+        fence.push('`');
+    }
+    format!("{fence}{language}\n{text}\n{fence}")
+}
+
+fn code_span(text: &str) -> String {
+    let text = collapse_whitespace(text);
+    if text.trim().is_empty() {
+        return String::new();
+    }
+    let mut ticks = "`".to_string();
+    while text.contains(&ticks) {
+        ticks.push('`');
+    }
+    let padding = if text.starts_with('`') || text.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{ticks}{padding}{text}{padding}{ticks}")
+}
+
+///
+/// Wrap text in emphasis markers, surrounding spaces are moved outside of the markers
+///
+fn wrap(text: &str, marker: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let before = if text.starts_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    let after = if text.ends_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    format!("{before}{marker}{trimmed}{marker}{after}")
+}
+
+fn link_destination(url: &str) -> String {
+    if url.contains([' ', '(', ')']) {
+        format!("<{}>", url.replace('>', "%3E"))
+    } else {
+        url.to_string()
+    }
+}
+
+///
+/// Replace `[text](url)` with `text`, images are kept
+///
+fn strip_links(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        let is_image = start > 0 && rest.as_bytes()[start - 1] == b'!';
+        let escaped = start > 0 && rest.as_bytes()[start - 1] == b'\\';
+        let link = rest[start..].find("](").and_then(|middle| {
+            let end = rest[start + middle..].find(')')?;
+            Some((start + middle, start + middle + end))
+        });
+        match link {
+            Some((middle, end)) if !is_image && !escaped => {
+                out.push_str(&rest[..start]);
+                out.push_str(&rest[start + 1..middle]);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn prefix_lines(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                prefix.trim_end().to_string()
+            } else {
+                format!("{}{}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !space {
+                out.push(' ');
+            }
+            space = true;
+        } else {
+            out.push(c);
+            space = false;
+        }
+    }
+    out
+}
+
+///
+/// Collapse spaces left between inline elements, hard line breaks are kept
+///
+fn collapse_spaces(text: &str) -> String {
+    let parts: Vec<&str> = text.split("  \n").collect();
+    let last = parts.len() - 1;
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            let mut out = String::with_capacity(part.len());
+            for c in part.chars() {
+                if !(c == ' ' && out.ends_with(' ')) {
+                    out.push(c);
+                }
+            }
+            let out = if i > 0 { out.trim_start() } else { &out };
+            let out = if i < last { out.trim_end() } else { out };
+            out.to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("  \n")
+}
+
+///
+/// Escape characters that Markdown would treat as formatting
+///
+fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let escape = match c {
+            '\\' | '*' | '`' | '[' | ']' | '<' => true,
+            // Underscores inside words don't start emphasis
+            '_' => {
+                let before = i.checked_sub(1).and_then(|i| chars.get(i));
+                let after = chars.get(i + 1);
+                !(before.is_some_and(|c| c.is_alphanumeric())
+                    && after.is_some_and(|c| c.is_alphanumeric()))
+            }
+            _ => false,
+        };
+        if escape {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use scraper::Html;
+
+    use super::to_markdown;
+
+    fn convert(html: &str) -> String {
+        convert_with_urls(html, &HashMap::new())
+    }
+
+    fn convert_with_urls(html: &str, urls: &HashMap<String, String>) -> String {
+        let fragment = Html::parse_fragment(html);
+        to_markdown(fragment.root_element(), urls)
+    }
+
+    #[test]
+    fn headings_drop_self_links() {
+        assert_eq!(
+            convert(r##"<h1>Title</h1><h3><a href="#usage">Usage</a> <em>notes</em></h3>"##),
+            "# Title\n\n### Usage *notes*"
+        );
+        assert_eq!(convert("<h2> </h2><p>Text</p>"), "Text");
+    }
+
+    #[test]
+    fn nested_lists_are_indented_under_their_item() {
+        let html = r#"
+            <ul>
+                <li>First
+                    <ol start="3"><li>Three</li><li><p>Four</p><p>More</p></li></ol>
+                </li>
+                <li>Second</li>
+            </ul>"#;
+        assert_eq!(
+            convert(html),
+            "- First\n  3. Three\n  4. Four\n\n     More\n- Second"
+        );
+    }
+
+    #[test]
+    fn tables_get_a_header_separator() {
+        let html = r#"
+            <table>
+                <thead><tr><th>Name</th><th>Value</th></tr></thead>
+                <tbody>
+                    <tr><td>a|b</td><td>1<br>2</td></tr>
+                    <tr><td>only</td></tr>
+                </tbody>
+            </table>"#;
+        assert_eq!(
+            convert(html),
+            "| Name | Value |\n| --- | --- |\n| a\\|b | 1<br>2 |\n| only |  |"
+        );
+    }
+
+    #[test]
+    fn code_blocks_are_fenced_with_the_language() {
+        let html =
+            "<pre><code class=\"language-rust\">fn main() {\n    let _ = 1;\n}\n</code></pre>";
+        assert_eq!(
+            convert(html),
+            "```rust\nfn main() {\n    let _ = 1;\n}\n```"
+        );
+
+        // The fence is longer than any backtick run of the code
+        let html = "<pre class=\"lang-md\">```\ncode\n```</pre>";
+        assert_eq!(convert(html), "````md\n```\ncode\n```\n````");
+
+        assert_eq!(convert("<p>Run <code>a `b`</code></p>"), "Run `` a `b` ``");
+    }
+
+    #[test]
+    fn markdown_characters_are_escaped() {
+        assert_eq!(
+            convert("<p>*not bold* [x] a_b _c_ 1\\2 &lt;tag&gt;</p>"),
+            "\\*not bold\\* \\[x\\] a_b \\_c\\_ 1\\\\2 \\<tag>"
+        );
+    }
+
+    #[test]
+    fn links_and_images_are_rewritten_from_urls() {
+        let urls = HashMap::from([
+            (
+                "https://example.com/guide/next.html".to_string(),
+                "#next".to_string(),
+            ),
+            ("/img/logo.png".to_string(), "images/logo.png".to_string()),
+        ]);
+        let html = r#"<p>See <a href="https://example.com/guide/next.html">next page</a>,
+            <a href="https://other.org/a (b)">other</a> and
+            <img src="/img/logo.png" alt="Logo [v2]"><a href="/empty"> </a></p>"#;
+        assert_eq!(
+            convert_with_urls(html, &urls),
+            "See [next page](#next), [other](<https://other.org/a (b)>) and \
+             ![Logo \\[v2\\]](images/logo.png)"
+        );
+    }
+}
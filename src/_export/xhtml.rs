@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use scraper::{ElementRef, Node};
+
+use crate::escape::{escape_attr, escape_text};
+
+/// Elements without content and closing tag
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Elements whose text is not escaped in HTML
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["style", "script"];
+
+/// Output syntax of the serializer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    /// Well-formed XML as required by EPUB content documents
+    Xhtml,
+    /// Plain HTML5
+    Html,
+}
+
+/// How URLs and ids of a page are changed while it is serialized
+#[derive(Debug, Clone, Copy)]
+pub struct Rewrite<'a> {
+    /// Replacements for `src` of images and `href` of links
+    pub urls: &'a HashMap<String, String>,
+    /// Prefix of every `id` and local reference, keeps ids unique when pages share a document
+    pub id_prefix: Option<&'a str>,
+}
+
+///
+/// Serialize the children of an element with the URLs and ids changed by `rewrite`
+///
+/// Images without a replacement keep their source in HTML and are replaced by the alt text
+/// in XHTML, because EPUB doesn't allow remote images.
+///
+pub fn serialize_children(element: ElementRef, markup: Markup, rewrite: Rewrite) -> String {
+    let mut out = String::new();
+    for child in element.children() {
+        match child.value() {
+            Node::Text(text) => {
+                let raw =
+                    markup == Markup::Html && RAW_TEXT_ELEMENTS.contains(&element.value().name());
+                if raw {
+                    out.push_str(text);
+                } else {
+                    out.push_str(&escape_text(text));
+                }
+            }
+            Node::Element(_) => {
+                if let Some(child) = ElementRef::wrap(child) {
+                    serialize_element(child, markup, rewrite, &mut out);
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn serialize_element(element: ElementRef, markup: Markup, rewrite: Rewrite, out: &mut String) {
+    let value = element.value();
+    let name = value.name();
+
+    if name == "img" {
+        let replaced = value.attr("src").and_then(|src| rewrite.urls.get(src));
+        if replaced.is_none() && markup == Markup::Xhtml {
+            out.push_str(&escape_text(value.attr("alt").unwrap_or_default()));
+            return;
+        }
+    }
+
+    out.push('<');
+    out.push_str(name);
+    match name {
+        "svg" if markup == Markup::Xhtml => out.push_str(r#" xmlns="http://www.w3.org/2000/svg""#),
+        "math" if markup == Markup::Xhtml => {
+            out.push_str(r#" xmlns="http://www.w3.org/1998/Math/MathML""#)
+        }
+        _ => {}
+    }
+
+    for (attr, attr_value) in value.attrs() {
+        // Namespaced and framework attributes (xlink:href, @click, :class) aren't valid XML names
+        if markup == Markup::Xhtml && (!is_xml_name(attr) || attr.contains(':')) {
+            continue;
+        }
+        if attr == "xmlns" {
+            continue;
+        }
+
+        let rewritten = match (name, attr) {
+            ("img", "src") | ("a", "href") => rewrite.urls.get(attr_value).cloned(),
+            _ => None,
+        };
+        let prefixed = rewrite.id_prefix.and_then(|prefix| match attr {
+            "id" | "for" => Some(format!("{}{}", prefix, attr_value)),
+            "href" => attr_value
+                .strip_prefix('#')
+                .filter(|id| !id.is_empty())
+                .map(|id| format!("#{}{}", prefix, id)),
+            _ => None,
+        });
+        out.push(' ');
+        out.push_str(attr);
+        out.push_str("=\"");
+        out.push_str(&escape_attr(
+            rewritten.or(prefixed).as_deref().unwrap_or(attr_value),
+        ));
+        out.push('"');
+    }
+
+    if VOID_ELEMENTS.contains(&name) {
+        out.push_str(if markup == Markup::Xhtml { "/>" } else { ">" });
+        return;
+    }
+
+    out.push('>');
+    out.push_str(&serialize_children(element, markup, rewrite));
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+}
+
+/// An entry of a generated table of contents
+pub struct NavEntry<'a> {
+    pub href: &'a str,
+    pub title: &'a str,
+    pub level: u8,
+}
+
+///
+/// Build nested lists from the TOC levels, an entry is nested at most one level deeper than the previous one
+///
+pub fn nav_list(entries: &[NavEntry]) -> String {
+    let mut list = String::from("<ol>\n");
+    let mut depth = 0usize;
+    for (i, entry) in entries.iter().enumerate() {
+        let level = (entry.level as usize).min(depth + usize::from(i > 0));
+        if i > 0 {
+            if level > depth {
+                list.push_str("\n<ol>\n");
+            } else {
+                list.push_str("</li>\n");
+                for _ in level..depth {
+                    list.push_str("</ol>\n</li>\n");
+                }
+            }
+        }
+        depth = level;
+        list.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            escape_attr(entry.href),
+            escape_text(entry.title)
+        ));
+    }
+    if !entries.is_empty() {
+        list.push_str("</li>\n");
+    }
+    for _ in 0..depth {
+        list.push_str("</ol>\n</li>\n");
+    }
+    list.push_str("</ol>\n");
+    list
+}
+
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
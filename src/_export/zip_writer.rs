@@ -0,0 +1,256 @@
+use std::io::{self, Write};
+
+use flate2::{Compression, write::DeflateEncoder};
+
+/// DOS date of 1980-01-01, all entries get it so the archive doesn't depend on the build time
+const DOS_DATE: u16 = (1 << 5) | 1;
+const DOS_TIME: u16 = 0;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// General purpose flag: the entry name is UTF-8
+const FLAG_UTF8: u16 = 1 << 11;
+
+struct CentralEntry {
+    name: String,
+    name_len: u16,
+    flags: u16,
+    method: u16,
+    crc: u32,
+    compressed_size: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Minimal ZIP writer for EPUB containers, entries are written in the order they are added
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: u32,
+    entries: Vec<CentralEntry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    ///
+    /// Add an uncompressed entry, EPUB requires it for the `mimetype` file
+    ///
+    pub fn add_stored(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.add_entry(name, METHOD_STORED, data, data.to_vec())
+    }
+
+    pub fn add_deflated(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        self.add_entry(name, METHOD_DEFLATED, data, compressed)
+    }
+
+    fn add_entry(
+        &mut self,
+        name: &str,
+        method: u16,
+        data: &[u8],
+        compressed: Vec<u8>,
+    ) -> io::Result<()> {
+        let entry = CentralEntry {
+            name: name.to_string(),
+            name_len: u16::try_from(name.len())
+                .map_err(|_| io::Error::other(format!("entry name is too long: {}", name)))?,
+            // Without the flag readers decode non-ASCII names as CP437
+            flags: if name.is_ascii() { 0 } else { FLAG_UTF8 },
+            method,
+            crc: crc32fast::hash(data),
+            compressed_size: size_u32(compressed.len())?,
+            size: size_u32(data.len())?,
+            offset: self.offset,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // version needed to extract
+        header.extend_from_slice(&entry.flags.to_le_bytes());
+        header.extend_from_slice(&entry.method.to_le_bytes());
+        header.extend_from_slice(&DOS_TIME.to_le_bytes());
+        header.extend_from_slice(&DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        header.extend_from_slice(&entry.size.to_le_bytes());
+        header.extend_from_slice(&entry.name_len.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(name.as_bytes());
+
+        self.out.write_all(&header)?;
+        self.out.write_all(&compressed)?;
+        self.offset = self
+            .offset
+            .checked_add(size_u32(header.len() + compressed.len())?)
+            .ok_or_else(too_large)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    ///
+    /// Write the central directory and return the underlying writer
+    ///
+    pub fn finish(mut self) -> io::Result<W> {
+        let directory_offset = self.offset;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+            directory.extend_from_slice(&20u16.to_le_bytes()); // version needed to extract
+            directory.extend_from_slice(&entry.flags.to_le_bytes());
+            directory.extend_from_slice(&entry.method.to_le_bytes());
+            directory.extend_from_slice(&DOS_TIME.to_le_bytes());
+            directory.extend_from_slice(&DOS_DATE.to_le_bytes());
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.compressed_size.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&entry.name_len.to_le_bytes());
+            directory.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+
+        let count = u16::try_from(self.entries.len()).map_err(|_| {
+            io::Error::other("archive has more than 65535 entries, ZIP64 is not supported")
+        })?;
+        let mut end = Vec::with_capacity(22);
+        end.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        end.extend_from_slice(&[0; 4]); // disk numbers
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&size_u32(directory.len())?.to_le_bytes());
+        end.extend_from_slice(&directory_offset.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment length
+
+        self.out.write_all(&directory)?;
+        self.out.write_all(&end)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+fn size_u32(size: usize) -> io::Result<u32> {
+    u32::try_from(size).map_err(|_| too_large())
+}
+
+fn too_large() -> io::Error {
+    io::Error::other("archive is larger than 4 GB, ZIP64 is not supported")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::DeflateDecoder;
+
+    use super::{FLAG_UTF8, METHOD_DEFLATED, METHOD_STORED, ZipWriter};
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(bytes[at..at + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// Name, flags, method and content of every entry, read through the central directory
+    fn read_archive(archive: &[u8]) -> Vec<(String, u16, u16, Vec<u8>)> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), 0x0605_4b50);
+        let count = u16_at(archive, end + 10) as usize;
+        let directory_size = u32_at(archive, end + 12) as usize;
+        let mut at = u32_at(archive, end + 16) as usize;
+        assert_eq!(at + directory_size, end);
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(archive, at), 0x0201_4b50);
+            let flags = u16_at(archive, at + 8);
+            let method = u16_at(archive, at + 10);
+            let crc = u32_at(archive, at + 16);
+            let compressed_size = u32_at(archive, at + 20) as usize;
+            let size = u32_at(archive, at + 24) as usize;
+            let name_len = u16_at(archive, at + 28) as usize;
+            let offset = u32_at(archive, at + 42) as usize;
+            let name = String::from_utf8(archive[at + 46..at + 46 + name_len].to_vec()).unwrap();
+            at += 46 + name_len;
+
+            // The local header repeats the central one
+            assert_eq!(u32_at(archive, offset), 0x0403_4b50);
+            assert_eq!(u16_at(archive, offset + 6), flags);
+            assert_eq!(u16_at(archive, offset + 8), method);
+            assert_eq!(u32_at(archive, offset + 14), crc);
+            assert_eq!(u16_at(archive, offset + 26) as usize, name_len);
+            assert_eq!(
+                &archive[offset + 30..offset + 30 + name_len],
+                name.as_bytes()
+            );
+
+            let start = offset + 30 + name_len;
+            let data = &archive[start..start + compressed_size];
+            let content = match method {
+                METHOD_STORED => data.to_vec(),
+                METHOD_DEFLATED => {
+                    let mut content = Vec::new();
+                    DeflateDecoder::new(data).read_to_end(&mut content).unwrap();
+                    content
+                }
+                other => panic!("unexpected method {}", other),
+            };
+            assert_eq!(content.len(), size);
+            assert_eq!(crc32fast::hash(&content), crc);
+            entries.push((name, flags, method, content));
+        }
+        entries
+    }
+
+    #[test]
+    fn archive_round_trips() {
+        let text = "<p>Hello</p>".repeat(100);
+        let mut zip = ZipWriter::new(Vec::new());
+        zip.add_stored("mimetype", b"application/epub+zip").unwrap();
+        zip.add_deflated("OEBPS/page.xhtml", text.as_bytes())
+            .unwrap();
+        zip.add_deflated("OEBPS/images/café.png", b"").unwrap();
+        let archive = zip.finish().unwrap();
+
+        // EPUB readers expect the mimetype at a fixed offset
+        assert_eq!(&archive[30..38], b"mimetype");
+        assert_eq!(&archive[38..58], b"application/epub+zip");
+
+        let entries = read_archive(&archive);
+        assert_eq!(
+            entries,
+            [
+                (
+                    "mimetype".to_string(),
+                    0,
+                    METHOD_STORED,
+                    b"application/epub+zip".to_vec()
+                ),
+                (
+                    "OEBPS/page.xhtml".to_string(),
+                    0,
+                    METHOD_DEFLATED,
+                    text.into_bytes()
+                ),
+                (
+                    "OEBPS/images/café.png".to_string(),
+                    FLAG_UTF8,
+                    METHOD_DEFLATED,
+                    Vec::new()
+                ),
+            ]
+        );
+    }
+}
@@ -1,3 +1,15 @@
+pub mod check_pdf;
+mod dedup;
+pub mod extract_text;
+mod helpers;
 pub mod merge_pdfs;
+pub mod merge_report;
+pub mod metadata;
+mod outline;
+pub mod page_labels;
+mod pdf_writer;
+pub mod pdfa;
 mod sanitize_pdf;
-mod helpers;
+pub mod split;
+mod stamp_font;
+pub mod stamp_pages;
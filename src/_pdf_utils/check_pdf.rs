@@ -0,0 +1,881 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+use super::helpers::*;
+
+/// How many issues of a single check are printed
+const MAX_PRINTED_ISSUES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Result of a single conformance check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub issues: Vec<CheckIssue>,
+}
+
+impl CheckResult {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            issues: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.issues.push(CheckIssue {
+            severity: Severity::Error,
+            message: message.into(),
+        });
+    }
+
+    fn warning(&mut self, message: impl Into<String>) {
+        self.issues.push(CheckIssue {
+            severity: Severity::Warning,
+            message: message.into(),
+        });
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == severity)
+            .count()
+    }
+}
+
+/// PDF/UA conformance report
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    pub fn error_count(&self) -> usize {
+        self.checks.iter().map(|c| c.count(Severity::Error)).sum()
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.checks.iter().map(|c| c.count(Severity::Warning)).sum()
+    }
+
+    pub fn print(&self) {
+        for check in &self.checks {
+            let errors = check.count(Severity::Error);
+            let warnings = check.count(Severity::Warning);
+
+            if errors > 0 {
+                println!("  ❌ {}: {} error(s)", check.name, errors);
+            } else if warnings > 0 {
+                println!("  ⚠️ {}: {} warning(s)", check.name, warnings);
+            } else {
+                println!("  ✅ {}", check.name);
+            }
+
+            for issue in check.issues.iter().take(MAX_PRINTED_ISSUES) {
+                let mark = match issue.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                println!("     - [{}] {}", mark, issue.message);
+            }
+            if check.issues.len() > MAX_PRINTED_ISSUES {
+                println!(
+                    "     ... and {} more",
+                    check.issues.len() - MAX_PRINTED_ISSUES
+                );
+            }
+        }
+
+        println!(
+            "📋 Summary: {} error(s), {} warning(s)",
+            self.error_count(),
+            self.warning_count()
+        );
+    }
+}
+
+/// A structure element visited in document order
+struct StructNode {
+    id: ObjectId,
+    role: Vec<u8>,
+    page: Option<ObjectId>,
+    dict: Dictionary,
+}
+
+///
+/// Load a PDF file and check its PDF/UA conformance
+///
+pub fn check_pdf<P: AsRef<Path>>(path: P) -> Result<CheckReport> {
+    let path = path.as_ref();
+    let doc = Document::load(path).with_context(|| format!("Failed to load {:?}", path))?;
+
+    Ok(check_document(&doc))
+}
+
+///
+/// Check the tagged structure, metadata, headings, figures and links of a document
+///
+pub fn check_document(doc: &Document) -> CheckReport {
+    let mut report = CheckReport::default();
+
+    let page_numbers: HashMap<ObjectId, u32> =
+        doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+    let page_label = |page: Option<ObjectId>| {
+        page.and_then(|p| page_numbers.get(&p))
+            .map(|n| format!("page {}", n))
+            .unwrap_or_else(|| "unknown page".to_string())
+    };
+
+    report.checks.push(check_title(doc));
+    report.checks.push(check_lang(doc));
+
+    // --- Tagged structure ---
+    let mut tagged = CheckResult::new("Tagged structure");
+    let marked = doc
+        .catalog()
+        .and_then(|c| c.get(b"MarkInfo"))
+        .and_then(|o| match o {
+            Object::Reference(id) => doc.get_dictionary(*id),
+            other => other.as_dict(),
+        })
+        .and_then(|d| d.get(b"Marked"))
+        .and_then(|o| o.as_bool())
+        .unwrap_or(false);
+    if !marked {
+        tagged.error("Catalog MarkInfo/Marked is not true");
+    }
+
+    let Some(root_id) = find_struct_tree_root_id(doc) else {
+        tagged.error("StructTreeRoot not found, the document is not tagged");
+        report.checks.push(tagged);
+        return report;
+    };
+    report.checks.push(tagged);
+
+    let role_map = get_role_map(doc);
+    let parent_tree = collect_parent_tree(doc, root_id);
+
+    // Walk the structure tree in document order, inheriting Pg from parents
+    let mut node_ids = Vec::new();
+    collect_all_node_ids(doc, root_id, &mut node_ids, &mut HashSet::new());
+
+    let mut inherited_pg: HashMap<ObjectId, ObjectId> = HashMap::new();
+    let mut reachable_mcids: BTreeSet<(ObjectId, i64)> = BTreeSet::new();
+    let mut nodes = Vec::new();
+
+    for id in node_ids {
+        let Ok(dict) = doc.get_dictionary(id) else {
+            continue;
+        };
+        if dict.has_type(b"MCR") || dict.has_type(b"OBJR") {
+            continue;
+        }
+
+        let page = dict
+            .get(b"Pg")
+            .and_then(|o| o.as_reference())
+            .ok()
+            .or_else(|| inherited_pg.get(&id).copied());
+
+        for kid in struct_kids(dict) {
+            match kid {
+                Object::Integer(mcid) => {
+                    if let Some(pg) = page {
+                        reachable_mcids.insert((pg, mcid));
+                    }
+                }
+                Object::Dictionary(mcr) => {
+                    insert_mcr(&mcr, page, &mut reachable_mcids);
+                }
+                Object::Reference(kid_id) => match doc.get_dictionary(kid_id) {
+                    Ok(kid_dict) if kid_dict.has_type(b"MCR") => {
+                        insert_mcr(kid_dict, page, &mut reachable_mcids);
+                    }
+                    _ => {
+                        if let Some(pg) = page {
+                            inherited_pg.entry(kid_id).or_insert(pg);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if id == root_id {
+            continue;
+        }
+
+        let role = get_node_role(doc, id).unwrap_or_default();
+        nodes.push(StructNode {
+            id,
+            role: resolve_role(role_map.as_ref(), &role),
+            page,
+            dict: dict.clone(),
+        });
+    }
+
+    // --- ParentTree consistency ---
+    let mut parent_tree_check = CheckResult::new("ParentTree consistency");
+    let next_key = doc
+        .get_dictionary(root_id)
+        .and_then(|d| d.get(b"ParentTreeNextKey"))
+        .and_then(|o| o.as_i64())
+        .ok();
+
+    if let Some(next_key) = next_key
+        && let Some((&max_key, _)) = parent_tree.last_key_value()
+        && max_key >= next_key
+    {
+        parent_tree_check.error(format!(
+            "ParentTreeNextKey {} is not greater than the largest key {}",
+            next_key, max_key
+        ));
+    }
+
+    // --- Marked content reachability ---
+    let mut mcid_check = CheckResult::new("Marked content reachability");
+
+    for (page_num, page_id) in doc.get_pages() {
+        let content_mcids = collect_content_mcids(doc, page_id);
+        let struct_parents = doc
+            .get_dictionary(page_id)
+            .and_then(|d| d.get(b"StructParents"))
+            .and_then(|o| o.as_i64())
+            .ok();
+
+        if !content_mcids.is_empty() {
+            match struct_parents {
+                None => parent_tree_check.error(format!(
+                    "Page {} has marked content but no StructParents",
+                    page_num
+                )),
+                Some(key) => match parent_tree.get(&key) {
+                    None => parent_tree_check.error(format!(
+                        "Page {} StructParents {} has no ParentTree entry",
+                        page_num, key
+                    )),
+                    Some(entry) => check_page_entry(
+                        doc,
+                        entry,
+                        page_num,
+                        &content_mcids,
+                        &mut parent_tree_check,
+                    ),
+                },
+            }
+        }
+
+        let unreachable: Vec<i64> = content_mcids
+            .iter()
+            .copied()
+            .filter(|mcid| !reachable_mcids.contains(&(page_id, *mcid)))
+            .collect();
+        if !unreachable.is_empty() {
+            mcid_check.error(format!(
+                "Page {}: {} MCID(s) are not reachable from the structure tree: {:?}",
+                page_num,
+                unreachable.len(),
+                unreachable
+            ));
+        }
+    }
+
+    // --- Figures alternate text ---
+    let mut figure_check = CheckResult::new("Figures alternate text");
+    for node in nodes.iter().filter(|n| n.role == b"Figure") {
+        let has_alt = [b"Alt".as_slice(), b"ActualText".as_slice()]
+            .iter()
+            .any(|key| {
+                node.dict
+                    .get(key)
+                    .and_then(|o| o.as_str())
+                    .map(|s| !s.is_empty())
+                    .unwrap_or(false)
+            });
+        if !has_alt {
+            figure_check.error(format!(
+                "Figure {:?} on {} has no Alt text",
+                node.id,
+                page_label(node.page)
+            ));
+        }
+    }
+
+    // --- Heading nesting ---
+    let mut heading_check = CheckResult::new("Heading nesting");
+    let mut previous_level: Option<u8> = None;
+    for node in &nodes {
+        let Some(level) = heading_level(&node.role) else {
+            continue;
+        };
+
+        match previous_level {
+            None if level != 1 => heading_check.warning(format!(
+                "First heading is H{} instead of H1 ({})",
+                level,
+                page_label(node.page)
+            )),
+            Some(prev) if level > prev + 1 => heading_check.error(format!(
+                "Heading level skipped: H{} follows H{} ({})",
+                level,
+                prev,
+                page_label(node.page)
+            )),
+            _ => {}
+        }
+        previous_level = Some(level);
+    }
+
+    // --- Tagged links ---
+    let mut link_check = CheckResult::new("Tagged links");
+    for (&page_num, &page_id) in doc.get_pages().iter() {
+        let Ok(annotations) = doc.get_page_annotations(page_id) else {
+            continue;
+        };
+        for annot in annotations {
+            if annot.get(b"Subtype").and_then(|o| o.as_name()).ok() != Some(b"Link") {
+                continue;
+            }
+
+            let Ok(key) = annot.get(b"StructParent").and_then(|o| o.as_i64()) else {
+                link_check.error(format!(
+                    "Page {}: Link annotation has no StructParent",
+                    page_num
+                ));
+                continue;
+            };
+
+            let role = parent_tree
+                .get(&key)
+                .and_then(|o| o.as_reference().ok())
+                .and_then(|id| get_node_role(doc, id))
+                .map(|r| resolve_role(role_map.as_ref(), &r));
+            if role.as_deref() != Some(b"Link".as_slice()) {
+                link_check.error(format!(
+                    "Page {}: Link annotation (StructParent {}) is not tagged as Link",
+                    page_num, key
+                ));
+            }
+        }
+    }
+
+    report.checks.push(parent_tree_check);
+    report.checks.push(mcid_check);
+    report.checks.push(figure_check);
+    report.checks.push(heading_check);
+    report.checks.push(link_check);
+
+    report
+}
+
+fn check_title(doc: &Document) -> CheckResult {
+    let mut result = CheckResult::new("Document title");
+
+    let title = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|o| o.as_reference())
+        .and_then(|id| doc.get_dictionary(id))
+        .and_then(|info| info.get(b"Title"))
+        .and_then(|o| o.as_str())
+        .map(|s| !s.is_empty())
+        .unwrap_or(false);
+    if !title {
+        result.error("Info dictionary has no Title");
+    }
+
+    let xmp_title = doc
+        .catalog()
+        .and_then(|c| c.get(b"Metadata"))
+        .and_then(|o| o.as_reference())
+        .and_then(|id| doc.get_object(id))
+        .and_then(|o| o.as_stream())
+        .and_then(|s| s.get_plain_content())
+        .map(|xmp| String::from_utf8_lossy(&xmp).contains("<dc:title>"))
+        .unwrap_or(false);
+    if !xmp_title {
+        result.warning("XMP metadata has no dc:title");
+    }
+
+    let display_title = doc
+        .catalog()
+        .and_then(|c| c.get(b"ViewerPreferences"))
+        .and_then(|o| match o {
+            Object::Reference(id) => doc.get_dictionary(*id),
+            other => other.as_dict(),
+        })
+        .and_then(|d| d.get(b"DisplayDocTitle"))
+        .and_then(|o| o.as_bool())
+        .unwrap_or(false);
+    if !display_title {
+        result.error("ViewerPreferences/DisplayDocTitle is not true");
+    }
+
+    result
+}
+
+fn check_lang(doc: &Document) -> CheckResult {
+    let mut result = CheckResult::new("Document language");
+
+    let lang = doc
+        .catalog()
+        .and_then(|c| c.get(b"Lang"))
+        .and_then(|o| o.as_str())
+        .map(|s| !s.is_empty())
+        .unwrap_or(false);
+    if !lang {
+        result.error("Catalog has no Lang");
+    }
+
+    result
+}
+
+///
+/// Check that every MCID of a page points to a structure element which owns it
+///
+fn check_page_entry(
+    doc: &Document,
+    entry: &Object,
+    page_num: u32,
+    content_mcids: &BTreeSet<i64>,
+    result: &mut CheckResult,
+) {
+    let entry = match entry {
+        Object::Reference(id) => doc.get_object(*id).unwrap_or(entry),
+        other => other,
+    };
+    let Ok(elements) = entry.as_array() else {
+        result.error(format!(
+            "Page {}: ParentTree entry is not an array",
+            page_num
+        ));
+        return;
+    };
+
+    for &mcid in content_mcids {
+        let owner = usize::try_from(mcid)
+            .ok()
+            .and_then(|i| elements.get(i))
+            .and_then(|o| o.as_reference().ok());
+        let Some(owner_id) = owner else {
+            result.error(format!(
+                "Page {}: MCID {} has no ParentTree entry",
+                page_num, mcid
+            ));
+            continue;
+        };
+
+        let owns = doc
+            .get_dictionary(owner_id)
+            .map(|d| {
+                struct_kids(d).iter().any(|kid| match kid {
+                    Object::Integer(k) => *k == mcid,
+                    Object::Dictionary(mcr) => mcr_id(mcr) == Some(mcid),
+                    Object::Reference(id) => doc
+                        .get_dictionary(*id)
+                        .map(|d| mcr_id(d) == Some(mcid))
+                        .unwrap_or(false),
+                    _ => false,
+                })
+            })
+            .unwrap_or(false);
+        if !owns {
+            result.error(format!(
+                "Page {}: MCID {} points to {:?} which doesn't contain it",
+                page_num, mcid, owner_id
+            ));
+        }
+    }
+}
+
+///
+/// Flatten the ParentTree number tree into a map
+///
+fn collect_parent_tree(doc: &Document, root_id: ObjectId) -> BTreeMap<i64, Object> {
+    fn walk(doc: &Document, node: &Dictionary, map: &mut BTreeMap<i64, Object>, depth: u8) {
+        if depth > 32 {
+            return;
+        }
+        if let Ok(nums) = node.get(b"Nums").and_then(|o| o.as_array()) {
+            for pair in nums.chunks(2) {
+                if let [Object::Integer(k), v] = pair {
+                    map.insert(*k, v.clone());
+                }
+            }
+        }
+        if let Ok(kids) = node.get(b"Kids").and_then(|o| o.as_array()) {
+            for kid in kids {
+                if let Ok(kid) = kid.as_reference().and_then(|id| doc.get_dictionary(id)) {
+                    walk(doc, kid, map, depth + 1);
+                }
+            }
+        }
+    }
+
+    let mut map = BTreeMap::new();
+    if let Ok(parent_tree) = doc
+        .get_dictionary(root_id)
+        .and_then(|d| d.get(b"ParentTree"))
+        .and_then(|o| o.as_reference())
+        .and_then(|id| doc.get_dictionary(id))
+    {
+        walk(doc, parent_tree, &mut map, 0);
+    }
+    map
+}
+
+///
+/// Collect MCIDs of marked content sequences in the page content stream
+///
+pub fn collect_content_mcids(doc: &Document, page_id: ObjectId) -> BTreeSet<i64> {
+    let mut mcids = BTreeSet::new();
+
+    let Ok(content) = doc.get_and_decode_page_content(page_id) else {
+        return mcids;
+    };
+    let properties = doc
+        .get_page_resources(page_id)
+        .ok()
+        .and_then(|(dict, _)| dict)
+        .and_then(|r| r.get(b"Properties").ok())
+        .and_then(|o| match o {
+            Object::Reference(id) => doc.get_dictionary(*id).ok(),
+            other => other.as_dict().ok(),
+        });
+
+    for op in content.operations.iter().filter(|op| op.operator == "BDC") {
+        let props = match op.operands.get(1) {
+            Some(Object::Dictionary(d)) => Some(d),
+            Some(Object::Name(name)) => {
+                properties
+                    .and_then(|p| p.get(name).ok())
+                    .and_then(|o| match o {
+                        Object::Reference(id) => doc.get_dictionary(*id).ok(),
+                        other => other.as_dict().ok(),
+                    })
+            }
+            _ => None,
+        };
+        if let Some(mcid) = props.and_then(|p| p.get(b"MCID").and_then(|o| o.as_i64()).ok()) {
+            mcids.insert(mcid);
+        }
+    }
+
+    mcids
+}
+
+fn struct_kids(dict: &Dictionary) -> Vec<Object> {
+    match dict.get(b"K") {
+        Ok(Object::Array(arr)) => arr.clone(),
+        Ok(obj) => vec![obj.clone()],
+        Err(_) => vec![],
+    }
+}
+
+fn mcr_id(dict: &Dictionary) -> Option<i64> {
+    if !dict.has_type(b"MCR") {
+        return None;
+    }
+    dict.get(b"MCID").and_then(|o| o.as_i64()).ok()
+}
+
+fn insert_mcr(mcr: &Dictionary, page: Option<ObjectId>, mcids: &mut BTreeSet<(ObjectId, i64)>) {
+    let pg = mcr.get(b"Pg").and_then(|o| o.as_reference()).ok().or(page);
+    if let (Some(pg), Some(mcid)) = (pg, mcr_id(mcr)) {
+        mcids.insert((pg, mcid));
+    }
+}
+
+fn heading_level(role: &[u8]) -> Option<u8> {
+    match role {
+        [b'H', d @ b'1'..=b'6'] => Some(d - b'0'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Dictionary, Document, Object, ObjectId, Stream, dictionary};
+
+    use super::{CheckReport, Severity, check_document};
+
+    const INFO: ObjectId = (1, 0);
+    const CATALOG: ObjectId = (3, 0);
+    const STRUCT_TREE_ROOT: ObjectId = (10, 0);
+    const PARENT_TREE: ObjectId = (12, 0);
+    const H2: ObjectId = (15, 0);
+    const FIGURE: ObjectId = (14, 0);
+    const LINK: ObjectId = (16, 0);
+
+    ///
+    /// A single page document which passes every check: H1, Figure, H2 and Link elements
+    /// own one marked content sequence each, the link annotation is tagged as well
+    ///
+    fn conforming_document() -> Document {
+        let page: ObjectId = (5, 0);
+        let mut doc = Document::with_version("1.7");
+        let objects = [
+            (
+                INFO,
+                Object::Dictionary(dictionary! { "Title" => Object::string_literal("Guide") }),
+            ),
+            (
+                (2, 0),
+                Object::Stream(Stream::new(
+                    dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+                    b"<x:xmpmeta><dc:title>Guide</dc:title></x:xmpmeta>".to_vec(),
+                )),
+            ),
+            (
+                CATALOG,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Catalog",
+                    "Pages" => (4, 0),
+                    "Lang" => Object::string_literal("en"),
+                    "MarkInfo" => dictionary! { "Marked" => true },
+                    "ViewerPreferences" => dictionary! { "DisplayDocTitle" => true },
+                    "Metadata" => (2, 0),
+                    "StructTreeRoot" => STRUCT_TREE_ROOT,
+                }),
+            ),
+            (
+                (4, 0),
+                Object::Dictionary(
+                    dictionary! { "Type" => "Pages", "Kids" => vec![page.into()], "Count" => 1 },
+                ),
+            ),
+            (
+                page,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => (4, 0),
+                    "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                    "Contents" => (6, 0),
+                    "StructParents" => 0,
+                    "Annots" => vec![Object::Reference((7, 0))],
+                }),
+            ),
+            (
+                (6, 0),
+                Object::Stream(Stream::new(
+                    Dictionary::new(),
+                    b"/H1 <</MCID 0>> BDC EMC /Figure <</MCID 1>> BDC EMC \
+                      /H2 <</MCID 2>> BDC EMC /Link <</MCID 3>> BDC EMC"
+                        .to_vec(),
+                )),
+            ),
+            (
+                (7, 0),
+                Object::Dictionary(dictionary! {
+                    "Type" => "Annot",
+                    "Subtype" => "Link",
+                    "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                    "StructParent" => 1,
+                }),
+            ),
+            (
+                STRUCT_TREE_ROOT,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructTreeRoot",
+                    "K" => (11, 0),
+                    "ParentTree" => PARENT_TREE,
+                    "ParentTreeNextKey" => 2,
+                }),
+            ),
+            (
+                (11, 0),
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Document",
+                    "Pg" => page,
+                    "K" => vec![(13, 0).into(), FIGURE.into(), H2.into(), LINK.into()],
+                }),
+            ),
+            (
+                PARENT_TREE,
+                Object::Dictionary(dictionary! {
+                    "Nums" => vec![
+                        0.into(),
+                        vec![(13, 0).into(), FIGURE.into(), H2.into(), LINK.into()].into(),
+                        1.into(),
+                        LINK.into(),
+                    ],
+                }),
+            ),
+            (
+                (13, 0),
+                Object::Dictionary(dictionary! { "Type" => "StructElem", "S" => "H1", "K" => 0 }),
+            ),
+            (
+                FIGURE,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Figure",
+                    "Alt" => Object::string_literal("Logo"),
+                    "K" => 1,
+                }),
+            ),
+            (
+                H2,
+                Object::Dictionary(dictionary! { "Type" => "StructElem", "S" => "H2", "K" => 2 }),
+            ),
+            (
+                LINK,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Link",
+                    "K" => vec![3.into(), dictionary! { "Type" => "OBJR", "Obj" => (7, 0) }.into()],
+                }),
+            ),
+        ];
+        for (id, object) in objects {
+            doc.objects.insert(id, object);
+        }
+        doc.max_id = 16;
+        doc.trailer.set("Root", CATALOG);
+        doc.trailer.set("Info", INFO);
+        doc
+    }
+
+    fn dict(doc: &mut Document, id: ObjectId) -> &mut Dictionary {
+        doc.get_object_mut(id).unwrap().as_dict_mut().unwrap()
+    }
+
+    /// Checks which reported errors
+    fn failed(report: &CheckReport) -> Vec<&'static str> {
+        report
+            .checks
+            .iter()
+            .filter(|c| c.issues.iter().any(|i| i.severity == Severity::Error))
+            .map(|c| c.name)
+            .collect()
+    }
+
+    #[test]
+    fn conforming_document_passes() {
+        let report = check_document(&conforming_document());
+
+        assert_eq!(report.checks.len(), 8);
+        assert_eq!(report.error_count(), 0);
+        assert_eq!(report.warning_count(), 0);
+    }
+
+    #[test]
+    fn missing_title_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, INFO).remove(b"Title");
+
+        assert_eq!(failed(&check_document(&doc)), ["Document title"]);
+    }
+
+    #[test]
+    fn missing_lang_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, CATALOG).remove(b"Lang");
+
+        assert_eq!(failed(&check_document(&doc)), ["Document language"]);
+    }
+
+    #[test]
+    fn unmarked_document_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, CATALOG).set("MarkInfo", dictionary! { "Marked" => false });
+
+        assert_eq!(failed(&check_document(&doc)), ["Tagged structure"]);
+    }
+
+    #[test]
+    fn parent_tree_pointing_to_another_element_fails() {
+        let mut doc = conforming_document();
+        // MCID 0 and 1 are swapped
+        dict(&mut doc, PARENT_TREE).set(
+            "Nums",
+            vec![
+                0.into(),
+                vec![FIGURE.into(), (13, 0).into(), H2.into(), LINK.into()].into(),
+                1.into(),
+                LINK.into(),
+            ],
+        );
+
+        let report = check_document(&doc);
+        assert_eq!(failed(&report), ["ParentTree consistency"]);
+        assert_eq!(report.error_count(), 2);
+    }
+
+    #[test]
+    fn stale_parent_tree_next_key_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, STRUCT_TREE_ROOT).set("ParentTreeNextKey", 1);
+
+        assert_eq!(failed(&check_document(&doc)), ["ParentTree consistency"]);
+    }
+
+    #[test]
+    fn unreachable_mcid_fails() {
+        let mut doc = conforming_document();
+        // The ParentTree still points to H2, so its entry is inconsistent too
+        dict(&mut doc, H2).set("K", Vec::<Object>::new());
+
+        let report = check_document(&doc);
+        assert_eq!(
+            failed(&report),
+            ["ParentTree consistency", "Marked content reachability"]
+        );
+        let reachability = report
+            .checks
+            .iter()
+            .find(|c| c.name == "Marked content reachability")
+            .unwrap();
+        assert!(reachability.issues[0].message.ends_with("[2]"));
+    }
+
+    #[test]
+    fn figure_without_alt_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, FIGURE).remove(b"Alt");
+
+        assert_eq!(failed(&check_document(&doc)), ["Figures alternate text"]);
+
+        // ActualText is accepted instead
+        dict(&mut doc, FIGURE).set("ActualText", Object::string_literal("Logo"));
+        assert_eq!(check_document(&doc).error_count(), 0);
+    }
+
+    #[test]
+    fn skipped_heading_level_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, H2).set("S", "H3");
+
+        assert_eq!(failed(&check_document(&doc)), ["Heading nesting"]);
+    }
+
+    #[test]
+    fn untagged_link_fails() {
+        let mut doc = conforming_document();
+        dict(&mut doc, LINK).set("S", "Span");
+
+        assert_eq!(failed(&check_document(&doc)), ["Tagged links"]);
+
+        // A role mapped to Link is fine
+        dict(&mut doc, STRUCT_TREE_ROOT).set("RoleMap", dictionary! { "Span" => "Link" });
+        assert_eq!(check_document(&doc).error_count(), 0);
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat};
+
+/// Dictionary types which are plain shared resources, their identity doesn't matter
+const SHARED_DICT_TYPES: [&[u8]; 6] = [
+    b"Font",
+    b"FontDescriptor",
+    b"ExtGState",
+    b"Pattern",
+    b"Shading",
+    b"XObject",
+];
+
+/// Each round can only merge objects whose children were merged in the previous one
+const MAX_ROUNDS: usize = 8;
+
+/// Result of the deduplication pass
+#[derive(Debug, Default)]
+pub struct DedupReport {
+    /// Number of removed duplicate objects
+    pub removed: usize,
+    /// Encoded size of the removed streams
+    pub bytes_saved: usize,
+}
+
+impl DedupReport {
+    pub fn print(&self) {
+        if self.removed > 0 {
+            println!(
+                "  ♻️ Deduplicated {} shared object(s), saved {:.1} KB",
+                self.removed,
+                self.bytes_saved as f64 / 1024.0
+            );
+        }
+    }
+}
+
+/// Replaces identical streams (fonts, images, ICC profiles, XObjects) and shared resource
+/// dictionaries with one canonical copy while chapters are merged one by one
+///
+/// Objects are looked up by a content hash and a match is confirmed by comparing the objects,
+/// so the canonical copies of written objects are kept. Page content streams are never shared
+/// and are not kept.
+#[derive(Debug, Default)]
+pub struct Deduplicator {
+    seen: HashMap<u64, Vec<(ObjectId, Object)>>,
+    report: DedupReport,
+}
+
+impl Deduplicator {
+    ///
+    /// Remove objects of the chapter which repeat an earlier object and rewrite references to it
+    ///
+    pub fn dedup_chapter(&mut self, doc: &mut Document) {
+        let contents = page_contents(doc);
+
+        for round in 0..MAX_ROUNDS {
+            let mut local: HashMap<u64, Vec<ObjectId>> = HashMap::new();
+            let mut replacements = BTreeMap::new();
+
+            // Objects are visited in id order, so the first copy is always the canonical one
+            // and the output doesn't depend on hash map ordering
+            for (id, object) in &doc.objects {
+                if !is_shareable(object) || contents.contains(id) {
+                    continue;
+                }
+                let hash = object_hash(object);
+                let earlier = self
+                    .seen
+                    .get(&hash)
+                    .and_then(|copies| {
+                        copies
+                            .iter()
+                            .find(|(_, copy)| same_object(copy, object))
+                            .map(|(canonical, _)| *canonical)
+                    })
+                    .or_else(|| {
+                        local.get(&hash).and_then(|ids| {
+                            ids.iter()
+                                .find(|canonical| same_object(&doc.objects[canonical], object))
+                                .copied()
+                        })
+                    });
+                match earlier {
+                    Some(canonical) => {
+                        replacements.insert(*id, canonical);
+                    }
+                    None => local.entry(hash).or_default().push(*id),
+                }
+            }
+
+            // Objects are registered only when nothing changes anymore,
+            // because rewritten references change the referencing objects
+            if replacements.is_empty() || round + 1 == MAX_ROUNDS {
+                for (hash, ids) in local {
+                    let copies = self.seen.entry(hash).or_default();
+                    copies.extend(ids.into_iter().map(|id| (id, doc.objects[&id].clone())));
+                }
+                break;
+            }
+
+            for duplicate in replacements.keys() {
+                if let Some(Object::Stream(stream)) = doc.objects.remove(duplicate) {
+                    self.report.bytes_saved += stream.content.len();
+                }
+                self.report.removed += 1;
+            }
+            for object in doc.objects.values_mut() {
+                rewrite_references(object, &replacements);
+            }
+
+            tracing::debug!(
+                "Dedup round {}: merged {} objects",
+                round + 1,
+                replacements.len()
+            );
+        }
+    }
+
+    pub fn report(&self) -> &DedupReport {
+        &self.report
+    }
+}
+
+fn object_hash(object: &Object) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_object(object, &mut hasher);
+    hasher.finish()
+}
+
+///
+/// Objects are the same if they differ only in the order of dictionary keys and stream Length
+///
+fn same_object(a: &Object, b: &Object) -> bool {
+    match (a, b) {
+        (Object::Stream(a), Object::Stream(b)) => {
+            a.content == b.content && without_length(&a.dict) == without_length(&b.dict)
+        }
+        // Dictionaries compare their entries regardless of order
+        (a, b) => a == b,
+    }
+}
+
+/// Content streams of the pages
+fn page_contents(doc: &Document) -> HashSet<ObjectId> {
+    let mut contents = HashSet::new();
+    for page_id in doc.get_pages().into_values() {
+        match doc.get_dictionary(page_id).and_then(|p| p.get(b"Contents")) {
+            Ok(Object::Reference(id)) => {
+                contents.insert(*id);
+            }
+            Ok(Object::Array(array)) => {
+                contents.extend(array.iter().filter_map(|o| o.as_reference().ok()));
+            }
+            _ => {}
+        }
+    }
+    contents
+}
+
+fn is_shareable(object: &Object) -> bool {
+    match object {
+        Object::Stream(_) => true,
+        Object::Dictionary(dict) => dict
+            .get(b"Type")
+            .and_then(|o| o.as_name())
+            .is_ok_and(|t| SHARED_DICT_TYPES.contains(&t)),
+        _ => false,
+    }
+}
+
+/// Length may be an indirect object, it is recalculated on write anyway
+fn without_length(dict: &Dictionary) -> Dictionary {
+    let mut dict = dict.clone();
+    dict.remove(b"Length");
+    dict
+}
+
+fn hash_object<H: Hasher>(object: &Object, state: &mut H) {
+    match object {
+        Object::Null => 0u8.hash(state),
+        Object::Boolean(value) => value.hash(state),
+        Object::Integer(value) => value.hash(state),
+        Object::Real(value) => value.to_bits().hash(state),
+        Object::Name(name) => name.hash(state),
+        Object::String(value, format) => {
+            value.hash(state);
+            matches!(format, StringFormat::Literal).hash(state);
+        }
+        Object::Array(array) => {
+            array.len().hash(state);
+            for item in array {
+                hash_object(item, state);
+            }
+        }
+        Object::Dictionary(dict) => hash_dictionary(dict, state),
+        Object::Stream(stream) => {
+            hash_dictionary(&without_length(&stream.dict), state);
+            stream.content.hash(state);
+        }
+        Object::Reference(id) => id.hash(state),
+    }
+}
+
+/// Keys are hashed in sorted order, so the order of entries doesn't matter
+fn hash_dictionary<H: Hasher>(dict: &Dictionary, state: &mut H) {
+    let mut entries: Vec<_> = dict.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, value) in entries {
+        key.hash(state);
+        hash_object(value, state);
+    }
+}
+
+fn rewrite_references(object: &mut Object, replacements: &BTreeMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(canonical) = replacements.get(id) {
+                *id = *canonical;
+            }
+        }
+        Object::Array(array) => {
+            for item in array {
+                rewrite_references(item, replacements);
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                rewrite_references(value, replacements);
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                rewrite_references(value, replacements);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use lopdf::{Document, Object, ObjectId, Stream, dictionary};
+
+    use super::{Deduplicator, same_object};
+
+    fn image(content: &[u8]) -> Object {
+        Object::Stream(Stream::new(
+            dictionary! { "Type" => "XObject", "Subtype" => "Image", "Width" => 1, "Height" => 1 },
+            content.to_vec(),
+        ))
+    }
+
+    ///
+    /// A chapter with one page using an embedded font and two images,
+    /// object ids start at `first_id`
+    ///
+    fn chapter(first_id: u32, images: [&[u8]; 2]) -> Document {
+        let mut doc = Document::with_version("1.7");
+        let objects = [
+            (
+                1,
+                Object::Dictionary(dictionary! { "Type" => "Catalog", "Pages" => (2, 0) }),
+            ),
+            (
+                2,
+                Object::Dictionary(
+                    dictionary! { "Type" => "Pages", "Kids" => vec![(3, 0).into()], "Count" => 1 },
+                ),
+            ),
+            (
+                3,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => (2, 0),
+                    "Contents" => (4, 0),
+                    "Resources" => dictionary! {
+                        "Font" => dictionary! { "F1" => (5, 0) },
+                        "XObject" => dictionary! { "Im1" => (8, 0), "Im2" => (9, 0) },
+                    },
+                }),
+            ),
+            (
+                4,
+                Object::Stream(Stream::new(dictionary! {}, b"/Im1 Do /Im2 Do".to_vec())),
+            ),
+            (
+                5,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "TrueType",
+                    "BaseFont" => "ABCDEF+Sans",
+                    "FontDescriptor" => (6, 0),
+                }),
+            ),
+            (
+                6,
+                Object::Dictionary(dictionary! {
+                    "Type" => "FontDescriptor",
+                    "FontName" => "ABCDEF+Sans",
+                    "FontFile2" => (7, 0),
+                }),
+            ),
+            (
+                7,
+                Object::Stream(Stream::new(dictionary! {}, b"glyphs".to_vec())),
+            ),
+            (8, image(images[0])),
+            (9, image(images[1])),
+        ];
+        for (id, object) in objects {
+            doc.objects.insert((id, 0), object);
+        }
+        doc.max_id = 9;
+        doc.trailer.set("Root", (1, 0));
+        doc.renumber_objects_with(first_id);
+        doc
+    }
+
+    fn references(object: &Object, found: &mut BTreeSet<ObjectId>) {
+        match object {
+            Object::Reference(id) => {
+                found.insert(*id);
+            }
+            Object::Array(array) => array.iter().for_each(|o| references(o, found)),
+            Object::Dictionary(dict) => dict.iter().for_each(|(_, o)| references(o, found)),
+            Object::Stream(stream) => stream.dict.iter().for_each(|(_, o)| references(o, found)),
+            _ => {}
+        }
+    }
+
+    /// Every reference of the chapter points to its own object or an earlier written one
+    fn assert_references_resolve(doc: &Document, written: &Document) {
+        let mut found = BTreeSet::new();
+        doc.objects.values().for_each(|o| references(o, &mut found));
+        for id in found {
+            assert!(
+                doc.objects.contains_key(&id) || written.objects.contains_key(&id),
+                "{:?}",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn identical_resources_of_later_chapters_are_merged() {
+        let mut deduplicator = Deduplicator::default();
+
+        let mut first = chapter(1, [b"red", b"green"]);
+        deduplicator.dedup_chapter(&mut first);
+        assert_eq!(first.objects.len(), 9);
+
+        // The font file, descriptor, font and the first image repeat, the second image differs
+        let mut second = chapter(10, [b"red", b"blue"]);
+        deduplicator.dedup_chapter(&mut second);
+
+        assert_eq!(deduplicator.report().removed, 4);
+        assert_eq!(
+            deduplicator.report().bytes_saved,
+            b"glyphs".len() + b"red".len()
+        );
+        assert_eq!(second.objects.len(), 5);
+        assert_references_resolve(&second, &first);
+
+        let page = second.get_dictionary((12, 0)).unwrap();
+        let resources = page.get(b"Resources").unwrap().as_dict().unwrap();
+        let fonts = resources.get(b"Font").unwrap().as_dict().unwrap();
+        let images = resources.get(b"XObject").unwrap().as_dict().unwrap();
+        assert_eq!(fonts.get(b"F1").unwrap(), &Object::Reference((5, 0)));
+        assert_eq!(images.get(b"Im1").unwrap(), &Object::Reference((8, 0)));
+        assert_eq!(images.get(b"Im2").unwrap(), &Object::Reference((18, 0)));
+    }
+
+    #[test]
+    fn identical_resources_within_a_chapter_are_merged() {
+        let mut deduplicator = Deduplicator::default();
+        let mut doc = chapter(1, [b"same", b"same"]);
+        deduplicator.dedup_chapter(&mut doc);
+
+        assert_eq!(deduplicator.report().removed, 1);
+        assert!(!doc.objects.contains_key(&(9, 0)));
+        assert_references_resolve(&doc, &Document::new());
+    }
+
+    #[test]
+    fn page_contents_are_not_merged() {
+        let mut deduplicator = Deduplicator::default();
+        let mut first = chapter(1, [b"a", b"b"]);
+        deduplicator.dedup_chapter(&mut first);
+        let mut second = chapter(10, [b"c", b"d"]);
+        deduplicator.dedup_chapter(&mut second);
+
+        assert!(second.objects.contains_key(&(13, 0)));
+    }
+
+    #[test]
+    fn objects_differing_only_in_key_order_and_length_are_the_same() {
+        let a = Object::Stream(Stream::new(
+            dictionary! { "Length" => 3, "N" => 3, "Filter" => "FlateDecode" },
+            b"abc".to_vec(),
+        ));
+        let b = Object::Stream(Stream::new(
+            dictionary! { "Filter" => "FlateDecode", "N" => 3, "Length" => (1, 0) },
+            b"abc".to_vec(),
+        ));
+        assert!(same_object(&a, &b));
+
+        assert!(!same_object(&image(b"abc"), &image(b"abd")));
+        assert!(!same_object(
+            &Object::Dictionary(dictionary! { "Type" => "Font", "BaseFont" => "A" }),
+            &Object::Dictionary(dictionary! { "Type" => "Font", "BaseFont" => "B" }),
+        ));
+    }
+}
@@ -0,0 +1,827 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use lopdf::{
+    Dictionary, Document, Encoding, Object, ObjectId, content::Content, decode_text_string,
+};
+use serde::Serialize;
+
+use super::helpers::{find_struct_tree_root_id, get_role_map, resolve_role};
+
+/// Structure roles whose content is extracted as one block of text
+const BLOCK_ROLES: [&[u8]; 17] = [
+    b"P", b"H", b"H1", b"H2", b"H3", b"H4", b"H5", b"H6", b"LI", b"TH", b"TD", b"Caption",
+    b"Figure", b"Formula", b"Code", b"Note", b"TOCI",
+];
+
+/// A heading of a chapter
+#[derive(Debug, Clone, Serialize)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    pub page: Option<u32>,
+}
+
+/// A paragraph, list item, table cell or figure of a chapter
+#[derive(Debug, Clone, Serialize)]
+pub struct Paragraph {
+    /// Standard structure role, e.g. `P`, `LI`, `TD`
+    pub role: String,
+    pub text: String,
+    pub page: Option<u32>,
+}
+
+/// A block of text in reading order
+#[derive(Debug, Clone)]
+pub enum Block {
+    Heading(Heading),
+    Paragraph(Paragraph),
+}
+
+/// Text of a chapter, chapters are the titled `Part`/`Sect` elements of a merged document
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChapterText {
+    pub title: String,
+    pub level: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    pub start_page: Option<u32>,
+    pub end_page: Option<u32>,
+    pub headings: Vec<Heading>,
+    pub paragraphs: Vec<Paragraph>,
+    /// Headings and paragraphs in reading order
+    #[serde(skip)]
+    pub blocks: Vec<Block>,
+}
+
+impl ChapterText {
+    fn push(&mut self, role: &[u8], text: String, page: Option<u32>) {
+        let text = normalize_whitespace(&text);
+        if text.is_empty() {
+            return;
+        }
+        if let Some(page) = page {
+            self.start_page = Some(self.start_page.map_or(page, |p| p.min(page)));
+            self.end_page = Some(self.end_page.map_or(page, |p| p.max(page)));
+        }
+
+        let block = match heading_level(role) {
+            Some(level) => {
+                let heading = Heading { level, text, page };
+                self.headings.push(heading.clone());
+                Block::Heading(heading)
+            }
+            None => {
+                let paragraph = Paragraph {
+                    role: String::from_utf8_lossy(role).into_owned(),
+                    text,
+                    page,
+                };
+                self.paragraphs.push(paragraph.clone());
+                Block::Paragraph(paragraph)
+            }
+        };
+        self.blocks.push(block);
+    }
+
+    ///
+    /// Text of the headings and paragraphs in reading order
+    ///
+    pub fn block_texts(&self) -> Vec<String> {
+        self.blocks
+            .iter()
+            .map(|block| match block {
+                Block::Heading(heading) => heading.text.clone(),
+                Block::Paragraph(paragraph) => paragraph.text.clone(),
+            })
+            .collect()
+    }
+
+    ///
+    /// Chapter text as plain lines: headings and paragraphs separated by empty lines
+    ///
+    pub fn plain_text(&self) -> String {
+        self.block_texts().join("\n\n")
+    }
+}
+
+/// Text of a tagged document
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExtractedText {
+    pub title: Option<String>,
+    pub page_count: usize,
+    pub chapters: Vec<ChapterText>,
+}
+
+impl ExtractedText {
+    pub fn to_plain_text(&self) -> String {
+        let mut out = String::new();
+        for chapter in &self.chapters {
+            if !out.is_empty() {
+                out.push_str("\n\n");
+            }
+            let pages = match (chapter.start_page, chapter.end_page) {
+                (Some(start), Some(end)) if start != end => format!(" (pages {}-{})", start, end),
+                (Some(start), _) => format!(" (page {})", start),
+                _ => String::new(),
+            };
+            let title = if chapter.title.is_empty() {
+                "Untitled"
+            } else {
+                &chapter.title
+            };
+            let heading = format!("{}{}{}", "  ".repeat(chapter.level as usize), title, pages);
+            out.push_str(&heading);
+            out.push('\n');
+            out.push_str(&"=".repeat(heading.chars().count()));
+            out.push_str("\n\n");
+            out.push_str(&chapter.plain_text());
+        }
+        out.push('\n');
+        out
+    }
+}
+
+///
+/// Load a PDF file and extract its text in reading order
+///
+pub fn extract_text<P: AsRef<Path>>(path: P) -> Result<ExtractedText> {
+    let path = path.as_ref();
+    let doc = Document::load(path).with_context(|| format!("Failed to load {:?}", path))?;
+
+    extract_document(&doc)
+}
+
+///
+/// Walk the structure tree and collect the text of its marked content, chapter by chapter
+///
+pub fn extract_document(doc: &Document) -> Result<ExtractedText> {
+    let root_id = find_struct_tree_root_id(doc)
+        .context("StructTreeRoot not found, the document is not tagged")?;
+
+    let pages = doc.get_pages();
+    let page_numbers: HashMap<ObjectId, u32> = pages.iter().map(|(n, id)| (*id, *n)).collect();
+    let mut content = HashMap::new();
+    for &page_id in pages.values() {
+        content.insert(page_id, page_marked_text(doc, page_id));
+    }
+
+    let title = doc
+        .trailer
+        .get(b"Info")
+        .and_then(|o| o.as_reference())
+        .and_then(|id| doc.get_dictionary(id))
+        .and_then(|info| info.get(b"Title"))
+        .ok()
+        .and_then(text_value);
+
+    let mut walker = StructWalker {
+        doc,
+        role_map: get_role_map(doc),
+        content: &content,
+        page_numbers: &page_numbers,
+        chapters: vec![ChapterText {
+            title: title.clone().unwrap_or_default(),
+            ..Default::default()
+        }],
+        visited: HashSet::new(),
+    };
+    walker.walk(root_id, None, 0, 0);
+
+    // The untitled front chapter only holds content outside of the titled sections
+    let mut chapters = walker.chapters;
+    if chapters.len() > 1 && chapters[0].blocks.is_empty() {
+        chapters.remove(0);
+    }
+
+    Ok(ExtractedText {
+        title,
+        page_count: pages.len(),
+        chapters,
+    })
+}
+
+struct StructWalker<'a> {
+    doc: &'a Document,
+    role_map: Option<Dictionary>,
+    /// Text of every marked content sequence by page and MCID
+    content: &'a HashMap<ObjectId, BTreeMap<i64, String>>,
+    page_numbers: &'a HashMap<ObjectId, u32>,
+    chapters: Vec<ChapterText>,
+    visited: HashSet<ObjectId>,
+}
+
+/// Text collected for the block being read
+struct BlockText {
+    role: Vec<u8>,
+    text: String,
+    page: Option<ObjectId>,
+}
+
+impl StructWalker<'_> {
+    fn walk(&mut self, id: ObjectId, page: Option<ObjectId>, chapter: usize, depth: u8) {
+        if !self.visited.insert(id) {
+            return;
+        }
+        let Ok(dict) = self.doc.get_dictionary(id) else {
+            return;
+        };
+
+        let page = dict.get(b"Pg").and_then(|o| o.as_reference()).ok().or(page);
+        let role = dict
+            .get(b"S")
+            .and_then(|o| o.as_name())
+            .map(|role| resolve_role(self.role_map.as_ref(), role))
+            .unwrap_or_default();
+
+        // Titled sections are the chapters of a merged document
+        let section_title = dict
+            .get(b"T")
+            .ok()
+            .and_then(text_value)
+            .filter(|_| matches!(role.as_slice(), b"Part" | b"Sect"));
+        let (chapter, depth) = match section_title {
+            Some(title) => {
+                self.chapters.push(ChapterText {
+                    title,
+                    level: depth,
+                    lang: dict.get(b"Lang").ok().and_then(text_value),
+                    ..Default::default()
+                });
+                (self.chapters.len() - 1, depth + 1)
+            }
+            None => (chapter, depth),
+        };
+
+        if BLOCK_ROLES.contains(&role.as_slice()) {
+            let mut block = BlockText {
+                role: role.clone(),
+                text: String::new(),
+                page,
+            };
+            if let Some(text) = replacement_text(dict, &role) {
+                block.text = text;
+            } else {
+                self.read_kids(dict, page, chapter, depth, &mut block);
+            }
+            self.flush(chapter, &mut block);
+            return;
+        }
+
+        // Text outside of any block element becomes a paragraph of its own
+        let mut block = BlockText {
+            role: b"P".to_vec(),
+            text: String::new(),
+            page,
+        };
+        self.read_kids(dict, page, chapter, depth, &mut block);
+        self.flush(chapter, &mut block);
+    }
+
+    fn read_kids(
+        &mut self,
+        dict: &Dictionary,
+        page: Option<ObjectId>,
+        chapter: usize,
+        depth: u8,
+        block: &mut BlockText,
+    ) {
+        let kids = match dict.get(b"K") {
+            Ok(Object::Array(kids)) => kids.clone(),
+            Ok(kid) => vec![kid.clone()],
+            Err(_) => Vec::new(),
+        };
+
+        for kid in kids {
+            match kid {
+                Object::Integer(mcid) => self.append(block, page, mcid),
+                Object::Dictionary(mcr) => self.append_mcr(block, &mcr, page),
+                Object::Reference(kid_id) => {
+                    let Ok(kid_dict) = self.doc.get_dictionary(kid_id) else {
+                        continue;
+                    };
+                    if kid_dict.has_type(b"MCR") {
+                        self.append_mcr(block, kid_dict, page);
+                    } else if kid_dict.has_type(b"OBJR") {
+                        continue;
+                    } else if self.is_inline(kid_dict) {
+                        self.read_inline(kid_id, page, chapter, depth, block);
+                    } else {
+                        // A nested block ends the text read so far
+                        self.flush(chapter, block);
+                        self.walk(kid_id, page, chapter, depth);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    ///
+    /// Inline elements (Span, Link, LBody, ...) add their text to the enclosing block
+    ///
+    fn read_inline(
+        &mut self,
+        id: ObjectId,
+        page: Option<ObjectId>,
+        chapter: usize,
+        depth: u8,
+        block: &mut BlockText,
+    ) {
+        if !self.visited.insert(id) {
+            return;
+        }
+        let Ok(dict) = self.doc.get_dictionary(id) else {
+            return;
+        };
+        let page = dict.get(b"Pg").and_then(|o| o.as_reference()).ok().or(page);
+        if let Some(text) = replacement_text(dict, b"") {
+            block.text.push_str(&text);
+            return;
+        }
+        self.read_kids(dict, page, chapter, depth, block);
+    }
+
+    ///
+    /// Elements without block roles, titled sections or nested blocks are read as part of the enclosing block
+    ///
+    fn is_inline(&self, dict: &Dictionary) -> bool {
+        let role = dict
+            .get(b"S")
+            .and_then(|o| o.as_name())
+            .map(|role| resolve_role(self.role_map.as_ref(), role))
+            .unwrap_or_default();
+        if BLOCK_ROLES.contains(&role.as_slice()) || dict.has(b"T") {
+            return false;
+        }
+        matches!(
+            role.as_slice(),
+            b"Span"
+                | b"Link"
+                | b"Lbl"
+                | b"LBody"
+                | b"Quote"
+                | b"Reference"
+                | b"BibEntry"
+                | b"Annot"
+                | b"Ruby"
+                | b"Warichu"
+                | b"NonStruct"
+        ) && !self.has_block_descendant(dict, 0)
+    }
+
+    fn has_block_descendant(&self, dict: &Dictionary, depth: u8) -> bool {
+        if depth > 16 {
+            return false;
+        }
+        let kids = match dict.get(b"K") {
+            Ok(Object::Array(kids)) => kids.clone(),
+            Ok(kid) => vec![kid.clone()],
+            Err(_) => Vec::new(),
+        };
+        kids.iter()
+            .filter_map(|kid| kid.as_reference().ok())
+            .filter_map(|id| self.doc.get_dictionary(id).ok())
+            .any(|kid| {
+                let role = kid
+                    .get(b"S")
+                    .and_then(|o| o.as_name())
+                    .map(|role| resolve_role(self.role_map.as_ref(), role))
+                    .unwrap_or_default();
+                BLOCK_ROLES.contains(&role.as_slice())
+                    || matches!(
+                        role.as_slice(),
+                        b"L" | b"Table" | b"Div" | b"Sect" | b"Part"
+                    )
+                    || self.has_block_descendant(kid, depth + 1)
+            })
+    }
+
+    fn append_mcr(&self, block: &mut BlockText, mcr: &Dictionary, page: Option<ObjectId>) {
+        let page = mcr.get(b"Pg").and_then(|o| o.as_reference()).ok().or(page);
+        if let Ok(mcid) = mcr.get(b"MCID").and_then(|o| o.as_i64()) {
+            self.append(block, page, mcid);
+        }
+    }
+
+    fn append(&self, block: &mut BlockText, page: Option<ObjectId>, mcid: i64) {
+        let Some(page) = page else {
+            return;
+        };
+        if let Some(text) = self.content.get(&page).and_then(|texts| texts.get(&mcid)) {
+            if !block.text.is_empty() && !block.text.ends_with(char::is_whitespace) {
+                block.text.push(' ');
+            }
+            block.text.push_str(text);
+            block.page = block.page.or(Some(page));
+        }
+    }
+
+    fn flush(&mut self, chapter: usize, block: &mut BlockText) {
+        let text = std::mem::take(&mut block.text);
+        let page = block.page.and_then(|p| self.page_numbers.get(&p).copied());
+        self.chapters[chapter].push(&block.role, text, page);
+    }
+}
+
+///
+/// `ActualText` replaces the content of an element, figures are represented by their `Alt` text
+///
+fn replacement_text(dict: &Dictionary, role: &[u8]) -> Option<String> {
+    if let Some(text) = dict.get(b"ActualText").ok().and_then(text_value) {
+        return Some(text);
+    }
+    if role == b"Figure" || role == b"Formula" {
+        return Some(
+            dict.get(b"Alt")
+                .ok()
+                .and_then(text_value)
+                .unwrap_or_default(),
+        );
+    }
+    None
+}
+
+/// An open marked content sequence of a content stream
+enum MarkedContent {
+    Tagged(i64),
+    Artifact,
+    Other,
+}
+
+///
+/// Decode the text shown inside every marked content sequence of a page
+///
+fn page_marked_text(doc: &Document, page_id: ObjectId) -> BTreeMap<i64, String> {
+    let mut texts: BTreeMap<i64, String> = BTreeMap::new();
+
+    let Ok(content) = doc
+        .get_page_content(page_id)
+        .and_then(|data| Content::decode(&data))
+    else {
+        return texts;
+    };
+    let encodings: BTreeMap<Vec<u8>, Encoding> = doc
+        .get_page_fonts(page_id)
+        .map(|fonts| {
+            fonts
+                .into_iter()
+                .filter_map(|(name, font)| Some((name, font.get_font_encoding(doc).ok()?)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut stack: Vec<MarkedContent> = Vec::new();
+    let mut encoding = None;
+    let mut line_y: Option<f32> = None;
+    let mut new_line = false;
+
+    for op in &content.operations {
+        // The innermost sequence with an MCID owns the text, unless an artifact is nested in it
+        let mcid = stack.iter().rev().find_map(|m| match m {
+            MarkedContent::Tagged(mcid) => Some(Some(*mcid)),
+            MarkedContent::Artifact => Some(None),
+            MarkedContent::Other => None,
+        });
+        let mcid = mcid.flatten();
+        match op.operator.as_str() {
+            "BDC" | "BMC" => {
+                let is_artifact = op.operands.first().and_then(|o| o.as_name().ok())
+                    == Some(b"Artifact".as_slice());
+                let mcid = op
+                    .operands
+                    .get(1)
+                    .and_then(|props| marked_content_id(doc, page_id, props));
+                stack.push(match (is_artifact, mcid) {
+                    (true, _) => MarkedContent::Artifact,
+                    (false, Some(mcid)) => MarkedContent::Tagged(mcid),
+                    (false, None) => MarkedContent::Other,
+                });
+            }
+            "EMC" => {
+                stack.pop();
+            }
+            "Tf" => {
+                encoding = op
+                    .operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .and_then(|name| encodings.get(name));
+            }
+            "Td" | "TD" => {
+                let ty = op
+                    .operands
+                    .get(1)
+                    .and_then(|o| o.as_float().ok())
+                    .unwrap_or(0.0);
+                new_line |= ty != 0.0;
+            }
+            "Tm" => {
+                let y = op.operands.get(5).and_then(|o| o.as_float().ok());
+                new_line |= line_y.is_some() && y != line_y;
+                line_y = y;
+            }
+            "T*" => new_line = true,
+            "Tj" | "TJ" | "'" | "\"" => {
+                let (Some(mcid), Some(encoding)) = (mcid, encoding) else {
+                    continue;
+                };
+                let text = texts.entry(mcid).or_default();
+                if (new_line || op.operator == "'" || op.operator == "\"")
+                    && !text.is_empty()
+                    && !text.ends_with(char::is_whitespace)
+                {
+                    text.push(' ');
+                }
+                new_line = false;
+                if let Some(operand) = op.operands.last() {
+                    show_text(text, encoding, operand);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    texts
+}
+
+fn show_text(out: &mut String, encoding: &Encoding, operand: &Object) {
+    match operand {
+        Object::String(bytes, _) => {
+            if let Ok(text) = Document::decode_text(encoding, bytes) {
+                out.push_str(&text);
+            }
+        }
+        Object::Array(items) => {
+            for item in items {
+                match item {
+                    Object::String(..) => show_text(out, encoding, item),
+                    // A large negative adjustment is a word gap
+                    Object::Integer(_) | Object::Real(_) => {
+                        let adjustment = item.as_float().unwrap_or(0.0);
+                        if adjustment < -200.0 && !out.ends_with(char::is_whitespace) {
+                            out.push(' ');
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn marked_content_id(doc: &Document, page_id: ObjectId, properties: &Object) -> Option<i64> {
+    let dict = match properties {
+        Object::Dictionary(dict) => dict,
+        Object::Name(name) => doc
+            .get_page_resources(page_id)
+            .ok()
+            .and_then(|(resources, _)| resources)
+            .and_then(|r| r.get(b"Properties").ok())
+            .and_then(|o| match o {
+                Object::Reference(id) => doc.get_dictionary(*id).ok(),
+                other => other.as_dict().ok(),
+            })
+            .and_then(|p| p.get(name).ok())
+            .and_then(|o| match o {
+                Object::Reference(id) => doc.get_dictionary(*id).ok(),
+                other => other.as_dict().ok(),
+            })?,
+        _ => return None,
+    };
+    dict.get(b"MCID").and_then(|o| o.as_i64()).ok()
+}
+
+fn text_value(object: &Object) -> Option<String> {
+    decode_text_string(object).ok()
+}
+
+fn heading_level(role: &[u8]) -> Option<u8> {
+    match role {
+        [b'H', d @ b'1'..=b'6'] => Some(d - b'0'),
+        b"H" => Some(1),
+        _ => None,
+    }
+}
+
+///
+/// Collapse runs of whitespace so the text doesn't depend on the line breaks of the layout
+///
+pub fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Document, Object, ObjectId, Stream, dictionary};
+
+    use super::extract_document;
+
+    fn content(operations: &str) -> Object {
+        Object::Stream(Stream::new(
+            dictionary! {},
+            format!("BT /F1 12 Tf {} ET", operations).into_bytes(),
+        ))
+    }
+
+    fn elem(role: &str, kids: Object) -> Object {
+        Object::Dictionary(dictionary! { "Type" => "StructElem", "S" => role, "K" => kids })
+    }
+
+    ///
+    /// Two pages: an untitled intro paragraph, then the part "Guide" with a paragraph continued
+    /// on the second page and the nested section "Details" with a figure and replaced text
+    ///
+    fn tagged_document() -> Document {
+        let (page1, page2): (ObjectId, ObjectId) = ((5, 0), (6, 0));
+        let mut doc = Document::with_version("1.7");
+        let objects = [
+            (
+                1,
+                Object::Dictionary(dictionary! { "Title" => Object::string_literal("Book") }),
+            ),
+            (
+                2,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Catalog",
+                    "Pages" => (3, 0),
+                    "StructTreeRoot" => (20, 0),
+                }),
+            ),
+            (
+                3,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Pages",
+                    "Kids" => vec![page1.into(), page2.into()],
+                    "Count" => 2,
+                }),
+            ),
+            (
+                4,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => "Helvetica",
+                    "Encoding" => "WinAnsiEncoding",
+                }),
+            ),
+            (
+                5,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => (3, 0),
+                    "Contents" => (7, 0),
+                    "Resources" => dictionary! { "Font" => dictionary! { "F1" => (4, 0) } },
+                }),
+            ),
+            (
+                6,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => (3, 0),
+                    "Contents" => (8, 0),
+                    "Resources" => dictionary! { "Font" => dictionary! { "F1" => (4, 0) } },
+                }),
+            ),
+            (
+                7,
+                content(
+                    "/P <</MCID 0>> BDC (Intro text) Tj EMC \
+                     /H1 <</MCID 1>> BDC (Chapter one) Tj EMC \
+                     /P <</MCID 2>> BDC (Split para) Tj \
+                     /Artifact BMC (Page 1) Tj EMC \
+                     ( begins) Tj EMC",
+                ),
+            ),
+            (
+                8,
+                content(
+                    "/P <</MCID 0>> BDC (and ends here) Tj EMC \
+                     /H2 <</MCID 1>> BDC (Section) Tj EMC \
+                     /Figure <</MCID 2>> BDC (not text) Tj EMC \
+                     /Span <</MCID 3>> BDC (hyphen-) Tj EMC",
+                ),
+            ),
+            (
+                20,
+                Object::Dictionary(dictionary! { "Type" => "StructTreeRoot", "K" => (21, 0) }),
+            ),
+            (
+                21,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Document",
+                    "Pg" => page1,
+                    "K" => vec![(22, 0).into(), (23, 0).into()],
+                }),
+            ),
+            (22, elem("P", 0.into())),
+            (
+                23,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Part",
+                    "T" => Object::string_literal("Guide"),
+                    "Lang" => Object::string_literal("en"),
+                    "K" => vec![(24, 0).into(), (25, 0).into(), (26, 0).into()],
+                }),
+            ),
+            (24, elem("H1", 1.into())),
+            (
+                25,
+                elem(
+                    "P",
+                    vec![
+                        2.into(),
+                        dictionary! { "Type" => "MCR", "Pg" => page2, "MCID" => 0 }.into(),
+                    ]
+                    .into(),
+                ),
+            ),
+            (
+                26,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Sect",
+                    "T" => Object::string_literal("Details"),
+                    "Pg" => page2,
+                    "K" => vec![(27, 0).into(), (28, 0).into(), (29, 0).into()],
+                }),
+            ),
+            (27, elem("H2", 1.into())),
+            (
+                28,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Figure",
+                    "Alt" => Object::string_literal("Diagram"),
+                    "K" => 2,
+                }),
+            ),
+            (29, elem("P", (30, 0).into())),
+            (
+                30,
+                Object::Dictionary(dictionary! {
+                    "Type" => "StructElem",
+                    "S" => "Span",
+                    "ActualText" => Object::string_literal("hyphenated"),
+                    "K" => 3,
+                }),
+            ),
+        ];
+        for (id, object) in objects {
+            doc.objects.insert((id, 0), object);
+        }
+        doc.max_id = 30;
+        doc.trailer.set("Root", (2, 0));
+        doc.trailer.set("Info", (1, 0));
+        doc
+    }
+
+    #[test]
+    fn extracts_chapters_in_reading_order() {
+        let text = extract_document(&tagged_document()).unwrap();
+
+        assert_eq!(text.title.as_deref(), Some("Book"));
+        assert_eq!(text.page_count, 2);
+
+        let chapters: Vec<(&str, u8)> = text
+            .chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.level))
+            .collect();
+        assert_eq!(chapters, [("Book", 0), ("Guide", 0), ("Details", 1)]);
+        assert_eq!(text.chapters[0].block_texts(), ["Intro text"]);
+
+        // The artifact inside MCID 2 is left out, the paragraph continues on the next page
+        let guide = &text.chapters[1];
+        assert_eq!(guide.lang.as_deref(), Some("en"));
+        assert_eq!(
+            guide.block_texts(),
+            ["Chapter one", "Split para begins and ends here"]
+        );
+        assert_eq!((guide.start_page, guide.end_page), (Some(1), Some(1)));
+
+        // Figures are replaced by Alt and elements with ActualText by that text
+        let details = &text.chapters[2];
+        assert_eq!(details.block_texts(), ["Section", "Diagram", "hyphenated"]);
+        assert_eq!(details.headings[0].level, 2);
+        assert_eq!(details.headings[0].page, Some(2));
+        let roles: Vec<&str> = details.paragraphs.iter().map(|p| p.role.as_str()).collect();
+        assert_eq!(roles, ["Figure", "P"]);
+    }
+
+    #[test]
+    fn untagged_document_is_an_error() {
+        let mut doc = tagged_document();
+        doc.get_dictionary_mut((2, 0))
+            .unwrap()
+            .remove(b"StructTreeRoot");
+
+        assert!(extract_document(&doc).is_err());
+    }
+}
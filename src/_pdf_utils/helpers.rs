@@ -61,3 +61,34 @@ pub fn get_node_role(doc: &Document, id: ObjectId) -> Option<Vec<u8>> {
         .and_then(|s| s.as_name().ok())
         .map(|n| n.to_vec())
 }
+
+pub fn get_role_map(doc: &Document) -> Option<Dictionary> {
+    let root_id = find_struct_tree_root_id(doc)?;
+    doc.get_object(root_id)
+        .and_then(|o| o.as_dict())
+        .and_then(|d| d.get(b"RoleMap"))
+        .and_then(|o| match o {
+            Object::Reference(id) => doc.get_dictionary(*id),
+            other => other.as_dict(),
+        })
+        .ok()
+        .cloned()
+}
+
+/// Resolve a custom role to a standard one following the RoleMap chain
+pub fn resolve_role(role_map: Option<&Dictionary>, role: &[u8]) -> Vec<u8> {
+    let mut role = role.to_vec();
+    let Some(role_map) = role_map else {
+        return role;
+    };
+
+    // Guard against cyclic mappings
+    for _ in 0..8 {
+        match role_map.get(&role).and_then(|o| o.as_name()) {
+            Ok(mapped) if mapped != role.as_slice() => role = mapped.to_vec(),
+            _ => break,
+        }
+    }
+
+    role
+}
@@ -1,10 +1,28 @@
-use lopdf::{dictionary, Bookmark, Dictionary, Document, Object, ObjectId};
+use lopdf::{Bookmark, Dictionary, Document, Object, ObjectId, decode_text_string, dictionary};
 use std::{
-    collections::{BTreeMap, HashMap},
-    path::Path,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
 };
 
+use super::dedup::Deduplicator;
+use super::helpers::{
+    collect_all_node_ids, find_struct_tree_root_id, get_role_map, resolve_role, set_parent_link,
+};
+use super::merge_report::{ChapterReport, ChapterStatus, MergeError, MergeReport};
+use super::metadata::{
+    DocumentMetadata, apply_metadata, dominant_lang, strip_document_info, text_string,
+};
+use super::outline::{OutlineHeading, same_title, take_outline};
+use super::page_labels::{PageLabelStyle, PageLabels, PagePart};
+use super::pdf_writer::{ObjectSink, PdfWriter};
+use super::pdfa::{PdfaLevel, PdfaReport, apply_pdfa, fix_objects};
 use super::sanitize_pdf::sanitize_pdf;
+use super::split::{VolumeLinks, add_chapter_destinations, chapter_key, rewrite_remote_links};
+use super::stamp_pages::{
+    ChapterStart, StampFont, StampOptions, inline_page_resources, stamp_pages,
+};
 use crate::toc::TocNode;
 
 /// Result of processing the structure of a single document
@@ -19,33 +37,170 @@ pub struct DocStructureData {
     pub next_offset_increment: i64,
 }
 
-pub fn merge_pdfs<P>(toc: Vec<TocNode>, output: P) -> lopdf::Result<()>
+/// Structure data accumulated across all merged documents
+#[derive(Default)]
+pub struct MergedStructure {
+    /// ParentTree elements of all documents (Nums array)
+    pub nums: Vec<Object>,
+    /// Top-level structure children of all documents
+    pub kids: Vec<Object>,
+    /// Union of all custom tag mappings (RoleMap)
+    pub role_map: Dictionary,
+    /// Next free ParentTree key
+    pub next_key: i64,
+    /// Part/Sect elements wrapping every chapter
+    pub sections: BTreeMap<ObjectId, Dictionary>,
+    /// (TOC level, id) of the sections which may still get nested chapters
+    pub open_sections: Vec<(u8, ObjectId)>,
+}
+
+impl MergedStructure {
+    ///
+    /// Add a chapter section, nesting it under the closest open section of a lower TOC level
+    ///
+    pub fn add_section(&mut self, id: ObjectId, level: u8, mut section: Dictionary) {
+        while self
+            .open_sections
+            .last()
+            .is_some_and(|(open_level, _)| *open_level >= level)
+        {
+            self.open_sections.pop();
+        }
+
+        match self.open_sections.last() {
+            Some((_, parent_id)) => {
+                section.set("P", *parent_id);
+                if let Some(Ok(kids)) = self
+                    .sections
+                    .get_mut(parent_id)
+                    .map(|parent| parent.get_mut(b"K").and_then(|k| k.as_array_mut()))
+                {
+                    kids.push(Object::Reference(id));
+                }
+            }
+            // Top-level sections are linked to the Document element during assembly
+            None => self.kids.push(Object::Reference(id)),
+        }
+
+        self.open_sections.push((level, id));
+        self.sections.insert(id, section);
+    }
+}
+
+/// Version of the merged document, Tagged PDF needs at least 1.7
+const PDF_VERSION: &str = "1.7";
+
+/// Options of the merge stage
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Cover page PDF prepended before the first chapter
+    pub cover: Option<PathBuf>,
+    /// Document information (title, author, language, etc.)
+    pub metadata: DocumentMetadata,
+    /// Produce PDF/A archival output
+    pub pdfa: Option<PdfaLevel>,
+    /// Numbering style of the /PageLabels shown by viewers
+    pub page_labels: PageLabelStyle,
+    /// Top-level chapters in the previous volumes of a split book
+    pub first_chapter: u32,
+    /// Running headers and footers stamped over the merged pages
+    pub stamp: StampOptions,
+    /// Depth of in-page heading bookmarks under each chapter, 0 disables them
+    pub outline_depth: usize,
+    /// Fail instead of producing a document with untagged, skipped or broken chapters
+    pub strict: bool,
+    /// Set when the output is one volume of a split book, links to other volumes become GoToR
+    pub volume: Option<VolumeLinks>,
+}
+
+///
+/// Merge chapter PDFs into one tagged document
+///
+/// Chapters are processed one by one and their objects are written to the output right away.
+/// Only the page tree, outline, ParentTree and structure roots are kept in memory until the end.
+/// A partially written output is removed if the merge fails.
+///
+pub fn merge_pdfs<P>(
+    toc: Vec<TocNode>,
+    output: P,
+    options: &MergeOptions,
+) -> Result<MergeReport, MergeError>
 where
     P: AsRef<Path>,
 {
-    let toc_iter = toc.into_iter();
+    let result = File::create(output.as_ref())
+        .map_err(MergeError::from)
+        .and_then(|file| {
+            let mut writer = PdfWriter::new(BufWriter::new(file), PDF_VERSION)?;
+            let (report, trailer) = write_merged(toc, options, &mut writer)?;
+            writer.finish(trailer)?;
+            Ok(report)
+        });
+    if result.is_err() && output.as_ref().exists() {
+        let _ = std::fs::remove_file(output.as_ref());
+    }
+    result
+}
+
+///
+/// Merge the chapters into the sink, the returned trailer finishes the file
+///
+fn write_merged<S: ObjectSink>(
+    toc: Vec<TocNode>,
+    options: &MergeOptions,
+    writer: &mut S,
+) -> Result<(MergeReport, Dictionary), MergeError> {
+    // The cover gets its own bookmark titled after the document, but it isn't a TOC chapter
+    let cover_node = options.cover.as_ref().map(|path| TocNode {
+        file_path: Some(path.clone()),
+        title: options.metadata.title.clone(),
+        href: String::new(),
+        level: 0,
+        lang: None,
+        final_url: None,
+    });
+    let toc_iter = cover_node
+        .into_iter()
+        .map(|node| (PagePart::FrontMatter, node))
+        .chain(
+            toc.into_iter()
+                .map(|node| (PagePart::Chapter { level: node.level }, node)),
+        );
 
     // 📌 Step 1.1: Use version 1.7 to support modern Tagged PDF
-    let mut document = Document::with_version("1.7");
+    // The document holds only the objects assembled at the end, chapter objects go to the writer
+    let mut document = Document::with_version(PDF_VERSION);
 
     let mut max_id = 1;
     let mut pagenum = 1;
 
     let mut documents_pages = BTreeMap::new();
-    let mut documents_objects = BTreeMap::new();
+    let mut catalog_id: Option<ObjectId> = None;
+    let mut pages_id: Option<ObjectId> = None;
 
     // 📌 Step 1.2: Collectors for structural data (Stage 1)
-    // We'll save each document's StructTreeRoot as separate objects for later analysis
-    let mut source_struct_roots = Vec::new();
+    let mut struct_roots_found = 0;
 
     let mut previous_lever_bookmark: HashMap<u8, Option<u32>> = HashMap::new();
 
-    let mut global_nums = Vec::new();
-    let mut global_kids = Vec::new();
-    let mut global_role_map = Dictionary::new();
-    let mut current_offset = 0i64;
+    let mut merged_structure = MergedStructure::default();
 
-    for node in toc_iter {
+    let mut page_labels = PageLabels::new(options.page_labels, options.first_chapter);
+    let mut chapter_starts = Vec::new();
+
+    let mut deduplicator = Deduplicator::default();
+
+    let mut stamp_font = if options.stamp.is_empty() {
+        None
+    } else {
+        Some(StampFont::load(&options.stamp).map_err(MergeError::StampFont)?)
+    };
+    let mut pdfa_report = PdfaReport::default();
+    let mut report = MergeReport::default();
+    let mut chapter_langs = Vec::new();
+    let mut chapter_destinations = Vec::new();
+
+    for (part, node) in toc_iter {
         let file_path = if let Some(path) = node.file_path.as_ref() {
             path
         } else {
@@ -58,18 +213,35 @@ where
 
         tracing::debug!("Merging: \"{:?}\"", title);
 
+        let mut chapter_report = ChapterReport {
+            title: title.clone(),
+            file: file_path.clone(),
+            status: ChapterStatus::Tagged,
+            warnings: Vec::new(),
+        };
+
         let mut doc = match Document::load(file_path) {
             Ok(d) => d,
             Err(e) => {
                 eprintln!("⚠️ Skipping corrupted PDF {:?}: {:?}", file_path, e);
+                chapter_report.status = ChapterStatus::Skipped(e.to_string());
+                report_chapter(&mut report, chapter_report, options.strict)?;
                 continue;
             }
         };
 
-        if let Err(e) = sanitize_pdf(&mut doc) {
+        if find_struct_tree_root_id(&doc).is_some()
+            && let Err(e) = sanitize_pdf(&mut doc)
+        {
             tracing::error!("Failed to sanitize PDF structure: {:?}", e);
+            chapter_report
+                .warnings
+                .push(format!("sanitize failed: {}", e));
         }
 
+        // Chapter dates and identifiers would only end up as orphan objects
+        strip_document_info(&mut doc);
+
         // 📌 Renumbering
         let start_id = max_id;
         doc.renumber_objects_with(max_id);
@@ -85,29 +257,20 @@ where
         // 📌 Step 1.3: Extract StructTreeRoot data
         // Find the structure root in the current document
         let mut struct_found = false;
-        if let Ok(catalog) = doc.catalog() {
-            if let Ok(struct_root_res) = catalog.get(b"StructTreeRoot") {
-                // Save a reference to this document's StructTreeRoot for stages 2-4
-                if let Ok(id) = struct_root_res.as_reference() {
-                    if let Ok(dict) = doc.get_object(id).and_then(|o| o.as_dict()) {
-                        // Clone the dictionary since doc will be consumed or destroyed
-                        source_struct_roots.push(dict.clone());
-
-                        struct_found = true;
-
-                        // Log the keys present in the structure (K, ParentTree, RoleMap, etc.)
-                        let keys: Vec<String> = dict
-                            .iter()
-                            .map(|(k, _)| String::from_utf8_lossy(k).into_owned())
-                            .collect();
-                        tracing::debug!(
-                            "Found StructTreeRoot (ID: {:?}) with keys: {:?}",
-                            id,
-                            keys
-                        );
-                    }
-                }
-            }
+        if let Ok(catalog) = doc.catalog()
+            && let Ok(struct_root_res) = catalog.get(b"StructTreeRoot")
+            && let Ok(id) = struct_root_res.as_reference()
+            && let Ok(dict) = doc.get_object(id).and_then(|o| o.as_dict())
+        {
+            struct_roots_found += 1;
+            struct_found = true;
+
+            // Log the keys present in the structure (K, ParentTree, RoleMap, etc.)
+            let keys: Vec<String> = dict
+                .iter()
+                .map(|(k, _)| String::from_utf8_lossy(k).into_owned())
+                .collect();
+            tracing::debug!("Found StructTreeRoot (ID: {:?}) with keys: {:?}", id, keys);
         }
 
         if !struct_found {
@@ -115,37 +278,109 @@ where
                 "No StructTreeRoot found in '{}'. This document might not be Tagged (PDF/UA).",
                 title
             );
+            chapter_report.status = ChapterStatus::Untagged;
+        }
+        report_chapter(&mut report, chapter_report, options.strict)?;
+
+        // Pages rendered by the browser carry the language in the TOC, existing PDFs in the catalog
+        let lang = node.lang.clone().or_else(|| {
+            doc.catalog()
+                .and_then(|c| c.get(b"Lang"))
+                .and_then(decode_text_string)
+                .ok()
+                .filter(|lang| !lang.is_empty())
+        });
+        if let Some(lang) = &lang {
+            chapter_langs.push(lang.clone());
         }
 
         // --- Call structure processing function ---
-        let struct_data = extract_and_shift_structure(&mut doc, current_offset);
+        let struct_data = extract_and_shift_structure(&mut doc, merged_structure.next_key);
 
         // 1. Collect Nums (ParentTree)
-        global_nums.extend(struct_data.shifted_nums);
+        merged_structure.nums.extend(struct_data.shifted_nums);
+
+        // 2. Wrap the chapter's children (K) into a section nested according to the TOC
+        if !struct_data.root_kids.is_empty() {
+            let shifted = shift_heading_levels(&mut doc, &struct_data.root_kids, node.level);
+
+            let section_id = (max_id, 0);
+            max_id += 1;
+            for kid in &struct_data.root_kids {
+                if let Ok(kid_id) = kid.as_reference() {
+                    set_parent_link(&mut doc, kid_id, section_id);
+                }
+            }
+
+            let mut section = dictionary! {
+                "Type" => "StructElem",
+                "S" => if node.level == 0 { "Part" } else { "Sect" },
+                "T" => text_string(&title),
+                "K" => struct_data.root_kids,
+            };
+            if let Some(lang) = &lang {
+                section.set("Lang", text_string(lang));
+            }
+            merged_structure.add_section(section_id, node.level, section);
 
-        // 2. Collect children (K) - now just extend, without if let Some
-        global_kids.extend(struct_data.root_kids);
+            tracing::debug!(
+                "Wrapped '{}' into section {:?}, shifted {} headings",
+                title,
+                section_id,
+                shifted
+            );
+        }
 
         // 3. Collect RoleMap
         if let Some(rm) = struct_data.role_map {
             for (k, v) in rm {
-                global_role_map.set(k.clone(), v.clone());
+                merged_structure.role_map.set(k.clone(), v.clone());
             }
         }
 
         // 4. Update global offset for the next file
-        current_offset += struct_data.next_offset_increment;
+        merged_structure.next_key += struct_data.next_offset_increment;
 
         tracing::debug!(
             "Processed structure for '{}': Shifted {} Nums, incremented offset by {}",
             title,
-            global_nums.len() / 2,
+            merged_structure.nums.len() / 2,
             struct_data.next_offset_increment
         );
 
+        let headings = take_outline(&mut doc);
+
+        if let Some(volume) = &options.volume {
+            let rewritten = rewrite_remote_links(&mut doc, volume);
+            tracing::debug!(
+                "Rewrote {} links to other volumes in '{}'",
+                rewritten,
+                title
+            );
+        }
+
+        // 📌 Per-chapter passes, they must run before the objects are written
+        if !options.stamp.is_empty() {
+            // The stamp font is added to the page resources after shared objects are written
+            for page_id in doc.get_pages().into_values() {
+                inline_page_resources(&mut doc, page_id)?;
+            }
+        }
+
+        // Fonts are checked before dedup, it points them to descriptors which are already written
+        if options.pdfa.is_some() {
+            fix_objects(&mut doc, &mut pdfa_report);
+        }
+
+        // Chapters embed their own copies of the same fonts, images and profiles
+        deduplicator.dedup_chapter(&mut doc);
+
+        doc.compress();
+
         // 📑 Collect pages and objects
         let mut file_page_count = 0;
         let mut is_first_page = true;
+        let mut chapter_bookmark = None;
         for (_page_num, object_id) in doc.get_pages() {
             if is_first_page {
                 let bookmark =
@@ -157,8 +392,18 @@ where
                     .get(&node.level.saturating_sub(1))
                     .copied()
                     .flatten();
-                previous_lever_bookmark
-                    .insert(node.level, Some(document.add_bookmark(bookmark, parent)));
+                chapter_bookmark = Some(document.add_bookmark(bookmark, parent));
+                previous_lever_bookmark.insert(node.level, chapter_bookmark);
+                page_labels.start_part(pagenum - 1, part);
+                if let PagePart::Chapter { .. } = part {
+                    chapter_starts.push(ChapterStart {
+                        page_index: pagenum - 1,
+                        title: title.clone(),
+                    });
+                    if options.volume.is_some() {
+                        chapter_destinations.push((chapter_key(&node.href), object_id));
+                    }
+                }
                 is_first_page = false;
             }
             pagenum += 1;
@@ -170,6 +415,17 @@ where
             }
         }
 
+        if let (Some(chapter_id), PagePart::Chapter { .. }) = (chapter_bookmark, part) {
+            let added = add_heading_bookmarks(
+                &mut document,
+                chapter_id,
+                &title,
+                &headings,
+                options.outline_depth,
+            );
+            tracing::debug!("Added {} heading bookmarks under '{}'", added, title);
+        }
+
         tracing::debug!(
             "Collected {} pages from '{}'. Current total pagenum: {}",
             file_page_count,
@@ -177,15 +433,36 @@ where
             pagenum + file_page_count - 1
         );
 
-        // Consume all objects from the current document
-        documents_objects.extend(doc.objects);
+        // Write all common objects (fonts, resources, content), keep the first Catalog and Pages
+        for (id, obj) in doc.objects {
+            match obj.type_name().unwrap_or(b"") {
+                b"Catalog" => {
+                    if catalog_id.is_none() {
+                        catalog_id = Some(id);
+                        // ⚠️ MANDATORY: insert into the document so assemble_merged_document can find it via get_mut
+                        document.objects.insert(id, obj);
+                    }
+                }
+                b"Pages" => {
+                    if pages_id.is_none() {
+                        pages_id = Some(id);
+                        // ⚠️ MANDATORY: insert into the document
+                        document.objects.insert(id, obj);
+                    }
+                }
+                b"Page" | b"Outlines" | b"Outline" | b"StructTreeRoot" => {
+                    // These types we reassemble manually, skip them
+                }
+                _ => writer.write_object(id, &obj)?,
+            }
+        }
     }
 
     tracing::info!(
-        "Stage 1 complete: Total objects: {}, Total pages: {}, Struct roots collected: {}",
-        documents_objects.len(),
+        "Stage 1 complete: Written objects: {}, Total pages: {}, Struct roots collected: {}",
+        writer.written(),
         documents_pages.len(),
-        source_struct_roots.len()
+        struct_roots_found
     );
 
     ////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -194,37 +471,8 @@ where
     // ⚠️ CRITICAL: Synchronize the ID counter in the new document with what we counted in the loop
     document.max_id = max_id;
 
-    let mut catalog_id: Option<ObjectId> = None;
-    let mut pages_id: Option<ObjectId> = None;
-
-    // First, simply transfer all common objects (fonts, resources)
-    for (id, obj) in &documents_objects {
-        match obj.type_name().unwrap_or(b"") {
-            b"Catalog" => {
-                if catalog_id.is_none() {
-                    catalog_id = Some(*id);
-                    // ⚠️ MANDATORY: insert into the document so assemble_merged_document can find it via get_mut
-                    document.objects.insert(*id, obj.clone());
-                }
-            }
-            b"Pages" => {
-                if pages_id.is_none() {
-                    pages_id = Some(*id);
-                    // ⚠️ MANDATORY: insert into the document
-                    document.objects.insert(*id, obj.clone());
-                }
-            }
-            b"Page" | b"Outlines" | b"Outline" | b"StructTreeRoot" => {
-                // These types we reassemble manually, skip them
-            }
-            _ => {
-                document.objects.insert(*id, obj.clone());
-            }
-        }
-    }
-
-    let catalog_id = catalog_id.expect("Catalog not found");
-    let pages_id = pages_id.expect("Pages root not found");
+    let catalog_id = catalog_id.ok_or(MergeError::CatalogNotFound)?;
+    let pages_id = pages_id.ok_or(MergeError::PagesRootNotFound)?;
 
     // --- STAGE 5: Final assembly ---
     // Now assemble_merged_document will get IDs starting from max_id + 1 (i.e., from 370+)
@@ -233,10 +481,7 @@ where
         catalog_id,
         pages_id,
         documents_pages,
-        global_kids,
-        global_nums,
-        global_role_map,
-        current_offset,
+        merged_structure,
     )?;
 
     // --- FINALIZATION ---
@@ -245,6 +490,40 @@ where
         "Size" => (document.objects.len() as i64) + 1
     };
 
+    let mut metadata = options.metadata.clone();
+    if metadata.lang.is_none() {
+        metadata.lang = dominant_lang(chapter_langs.iter().map(String::as_str));
+    }
+    // PDF/UA is claimed only when all content is tagged and every font is embedded
+    let pdfua = report.count(&ChapterStatus::Untagged) == 0
+        && stamp_font.as_ref().is_none_or(StampFont::is_embedded);
+    apply_metadata(&mut document, catalog_id, &metadata, options.pdfa, pdfua)?;
+
+    add_chapter_destinations(&mut document, catalog_id, chapter_destinations)?;
+
+    if let Some(labels) = page_labels.into_number_tree() {
+        let labels_id = document.add_object(labels);
+        document
+            .get_dictionary_mut(catalog_id)?
+            .set("PageLabels", labels_id);
+    }
+
+    if let Some(font) = &mut stamp_font {
+        let book_title = metadata.title.as_deref().unwrap_or_default();
+        stamp_pages(
+            &mut document,
+            book_title,
+            &chapter_starts,
+            &options.stamp,
+            font,
+        )?;
+        if !font.is_embedded() {
+            pdfa_report
+                .unresolved
+                .push("Stamp font 'Helvetica' is not embedded, set --stamp-font".to_string());
+        }
+    }
+
     // ⚠️ Shift max_id to the actual value after adding new structure objects
     document.max_id = document
         .objects
@@ -256,24 +535,124 @@ where
     // Don't use auto adjusting due to we have a custom merge algorithm
     // document.adjust_zero_pages();
 
-    if !document.bookmarks.is_empty() {
-        if let Some(outline_id) = document.build_outline() {
-            if let Ok(Object::Dictionary(dict)) = document.get_object_mut(catalog_id) {
-                dict.set("Outlines", Object::Reference(outline_id));
-            }
-        }
+    if !document.bookmarks.is_empty()
+        && let Some(outline_id) = document.build_outline()
+        && let Ok(Object::Dictionary(dict)) = document.get_object_mut(catalog_id)
+    {
+        dict.set("Outlines", Object::Reference(outline_id));
     }
 
-    // ⚠️ HIGHLY RECOMMENDED: renumber all objects at the very end for "clean" xref table
-    document.renumber_objects();
+    deduplicator.report().print();
+    report.print();
+
+    if let Some(level) = options.pdfa {
+        apply_pdfa(&mut document, catalog_id, level, &mut pdfa_report);
+        pdfa_report.print();
+    }
 
+    // 📌 Write the assembled objects after the chapters and finish the file
+    // Compression only depends on the stream content, so it doesn't break reproducible output
     document.compress();
-    document.save(output)?;
+    for (id, object) in &document.objects {
+        writer.write_object(*id, object)?;
+    }
 
     tracing::info!("Merged PDF saved successfully.");
+    Ok((report, document.trailer))
+}
+
+///
+/// Add the chapter to the report, in strict mode a degraded chapter stops the merge
+///
+fn report_chapter(
+    report: &mut MergeReport,
+    chapter: ChapterReport,
+    strict: bool,
+) -> Result<(), MergeError> {
+    if strict && chapter.is_degraded() {
+        return Err(MergeError::Strict {
+            title: chapter.title.clone(),
+            reason: chapter.reason(),
+        });
+    }
+
+    report.chapters.push(chapter);
     Ok(())
 }
 
+///
+/// Nest the chapter's own outline under its bookmark, skipping the heading that repeats the chapter title
+///
+fn add_heading_bookmarks(
+    document: &mut Document,
+    chapter_bookmark: u32,
+    chapter_title: &str,
+    headings: &[OutlineHeading],
+    max_depth: usize,
+) -> usize {
+    // (source depth, bookmark id) of the kept ancestors of the current heading
+    let mut stack: Vec<(usize, u32)> = Vec::new();
+    let mut added = 0;
+
+    for heading in headings {
+        while stack
+            .last()
+            .is_some_and(|(depth, _)| *depth >= heading.depth)
+        {
+            stack.pop();
+        }
+        if same_title(chapter_title, &heading.title) || stack.len() >= max_depth {
+            continue;
+        }
+
+        let parent = stack.last().map_or(chapter_bookmark, |(_, id)| *id);
+        let bookmark = Bookmark::new(heading.title.clone(), [0.0, 0.0, 0.0], 0, heading.page);
+        let id = document.add_bookmark(bookmark, Some(parent));
+        stack.push((heading.depth, id));
+        added += 1;
+    }
+
+    added
+}
+
+///
+/// Shift H1–H6 of a chapter by its TOC level, so the merged book has one heading hierarchy
+///
+fn shift_heading_levels(doc: &mut Document, root_kids: &[Object], shift: u8) -> usize {
+    if shift == 0 {
+        return 0;
+    }
+
+    let role_map = get_role_map(doc);
+    let mut nodes = Vec::new();
+    let mut visited = HashSet::new();
+    for kid in root_kids {
+        if let Ok(id) = kid.as_reference() {
+            collect_all_node_ids(doc, id, &mut nodes, &mut visited);
+        }
+    }
+
+    let mut shifted = 0;
+    for id in nodes {
+        let Ok(dict) = doc.get_dictionary_mut(id) else {
+            continue;
+        };
+        let Ok(role) = dict.get(b"S").and_then(|o| o.as_name()) else {
+            continue;
+        };
+        let level = match resolve_role(role_map.as_ref(), role).as_slice() {
+            [b'H', digit @ b'1'..=b'6'] => digit - b'0',
+            _ => continue,
+        };
+
+        let new_level = level.saturating_add(shift).min(6);
+        dict.set("S", Object::Name(format!("H{}", new_level).into_bytes()));
+        shifted += 1;
+    }
+
+    shifted
+}
+
 /// Extracts and shifts PDF structure elements for merging multiple PDFs
 ///
 /// This function processes the StructTreeRoot of a document to extract
@@ -286,86 +665,81 @@ fn extract_and_shift_structure(doc: &mut Document, current_offset: i64) -> DocSt
     let mut local_next_key = 0i64;
 
     // Try to get StructTreeRoot by Catalog
-    if let Ok(catalog) = doc.catalog() {
-        if let Ok(str_root_ref) = catalog
+    if let Ok(catalog) = doc.catalog()
+        && let Ok(str_root_ref) = catalog
             .get(b"StructTreeRoot")
             .and_then(|o| o.as_reference())
+        && let Ok(str_root) = doc.get_object(str_root_ref).and_then(|o| o.as_dict())
+    {
+        // --- A. Get ParentTreeNextKey to calculate future offset ---
+        local_next_key = str_root
+            .get(b"ParentTreeNextKey")
+            .and_then(|o| o.as_i64())
+            .unwrap_or(0);
+
+        // --- B. Shift keys in ParentTree (Nums) ---
+        if let Ok(pt_ref) = str_root.get(b"ParentTree").and_then(|o| o.as_reference())
+            && let Ok(pt_dict) = doc.get_object(pt_ref).and_then(|o| o.as_dict())
+            && let Ok(nums) = pt_dict.get(b"Nums").and_then(|o| o.as_array())
         {
-            if let Ok(str_root) = doc.get_object(str_root_ref).and_then(|o| o.as_dict()) {
-                // --- A. Get ParentTreeNextKey to calculate future offset ---
-                local_next_key = str_root
-                    .get(b"ParentTreeNextKey")
-                    .and_then(|o| o.as_i64())
-                    .unwrap_or(0);
-
-                // --- B. Shift keys in ParentTree (Nums) ---
-                if let Ok(pt_ref) = str_root.get(b"ParentTree").and_then(|o| o.as_reference()) {
-                    if let Ok(pt_dict) = doc.get_object(pt_ref).and_then(|o| o.as_dict()) {
-                        if let Ok(nums) = pt_dict.get(b"Nums").and_then(|o| o.as_array()) {
-                            for i in (0..nums.len()).step_by(2) {
-                                if let (Some(Object::Integer(k)), Some(val)) =
-                                    (nums.get(i), nums.get(i + 1))
-                                {
-                                    let new_key = k + current_offset;
-                                    shifted_nums.push(Object::Integer(new_key));
-                                    shifted_nums.push(val.clone());
-                                }
-                            }
-                        }
-                    }
+            for i in (0..nums.len()).step_by(2) {
+                if let (Some(Object::Integer(k)), Some(val)) = (nums.get(i), nums.get(i + 1)) {
+                    let new_key = k + current_offset;
+                    shifted_nums.push(Object::Integer(new_key));
+                    shifted_nums.push(val.clone());
                 }
+            }
+        }
 
-                // --- C. Extract and flatten structure children (K) ---
-                if let Ok(k_obj) = str_root.get(b"K") {
-                    match k_obj {
-                        Object::Array(arr) => {
-                            root_kids.extend(arr.iter().cloned());
-                        }
-                        Object::Reference(id) => {
-                            // Check: is this object a "Document" type node
-                            let is_doc_node = doc
-                                .get_object(*id)
-                                .ok()
-                                .and_then(|o| o.as_dict().ok())
-                                .and_then(|d| d.get(b"S").ok())
-                                .and_then(|s| s.as_name().ok())
-                                == Some(b"Document");
-
-                            if is_doc_node {
-                                // If it's a Document, take its children (/K) directly
-                                if let Ok(inner_k) =
-                                    doc.get_object(*id).and_then(|o| o.as_dict()?.get(b"K"))
-                                {
-                                    match inner_k {
-                                        Object::Array(arr) => root_kids.extend(arr.iter().cloned()),
-                                        _ => root_kids.push(inner_k.clone()),
-                                    }
-                                }
-                            } else {
-                                // If it's not a Document (e.g., Div or Part), just add the reference
-                                root_kids.push(k_obj.clone());
+        // --- C. Extract and flatten structure children (K) ---
+        if let Ok(k_obj) = str_root.get(b"K") {
+            match k_obj {
+                Object::Array(arr) => {
+                    root_kids.extend(arr.iter().cloned());
+                }
+                Object::Reference(id) => {
+                    // Check: is this object a "Document" type node
+                    let is_doc_node = doc
+                        .get_object(*id)
+                        .ok()
+                        .and_then(|o| o.as_dict().ok())
+                        .and_then(|d| d.get(b"S").ok())
+                        .and_then(|s| s.as_name().ok())
+                        == Some(b"Document");
+
+                    if is_doc_node {
+                        // If it's a Document, take its children (/K) directly
+                        if let Ok(inner_k) =
+                            doc.get_object(*id).and_then(|o| o.as_dict()?.get(b"K"))
+                        {
+                            match inner_k {
+                                Object::Array(arr) => root_kids.extend(arr.iter().cloned()),
+                                _ => root_kids.push(inner_k.clone()),
                             }
                         }
-                        _ => root_kids.push(k_obj.clone()),
+                    } else {
+                        // If it's not a Document (e.g., Div or Part), just add the reference
+                        root_kids.push(k_obj.clone());
                     }
                 }
-
-                // --- D. Extract RoleMap ---
-                role_map = str_root
-                    .get(b"RoleMap")
-                    .ok()
-                    .and_then(|o| o.as_dict().ok())
-                    .map(|d| d.clone());
+                _ => root_kids.push(k_obj.clone()),
             }
         }
+
+        // --- D. Extract RoleMap ---
+        role_map = str_root
+            .get(b"RoleMap")
+            .ok()
+            .and_then(|o| o.as_dict().ok())
+            .cloned();
     }
 
     // --- E. Shift StructParents on pages (most important for linking) ---
     for (_page_num, page_id) in doc.get_pages() {
-        if let Ok(page_dict) = doc.get_object_mut(page_id).and_then(|o| o.as_dict_mut()) {
-            if let Ok(old_sp) = page_dict.get(b"StructParents").and_then(|o| o.as_i64()) {
-                page_dict.set("StructParents", old_sp + current_offset);
-            }
+        if let Ok(page_dict) = doc.get_object_mut(page_id).and_then(|o| o.as_dict_mut())
+            && let Ok(old_sp) = page_dict.get(b"StructParents").and_then(|o| o.as_i64())
+        {
+            page_dict.set("StructParents", old_sp + current_offset);
         }
     }
 
@@ -387,11 +761,17 @@ fn assemble_merged_document(
     catalog_id: ObjectId,
     pages_id: ObjectId,
     documents_pages: BTreeMap<ObjectId, Object>,
-    global_kids: Vec<Object>,
-    global_nums: Vec<Object>,
-    global_role_map: Dictionary,
-    final_offset: i64,
+    merged_structure: MergedStructure,
 ) -> lopdf::Result<Document> {
+    let MergedStructure {
+        nums: global_nums,
+        kids: global_kids,
+        role_map: global_role_map,
+        next_key: final_offset,
+        sections,
+        ..
+    } = merged_structure;
+
     tracing::info!("--- Stage 4: Assembling final document structure ---");
 
     // 1. Insert pages into the final document and link them to the new Pages ID
@@ -408,6 +788,11 @@ fn assemble_merged_document(
         pages_id
     );
 
+    // 1.1. Insert the chapter sections, they are parents of the chapters' own elements
+    for (id, section) in sections {
+        document.objects.insert(id, Object::Dictionary(section));
+    }
+
     // 2. Create a unified ParentTree object (Nums)
     let parent_tree_id = document.add_object(dictionary! {
         "Nums" => global_nums.clone(),
@@ -433,11 +818,11 @@ fn assemble_merged_document(
     // 4. PARENT WIRING (/P): This is the "holy grail" of tag visibility in PDFix
     let mut reparented_count = 0;
     for child_ref in &global_kids {
-        if let Ok(child_id) = child_ref.as_reference() {
-            if let Ok(Object::Dictionary(dict)) = document.get_object_mut(child_id) {
-                dict.set("P", root_document_node_id);
-                reparented_count += 1;
-            }
+        if let Ok(child_id) = child_ref.as_reference()
+            && let Ok(Object::Dictionary(dict)) = document.get_object_mut(child_id)
+        {
+            dict.set("P", root_document_node_id);
+            reparented_count += 1;
         }
     }
     tracing::debug!(
@@ -492,3 +877,179 @@ fn assemble_merged_document(
 
     Ok(document)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use lopdf::content::{Content, Operation};
+    use lopdf::{Document, Object, ObjectId, Stream, dictionary};
+
+    use jiff::Timestamp;
+
+    use super::{DocumentMetadata, MergeOptions, PDF_VERSION, merge_pdfs, write_merged};
+    use crate::_pdf_utils::pdf_writer::ObjectSink;
+    use crate::_pdf_utils::pdf_writer::tests::assert_same_objects;
+    use crate::toc::TocNode;
+
+    /// Two-page chapter sharing the same font with the other fixtures
+    fn write_chapter(path: &Path, text: &str) {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+
+        let mut kids = Vec::new();
+        for page in 1..=2 {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![72.into(), 720.into()]),
+                    Operation::new(
+                        "Tj",
+                        vec![Object::string_literal(format!("{} {}", text, page))],
+                    ),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            kids.push(Object::Reference(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                "Contents" => content_id,
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            })));
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! { "Type" => "Pages", "Kids" => kids, "Count" => 2 }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc.save(path).unwrap();
+    }
+
+    fn assert_references_resolve(document: &Document) {
+        fn visit(document: &Document, object: &Object) {
+            match object {
+                Object::Reference(id) => {
+                    assert!(document.objects.contains_key(id), "dangling {:?}", id)
+                }
+                Object::Array(array) => array.iter().for_each(|o| visit(document, o)),
+                Object::Dictionary(dict) => dict.iter().for_each(|(_, o)| visit(document, o)),
+                Object::Stream(stream) => stream.dict.iter().for_each(|(_, o)| visit(document, o)),
+                _ => {}
+            }
+        }
+        document.objects.values().for_each(|o| visit(document, o));
+    }
+
+    fn write_chapters(dir: &Path, titles: &[&str]) -> Vec<TocNode> {
+        titles
+            .iter()
+            .map(|title| {
+                let path = dir.join(format!("{}.pdf", title));
+                write_chapter(&path, title);
+                TocNode {
+                    file_path: Some(path),
+                    title: Some(title.to_string()),
+                    href: String::new(),
+                    level: 0,
+                    lang: None,
+                    final_url: None,
+                }
+            })
+            .collect()
+    }
+
+    /// The in-memory path: every object is kept in the document, which lopdf saves at the end
+    impl ObjectSink for Document {
+        fn write_object(&mut self, id: ObjectId, object: &Object) -> io::Result<()> {
+            self.objects.insert(id, object.clone());
+            Ok(())
+        }
+
+        fn written(&self) -> usize {
+            self.objects.len()
+        }
+    }
+
+    fn fixed_options() -> MergeOptions {
+        MergeOptions {
+            metadata: DocumentMetadata {
+                title: Some("Book".to_string()),
+                date: Some(Timestamp::UNIX_EPOCH),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn incremental_output_matches_in_memory_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let toc = write_chapters(dir.path(), &["Alpha", "Beta"]);
+        let options = fixed_options();
+
+        let output = dir.path().join("merged.pdf");
+        merge_pdfs(toc.clone(), &output, &options).unwrap();
+        let incremental = Document::load(&output).unwrap();
+        assert_references_resolve(&incremental);
+
+        // The same merge kept in memory and saved by lopdf with the identifier of the file
+        let mut in_memory = Document::with_version(PDF_VERSION);
+        let (_, trailer) = write_merged(toc, &options, &mut in_memory).unwrap();
+        in_memory.trailer = trailer;
+        in_memory
+            .trailer
+            .set("ID", incremental.trailer.get(b"ID").unwrap().clone());
+        in_memory.max_id = in_memory.objects.keys().map(|id| id.0).max().unwrap();
+        let mut saved = Vec::new();
+        in_memory.save_to(&mut saved).unwrap();
+        let saved = Document::load_mem(&saved).unwrap();
+
+        // The files differ only in object order and lopdf's cross-reference stream
+        assert_same_objects(&saved, &incremental);
+        for key in [b"Root".as_slice(), b"Info", b"ID"] {
+            assert_eq!(
+                saved.trailer.get(key).ok(),
+                incremental.trailer.get(key).ok()
+            );
+        }
+        assert_eq!(incremental.get_pages().len(), 4);
+        assert!(incremental.extract_text(&[3]).unwrap().contains("Beta 1"));
+    }
+
+    #[test]
+    fn reproducible_output_is_byte_identical() {
+        let dir = tempfile::tempdir().unwrap();
+        let options = fixed_options();
+        let merge = |titles: &[&str], name: &str| {
+            let toc = write_chapters(dir.path(), titles);
+            let output = dir.path().join(name);
+            merge_pdfs(toc, &output, &options).unwrap();
+            std::fs::read(output).unwrap()
+        };
+        let id = |bytes: &[u8]| {
+            Document::load_mem(bytes)
+                .unwrap()
+                .trailer
+                .get(b"ID")
+                .unwrap()
+                .clone()
+        };
+
+        let first = merge(&["Alpha", "Beta"], "first.pdf");
+        let second = merge(&["Alpha", "Beta"], "second.pdf");
+        let changed = merge(&["Alpha", "Gamma"], "changed.pdf");
+
+        assert!(first == second);
+        assert_ne!(id(&first), id(&changed));
+    }
+}
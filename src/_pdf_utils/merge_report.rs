@@ -0,0 +1,138 @@
+use std::{fmt, io, path::PathBuf};
+
+/// Errors which stop the merge
+#[derive(Debug)]
+pub enum MergeError {
+    /// Reading a chapter or writing the output failed
+    Io(io::Error),
+    /// A PDF object couldn't be parsed or modified
+    Pdf(lopdf::Error),
+    /// None of the merged files has a document catalog, e.g. all of them were skipped
+    CatalogNotFound,
+    /// None of the merged files has a page tree root
+    PagesRootNotFound,
+    /// A chapter was degraded and `--strict` is set
+    Strict { title: String, reason: String },
+    /// The font for running headers and footers can't be embedded
+    StampFont(String),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error while merging: {}", e),
+            Self::Pdf(e) => write!(f, "PDF error while merging: {}", e),
+            Self::CatalogNotFound => write!(f, "No document catalog found in the merged files"),
+            Self::PagesRootNotFound => write!(f, "No page tree root found in the merged files"),
+            Self::Strict { title, reason } => {
+                write!(
+                    f,
+                    "Chapter '{}' is degraded (strict mode): {}",
+                    title, reason
+                )
+            }
+            Self::StampFont(e) => write!(f, "Stamp font can't be used: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Pdf(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MergeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<lopdf::Error> for MergeError {
+    fn from(e: lopdf::Error) -> Self {
+        Self::Pdf(e)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChapterStatus {
+    /// The chapter has a structure tree which was merged
+    Tagged,
+    /// The chapter has no StructTreeRoot, its content isn't reachable by assistive technology
+    Untagged,
+    /// The file couldn't be loaded and isn't part of the output
+    Skipped(String),
+}
+
+/// Outcome of merging a single file
+#[derive(Debug, Clone)]
+pub struct ChapterReport {
+    pub title: String,
+    pub file: PathBuf,
+    pub status: ChapterStatus,
+    /// Problems found while sanitizing the structure tree
+    pub warnings: Vec<String>,
+}
+
+impl ChapterReport {
+    pub fn is_degraded(&self) -> bool {
+        self.status != ChapterStatus::Tagged || !self.warnings.is_empty()
+    }
+
+    ///
+    /// Short description of what is wrong with the chapter
+    ///
+    pub fn reason(&self) -> String {
+        let mut reasons = match &self.status {
+            ChapterStatus::Tagged => Vec::new(),
+            ChapterStatus::Untagged => vec!["no StructTreeRoot".to_string()],
+            ChapterStatus::Skipped(error) => vec![format!("skipped: {}", error)],
+        };
+        reasons.extend(self.warnings.iter().cloned());
+        reasons.join("; ")
+    }
+}
+
+/// Per-chapter result of the merge
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    pub chapters: Vec<ChapterReport>,
+}
+
+impl MergeReport {
+    pub fn count(&self, status: &ChapterStatus) -> usize {
+        self.chapters
+            .iter()
+            .filter(|c| match (status, &c.status) {
+                (ChapterStatus::Skipped(_), ChapterStatus::Skipped(_)) => true,
+                (expected, actual) => expected == actual,
+            })
+            .count()
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.chapters.iter().any(ChapterReport::is_degraded)
+    }
+
+    pub fn print(&self) {
+        println!(
+            "  📋 Merge report: {} tagged, {} untagged, {} skipped",
+            self.count(&ChapterStatus::Tagged),
+            self.count(&ChapterStatus::Untagged),
+            self.count(&ChapterStatus::Skipped(String::new()))
+        );
+
+        for chapter in self.chapters.iter().filter(|c| c.is_degraded()) {
+            println!(
+                "     ⚠️ {} ({}): {}",
+                chapter.title,
+                chapter.file.display(),
+                chapter.reason()
+            );
+        }
+    }
+}
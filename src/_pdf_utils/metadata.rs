@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use jiff::Timestamp;
+use lopdf::{Document, Object, ObjectId, Stream, dictionary};
+
+use super::pdfa::PdfaLevel;
+use crate::escape::escape_text;
+
+const PRODUCER: &str = concat!("web2pdf ", env!("CARGO_PKG_VERSION"));
+
+/// Document information written to the Info dictionary and the XMP packet
+#[derive(Debug, Clone, Default)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// Natural language of the document (BCP 47), e.g. "en"
+    pub lang: Option<String>,
+    /// Creation and modification date, defaults to the current time
+    pub date: Option<Timestamp>,
+}
+
+///
+/// Date of the build: `SOURCE_DATE_EPOCH` if it is set, the Unix epoch for reproducible
+/// builds without it, otherwise `None` which means the current time
+///
+pub fn source_date(reproducible: bool) -> anyhow::Result<Option<Timestamp>> {
+    match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => {
+            let seconds: i64 = value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid SOURCE_DATE_EPOCH: {}", value))?;
+            Ok(Some(Timestamp::from_second(seconds)?))
+        }
+        Err(_) if reproducible => Ok(Some(Timestamp::UNIX_EPOCH)),
+        Err(_) => Ok(None),
+    }
+}
+
+///
+/// Remove the Info dictionary and the XMP packet of a chapter, they hold the render time
+/// and the merged document gets its own anyway
+///
+pub fn strip_document_info(doc: &mut Document) {
+    if let Ok(info_id) = doc.trailer.get(b"Info").and_then(|o| o.as_reference()) {
+        doc.objects.remove(&info_id);
+    }
+    doc.trailer.remove(b"Info");
+
+    if let Ok(catalog) = doc.catalog_mut()
+        && let Some(Object::Reference(metadata_id)) = catalog.remove(b"Metadata")
+    {
+        doc.objects.remove(&metadata_id);
+    }
+}
+
+///
+/// Pick the language used by the most chapters
+///
+pub fn dominant_lang<'a>(langs: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for lang in langs {
+        *counts.entry(lang).or_default() += 1;
+    }
+
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(lang, _)| lang.to_string())
+}
+
+///
+/// Write the Info dictionary, XMP metadata, /Lang and ViewerPreferences
+///
+/// Must be called after the trailer is created, because the Info dictionary is linked from it.
+/// With `pdfa` set the XMP packet also gets the PDF/A identification, with `pdfua` set
+/// the PDF/UA one, which may only be claimed when all content is tagged.
+///
+pub fn apply_metadata(
+    document: &mut Document,
+    catalog_id: ObjectId,
+    metadata: &DocumentMetadata,
+    pdfa: Option<PdfaLevel>,
+    pdfua: bool,
+) -> lopdf::Result<()> {
+    let date = metadata.date.unwrap_or_else(Timestamp::now);
+
+    // 1. Info dictionary
+    let mut info = dictionary! {
+        "Producer" => text_string(PRODUCER),
+        "Creator" => text_string(PRODUCER),
+        "CreationDate" => Object::from(date),
+        "ModDate" => Object::from(date),
+    };
+    let fields = [
+        ("Title", &metadata.title),
+        ("Author", &metadata.author),
+        ("Subject", &metadata.subject),
+        ("Keywords", &metadata.keywords),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            info.set(key, text_string(value));
+        }
+    }
+    let info_id = document.add_object(info);
+    document.trailer.set("Info", info_id);
+
+    // 2. XMP metadata stream, it must stay uncompressed to be readable by tools without PDF parsers
+    let xmp = build_xmp(metadata, date, pdfa, pdfua);
+    let metadata_stream = Stream::new(
+        dictionary! {
+            "Type" => "Metadata",
+            "Subtype" => "XML",
+        },
+        xmp.into_bytes(),
+    )
+    .with_compression(false);
+    let metadata_id = document.add_object(metadata_stream);
+
+    // 3. Catalog: Lang, Metadata and show the title instead of the file name
+    let catalog = document.get_dictionary_mut(catalog_id)?;
+    if let Some(lang) = &metadata.lang {
+        catalog.set("Lang", text_string(lang));
+    }
+    catalog.set("Metadata", metadata_id);
+    catalog.set(
+        "ViewerPreferences",
+        dictionary! { "DisplayDocTitle" => true },
+    );
+
+    tracing::debug!(
+        "Metadata applied: title={:?}, lang={:?}",
+        metadata.title,
+        metadata.lang
+    );
+
+    Ok(())
+}
+
+fn build_xmp(
+    metadata: &DocumentMetadata,
+    date: Timestamp,
+    pdfa: Option<PdfaLevel>,
+    pdfua: bool,
+) -> String {
+    let date = date.strftime("%Y-%m-%dT%H:%M:%SZ").to_string();
+
+    let mut dc = String::from("<dc:format>application/pdf</dc:format>\n");
+    if let Some(title) = &metadata.title {
+        dc.push_str(&format!(
+            "<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n",
+            escape_text(title)
+        ));
+    }
+    if let Some(author) = &metadata.author {
+        dc.push_str(&format!(
+            "<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            escape_text(author)
+        ));
+    }
+    if let Some(subject) = &metadata.subject {
+        dc.push_str(&format!(
+            "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n",
+            escape_text(subject)
+        ));
+    }
+    if let Some(lang) = &metadata.lang {
+        dc.push_str(&format!(
+            "<dc:language><rdf:Bag><rdf:li>{}</rdf:li></rdf:Bag></dc:language>\n",
+            escape_text(lang)
+        ));
+    }
+
+    let keywords = metadata
+        .keywords
+        .as_ref()
+        .map(|k| format!("<pdf:Keywords>{}</pdf:Keywords>\n", escape_text(k)))
+        .unwrap_or_default();
+
+    let pdfua_part = if pdfua {
+        "<pdfuaid:part>1</pdfuaid:part>\n"
+    } else {
+        ""
+    };
+    let pdfa = pdfa
+        .map(|level| pdfa_identification(level, pdfua))
+        .unwrap_or_default();
+
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about=""
+  xmlns:dc="http://purl.org/dc/elements/1.1/"
+  xmlns:pdf="http://ns.adobe.com/pdf/1.3/"
+  xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+  xmlns:pdfuaid="http://www.aiim.org/pdfua/ns/id/">
+{dc}{keywords}<pdf:Producer>{producer}</pdf:Producer>
+<xmp:CreatorTool>{producer}</xmp:CreatorTool>
+<xmp:CreateDate>{date}</xmp:CreateDate>
+<xmp:ModifyDate>{date}</xmp:ModifyDate>
+<xmp:MetadataDate>{date}</xmp:MetadataDate>
+{pdfua_part}</rdf:Description>
+{pdfa}</rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{FEFF}',
+        producer = PRODUCER,
+    )
+}
+
+///
+/// PDF/A identification and, with `pdfua`, the extension schema for the pdfuaid namespace,
+/// which isn't predefined by PDF/A
+///
+fn pdfa_identification(level: PdfaLevel, pdfua: bool) -> String {
+    let identification = format!(
+        r#"<rdf:Description rdf:about=""
+  xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/">
+<pdfaid:part>{part}</pdfaid:part>
+<pdfaid:conformance>{conformance}</pdfaid:conformance>
+</rdf:Description>
+"#,
+        part = level.part(),
+        conformance = level.conformance(),
+    );
+    if !pdfua {
+        return identification;
+    }
+
+    identification
+        + r#"<rdf:Description rdf:about=""
+  xmlns:pdfaExtension="http://www.aiim.org/pdfa/ns/extension/"
+  xmlns:pdfaSchema="http://www.aiim.org/pdfa/ns/schema#"
+  xmlns:pdfaProperty="http://www.aiim.org/pdfa/ns/property#">
+<pdfaExtension:schemas><rdf:Bag><rdf:li rdf:parseType="Resource">
+<pdfaSchema:schema>PDF/UA Universal Accessibility Schema</pdfaSchema:schema>
+<pdfaSchema:namespaceURI>http://www.aiim.org/pdfua/ns/id/</pdfaSchema:namespaceURI>
+<pdfaSchema:prefix>pdfuaid</pdfaSchema:prefix>
+<pdfaSchema:property><rdf:Seq><rdf:li rdf:parseType="Resource">
+<pdfaProperty:name>part</pdfaProperty:name>
+<pdfaProperty:valueType>Integer</pdfaProperty:valueType>
+<pdfaProperty:category>internal</pdfaProperty:category>
+<pdfaProperty:description>Indicates, which part of ISO 14289 standard is followed</pdfaProperty:description>
+</rdf:li></rdf:Seq></pdfaSchema:property>
+</rdf:li></rdf:Bag></pdfaExtension:schemas>
+</rdf:Description>
+"#
+}
+
+///
+/// Encode a PDF text string: PDFDocEncoding for ASCII, UTF-16BE with BOM otherwise
+///
+pub fn text_string(value: &str) -> Object {
+    if value.is_ascii() {
+        return Object::string_literal(value);
+    }
+
+    let mut bytes = vec![0xFE, 0xFF];
+    for unit in value.encode_utf16() {
+        bytes.extend_from_slice(&unit.to_be_bytes());
+    }
+    Object::string_literal(bytes)
+}
@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use lopdf::{Dictionary, Document, Object, ObjectId, decode_text_string};
+
+/// A heading found in the outline of a single chapter PDF
+#[derive(Debug, Clone)]
+pub struct OutlineHeading {
+    /// Nesting depth in the source outline, starting from 1
+    pub depth: usize,
+    pub title: String,
+    /// Page the heading points to
+    pub page: ObjectId,
+}
+
+///
+/// Read the document outline (e.g. generated by Chrome from H1–H6) in document order
+/// and remove it, so its items don't end up as orphan objects of the merged file
+///
+pub fn take_outline(doc: &mut Document) -> Vec<OutlineHeading> {
+    let Ok(outlines) = doc
+        .catalog()
+        .and_then(|c| c.get(b"Outlines"))
+        .and_then(|o| o.as_reference())
+    else {
+        return Vec::new();
+    };
+
+    let (headings, items) = read_outline(doc, outlines);
+
+    for id in items.into_iter().chain([outlines]) {
+        doc.objects.remove(&id);
+    }
+    if let Ok(catalog) = doc.catalog_mut() {
+        catalog.remove(b"Outlines");
+    }
+
+    headings
+}
+
+///
+/// Walk the outline items, returns the headings and ids of all visited items
+///
+fn read_outline(doc: &Document, outlines: ObjectId) -> (Vec<OutlineHeading>, HashSet<ObjectId>) {
+    let mut headings = Vec::new();
+
+    let Ok(first) = doc
+        .get_dictionary(outlines)
+        .and_then(|outlines| outlines.get(b"First"))
+        .and_then(|o| o.as_reference())
+    else {
+        return (headings, HashSet::new());
+    };
+
+    // Broken outlines may contain cycles
+    let mut visited = HashSet::new();
+    let mut stack = vec![(first, 1)];
+    while let Some((id, depth)) = stack.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let Ok(item) = doc.get_dictionary(id) else {
+            continue;
+        };
+
+        // Siblings go below children on the stack, so the walk stays in document order
+        if let Ok(next) = item.get(b"Next").and_then(|o| o.as_reference()) {
+            stack.push((next, depth));
+        }
+        if let Ok(child) = item.get(b"First").and_then(|o| o.as_reference()) {
+            stack.push((child, depth + 1));
+        }
+
+        let title = item
+            .get(b"Title")
+            .and_then(decode_text_string)
+            .map(|t| t.trim().to_string())
+            .unwrap_or_default();
+        if title.is_empty() {
+            continue;
+        }
+
+        if let Some(page) = destination_page(doc, item) {
+            headings.push(OutlineHeading { depth, title, page });
+        }
+    }
+
+    (headings, visited)
+}
+
+///
+/// Compare titles ignoring case, whitespace and a "Chapter N - " prefix of the chapter title
+///
+pub fn same_title(chapter_title: &str, heading: &str) -> bool {
+    let normalize = |s: &str| {
+        s.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    let chapter = normalize(chapter_title);
+    let heading = normalize(heading);
+
+    !heading.is_empty() && (chapter == heading || chapter.ends_with(&format!(" - {}", heading)))
+}
+
+fn destination_page(doc: &Document, item: &Dictionary) -> Option<ObjectId> {
+    let dest = match item.get(b"Dest") {
+        Ok(dest) => dest,
+        Err(_) => item
+            .get(b"A")
+            .ok()
+            .and_then(|a| resolve_dict(doc, a).ok())
+            .filter(|a| a.get(b"S").and_then(|s| s.as_name()).ok() == Some(b"GoTo"))?
+            .get(b"D")
+            .ok()?,
+    };
+
+    resolve_destination(doc, dest)?.first()?.as_reference().ok()
+}
+
+///
+/// Turn a direct, named or dictionary destination into the explicit destination array
+///
+fn resolve_destination<'a>(doc: &'a Document, dest: &'a Object) -> Option<&'a Vec<Object>> {
+    let dest = match dest {
+        Object::Reference(id) => doc.get_object(*id).ok()?,
+        other => other,
+    };
+
+    match dest {
+        Object::Array(array) => Some(array),
+        Object::Dictionary(dict) => resolve_destination(doc, dict.get(b"D").ok()?),
+        Object::Name(name) => {
+            let dests = doc
+                .catalog()
+                .and_then(|c| c.get(b"Dests"))
+                .and_then(|o| resolve_dict(doc, o))
+                .ok()?;
+            resolve_destination(doc, dests.get(name).ok()?)
+        }
+        Object::String(name, _) => {
+            let tree = doc
+                .catalog()
+                .and_then(|c| c.get(b"Names"))
+                .and_then(|o| resolve_dict(doc, o))
+                .and_then(|names| names.get(b"Dests"))
+                .and_then(|o| resolve_dict(doc, o))
+                .ok()?;
+            resolve_destination(doc, find_in_name_tree(doc, tree, name, 0)?)
+        }
+        _ => None,
+    }
+}
+
+fn find_in_name_tree<'a>(
+    doc: &'a Document,
+    node: &'a Dictionary,
+    key: &[u8],
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > 32 {
+        return None;
+    }
+
+    if let Ok(names) = node.get(b"Names").and_then(|o| o.as_array()) {
+        for pair in names.chunks(2) {
+            if let [Object::String(name, _), value] = pair
+                && name == key
+            {
+                return Some(value);
+            }
+        }
+    }
+
+    node.get(b"Kids")
+        .and_then(|o| o.as_array())
+        .ok()?
+        .iter()
+        .filter_map(|kid| resolve_dict(doc, kid).ok())
+        .find_map(|kid| find_in_name_tree(doc, kid, key, depth + 1))
+}
+
+fn resolve_dict<'a>(doc: &'a Document, object: &'a Object) -> lopdf::Result<&'a Dictionary> {
+    match object {
+        Object::Reference(id) => doc.get_dictionary(*id),
+        other => other.as_dict(),
+    }
+}
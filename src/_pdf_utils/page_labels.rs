@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use lopdf::{Dictionary, Object, dictionary};
+
+use super::metadata::text_string;
+
+/// Numbering style of the document body
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PageLabelStyle {
+    /// Continuous decimal numbers: 1, 2, 3, ...
+    #[default]
+    Decimal,
+    /// Numbers restart in every top-level chapter with its number as a prefix: 3-1, 3-2, ...
+    Chapter,
+}
+
+/// Kind of a merged part, decides how its pages are labeled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagePart {
+    /// Cover, contents and other pages before the body (lowercase roman numerals)
+    FrontMatter,
+    /// A TOC chapter of the given nesting level
+    Chapter { level: u8 },
+}
+
+/// Collects label ranges while the documents are merged
+#[derive(Debug, Default)]
+pub struct PageLabels {
+    style: PageLabelStyle,
+    ranges: BTreeMap<i64, Dictionary>,
+    front_started: bool,
+    body_started: bool,
+    chapter: u32,
+}
+
+impl PageLabels {
+    ///
+    /// `first_chapter` is the number of top-level chapters in the previous volumes of a split book,
+    /// chapter labels continue after them
+    ///
+    pub fn new(style: PageLabelStyle, first_chapter: u32) -> Self {
+        Self {
+            style,
+            chapter: first_chapter,
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Register a merged part starting at the zero-based page index
+    ///
+    pub fn start_part(&mut self, page_index: u32, part: PagePart) {
+        let key = page_index as i64;
+
+        match part {
+            PagePart::FrontMatter => {
+                if !self.front_started {
+                    self.front_started = true;
+                    self.ranges.insert(key, dictionary! { "S" => "r" });
+                }
+            }
+            PagePart::Chapter { level } => {
+                let new_chapter = level == 0;
+                if new_chapter {
+                    self.chapter += 1;
+                }
+
+                match self.style {
+                    PageLabelStyle::Decimal if !self.body_started => {
+                        self.ranges.insert(key, dictionary! { "S" => "D" });
+                    }
+                    // Nested chapters at the start continue the chapter of the previous volume
+                    PageLabelStyle::Chapter if new_chapter || !self.body_started => {
+                        let range = match self.chapter {
+                            0 => dictionary! { "S" => "D" },
+                            chapter => dictionary! {
+                                "S" => "D",
+                                "P" => text_string(&format!("{}-", chapter)),
+                            },
+                        };
+                        self.ranges.insert(key, range);
+                    }
+                    _ => {}
+                }
+                self.body_started = true;
+            }
+        }
+    }
+
+    ///
+    /// Build the /PageLabels number tree, `None` if no pages were registered
+    ///
+    pub fn into_number_tree(self) -> Option<Dictionary> {
+        if self.ranges.is_empty() {
+            return None;
+        }
+
+        let mut nums = Vec::with_capacity(self.ranges.len() * 2);
+        for (index, (start, label)) in self.ranges.into_iter().enumerate() {
+            // The first range must cover the first page
+            let start = if index == 0 { 0 } else { start };
+            nums.push(Object::Integer(start));
+            nums.push(Object::Dictionary(label));
+        }
+
+        Some(dictionary! { "Nums" => nums })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::Object;
+
+    use super::{PageLabelStyle, PageLabels, PagePart};
+
+    const TOP: PagePart = PagePart::Chapter { level: 0 };
+    const NESTED: PagePart = PagePart::Chapter { level: 1 };
+
+    /// Start page, numbering style and prefix of every label range
+    fn ranges(labels: PageLabels) -> Vec<(i64, String, Option<String>)> {
+        let tree = labels.into_number_tree().unwrap();
+        let nums = tree.get(b"Nums").unwrap().as_array().unwrap();
+        nums.chunks(2)
+            .map(|pair| {
+                let label = pair[1].as_dict().unwrap();
+                (
+                    pair[0].as_i64().unwrap(),
+                    String::from_utf8(label.get(b"S").unwrap().as_name().unwrap().to_vec())
+                        .unwrap(),
+                    label
+                        .get(b"P")
+                        .and_then(Object::as_str)
+                        .map(|p| String::from_utf8(p.to_vec()).unwrap())
+                        .ok(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn front_matter_is_roman_and_body_decimal() {
+        let mut labels = PageLabels::new(PageLabelStyle::Decimal, 0);
+        labels.start_part(0, PagePart::FrontMatter);
+        labels.start_part(1, TOP);
+        labels.start_part(4, NESTED);
+        labels.start_part(6, TOP);
+
+        assert_eq!(
+            ranges(labels),
+            [(0, "r".to_string(), None), (1, "D".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn first_range_covers_the_first_page() {
+        let mut labels = PageLabels::new(PageLabelStyle::Decimal, 0);
+        labels.start_part(2, TOP);
+
+        assert_eq!(ranges(labels), [(0, "D".to_string(), None)]);
+        assert!(
+            PageLabels::new(PageLabelStyle::Decimal, 0)
+                .into_number_tree()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn chapter_style_restarts_in_top_level_chapters() {
+        let mut labels = PageLabels::new(PageLabelStyle::Chapter, 0);
+        labels.start_part(0, PagePart::FrontMatter);
+        labels.start_part(1, TOP);
+        labels.start_part(3, NESTED);
+        labels.start_part(5, TOP);
+
+        assert_eq!(
+            ranges(labels),
+            [
+                (0, "r".to_string(), None),
+                (1, "D".to_string(), Some("1-".to_string())),
+                (5, "D".to_string(), Some("2-".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn chapter_numbers_continue_across_volumes() {
+        // The second volume starts inside chapter 2 of the book
+        let mut labels = PageLabels::new(PageLabelStyle::Chapter, 2);
+        labels.start_part(0, PagePart::FrontMatter);
+        labels.start_part(1, NESTED);
+        labels.start_part(4, TOP);
+
+        assert_eq!(
+            ranges(labels),
+            [
+                (0, "r".to_string(), None),
+                (1, "D".to_string(), Some("2-".to_string())),
+                (4, "D".to_string(), Some("3-".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use lopdf::{Dictionary, Object, ObjectId, StringFormat};
+use md5::{Digest, Md5};
+
+/// Binary comment after the header, all bytes > 127 as required by PDF/A
+const BINARY_MARK: [u8; 4] = [0xBB, 0xAD, 0xC0, 0xDE];
+
+/// Destination of the indirect objects of a merged document
+pub trait ObjectSink {
+    fn write_object(&mut self, id: ObjectId, object: &Object) -> io::Result<()>;
+
+    /// Number of objects written so far
+    fn written(&self) -> usize;
+}
+
+/// Writes indirect objects to the output as soon as they are ready
+/// and finishes the file with a classic cross-reference table
+///
+/// The serialization follows `lopdf::Document::save`, so the files look the same.
+pub struct PdfWriter<W: Write> {
+    out: W,
+    offset: u64,
+    /// Offset and generation of every written object
+    xref: BTreeMap<u32, (u64, u16)>,
+    /// Digest of the written bytes, it becomes the file identifier
+    digest: Md5,
+}
+
+impl<W: Write> PdfWriter<W> {
+    pub fn new(mut out: W, version: &str) -> io::Result<Self> {
+        let mut header = format!("%PDF-{}\n%", version).into_bytes();
+        header.extend_from_slice(&BINARY_MARK);
+        header.push(b'\n');
+        out.write_all(&header)?;
+
+        Ok(Self {
+            out,
+            offset: header.len() as u64,
+            xref: BTreeMap::new(),
+            digest: Md5::new_with_prefix(&header),
+        })
+    }
+
+    ///
+    /// Write the cross-reference table and the trailer, `Size` is set from the written objects
+    /// and `ID` from their digest, so the same content always gets the same identifier
+    ///
+    pub fn finish(mut self, mut trailer: Dictionary) -> io::Result<W> {
+        let size = self.xref.keys().next_back().map_or(1, |max| max + 1);
+        trailer.set("Size", size as i64);
+
+        // Both parts are equal for a newly created file
+        let id = Object::String(self.digest.finalize().to_vec(), StringFormat::Hexadecimal);
+        trailer.set("ID", vec![id.clone(), id]);
+
+        let xref_start = self.offset;
+        let mut buf = b"xref\n".to_vec();
+
+        // Sections of consecutive ids, the first one starts with the free entry 0
+        let mut sections: Vec<(u32, Vec<String>)> =
+            vec![(0, vec![format!("{:>010} {:>05} f ", 0, 65535)])];
+        for (&id, &(offset, generation)) in &self.xref {
+            let entry = format!("{:>010} {:>05} n ", offset, generation);
+            match sections.last_mut() {
+                Some((start, entries)) if *start + entries.len() as u32 == id => {
+                    entries.push(entry)
+                }
+                _ => sections.push((id, vec![entry])),
+            }
+        }
+        for (start, entries) in sections {
+            buf.extend_from_slice(format!("{} {}\n", start, entries.len()).as_bytes());
+            for entry in entries {
+                buf.extend_from_slice(entry.as_bytes());
+                buf.push(b'\n');
+            }
+        }
+
+        buf.extend_from_slice(b"trailer\n");
+        serialize(&Object::Dictionary(trailer), &mut buf);
+        buf.extend_from_slice(format!("\nstartxref\n{}\n%%EOF", xref_start).as_bytes());
+
+        self.out.write_all(&buf)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> ObjectSink for PdfWriter<W> {
+    fn written(&self) -> usize {
+        self.xref.len()
+    }
+
+    fn write_object(&mut self, id: ObjectId, object: &Object) -> io::Result<()> {
+        let mut buf = format!("{} {} obj\n", id.0, id.1).into_bytes();
+        if need_separator(object) {
+            buf.push(b' ');
+        }
+        serialize(object, &mut buf);
+        if need_end_separator(object) {
+            buf.push(b' ');
+        }
+        buf.extend_from_slice(b"\nendobj\n");
+
+        self.out.write_all(&buf)?;
+        self.digest.update(&buf);
+        self.xref.insert(id.0, (self.offset, id.1));
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+}
+
+fn need_separator(object: &Object) -> bool {
+    matches!(
+        object,
+        Object::Null
+            | Object::Boolean(_)
+            | Object::Integer(_)
+            | Object::Real(_)
+            | Object::Reference(_)
+    )
+}
+
+fn need_end_separator(object: &Object) -> bool {
+    matches!(
+        object,
+        Object::Null
+            | Object::Boolean(_)
+            | Object::Integer(_)
+            | Object::Real(_)
+            | Object::Name(_)
+            | Object::Reference(_)
+            | Object::Stream(_)
+    )
+}
+
+fn serialize(object: &Object, buf: &mut Vec<u8>) {
+    match object {
+        Object::Null => buf.extend_from_slice(b"null"),
+        Object::Boolean(value) => buf.extend_from_slice(if *value { b"true" } else { b"false" }),
+        Object::Integer(value) => buf.extend_from_slice(value.to_string().as_bytes()),
+        Object::Real(value) => buf.extend_from_slice(value.to_string().as_bytes()),
+        Object::Name(name) => serialize_name(name, buf),
+        Object::String(text, format) => serialize_string(text, format, buf),
+        Object::Array(array) => {
+            buf.push(b'[');
+            for (index, item) in array.iter().enumerate() {
+                if index > 0 && need_separator(item) {
+                    buf.push(b' ');
+                }
+                serialize(item, buf);
+            }
+            buf.push(b']');
+        }
+        Object::Dictionary(dict) => serialize_dictionary(dict, buf),
+        Object::Stream(stream) => {
+            // Length must match the content, it may be stale or indirect after modifications
+            let mut dict = stream.dict.clone();
+            dict.set("Length", stream.content.len() as i64);
+            serialize_dictionary(&dict, buf);
+            buf.extend_from_slice(b"stream\n");
+            buf.extend_from_slice(&stream.content);
+            buf.extend_from_slice(b"\nendstream");
+        }
+        Object::Reference(id) => buf.extend_from_slice(format!("{} {} R", id.0, id.1).as_bytes()),
+    }
+}
+
+fn serialize_dictionary(dict: &Dictionary, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(b"<<");
+    for (key, value) in dict.iter() {
+        serialize_name(key, buf);
+        if need_separator(value) {
+            buf.push(b' ');
+        }
+        serialize(value, buf);
+    }
+    buf.extend_from_slice(b">>");
+}
+
+fn serialize_name(name: &[u8], buf: &mut Vec<u8>) {
+    buf.push(b'/');
+    for &byte in name {
+        // White-space, delimiters and bytes outside 33..=126 are written as # sequences
+        if b" \t\n\r\x0C()<>[]{}/%#".contains(&byte) || !(33..=126).contains(&byte) {
+            buf.extend_from_slice(format!("#{:02X}", byte).as_bytes());
+        } else {
+            buf.push(byte);
+        }
+    }
+}
+
+fn serialize_string(text: &[u8], format: &StringFormat, buf: &mut Vec<u8>) {
+    match format {
+        StringFormat::Literal => {
+            // Backslashes, carriage returns and unbalanced parentheses are escaped
+            let mut escaped = vec![false; text.len()];
+            let mut open = Vec::new();
+            for (index, &byte) in text.iter().enumerate() {
+                match byte {
+                    b'(' => open.push(index),
+                    b')' if open.pop().is_none() => escaped[index] = true,
+                    b'\\' | b'\r' => escaped[index] = true,
+                    _ => {}
+                }
+            }
+            for index in open {
+                escaped[index] = true;
+            }
+
+            buf.push(b'(');
+            for (&byte, escaped) in text.iter().zip(escaped) {
+                if escaped {
+                    buf.push(b'\\');
+                    buf.push(if byte == b'\r' { b'r' } else { byte });
+                } else {
+                    buf.push(byte);
+                }
+            }
+            buf.push(b')');
+        }
+        StringFormat::Hexadecimal => {
+            buf.push(b'<');
+            for byte in text {
+                buf.extend_from_slice(format!("{:02X}", byte).as_bytes());
+            }
+            buf.push(b'>');
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use lopdf::{Document, Object, Stream, StringFormat, dictionary};
+
+    use super::{ObjectSink, PdfWriter};
+
+    /// Objects of every kind with gaps in the ids, as left by the deduplication
+    fn sample_document() -> Document {
+        let mut document = Document::with_version("1.7");
+        let objects = [
+            (
+                1,
+                Object::Dictionary(dictionary! { "Type" => "Catalog", "Pages" => (2, 0) }),
+            ),
+            (
+                2,
+                Object::Dictionary(
+                    dictionary! { "Type" => "Pages", "Kids" => vec![], "Count" => 0 },
+                ),
+            ),
+            (5, Object::Integer(-42)),
+            (6, Object::Real(0.25)),
+            (
+                9,
+                Object::string_literal(b"(unbalanced \\ paren\r".to_vec()),
+            ),
+            (
+                10,
+                Object::String(vec![0xFE, 0xFF, 0x04, 0x1A], StringFormat::Hexadecimal),
+            ),
+            (11, Object::Name(b"A B#C/D".to_vec())),
+            (
+                12,
+                Object::Array(vec![
+                    Object::Null,
+                    Object::Boolean(true),
+                    Object::Reference((5, 0)),
+                    Object::Name(b"Next".to_vec()),
+                    Object::Array(vec![1.into(), 2.5.into()]),
+                ]),
+            ),
+            (
+                20,
+                Object::Stream(Stream::new(
+                    dictionary! { "Length" => 999, "Filter" => "FlateDecode" },
+                    vec![0, 1, 2, 255, b'\n'],
+                )),
+            ),
+        ];
+        for (id, object) in objects {
+            document.objects.insert((id, 0), object);
+        }
+        document.max_id = 20;
+        document.trailer.set("Root", (1, 0));
+        document
+    }
+
+    fn write(document: &Document) -> Vec<u8> {
+        let mut writer = PdfWriter::new(Vec::new(), &document.version).unwrap();
+        for (id, object) in &document.objects {
+            writer.write_object(*id, object).unwrap();
+        }
+        writer.finish(document.trailer.clone()).unwrap()
+    }
+
+    pub(in crate::_pdf_utils) fn assert_same_objects(expected: &Document, actual: &Document) {
+        // lopdf saves with a cross-reference stream, which is an object itself
+        let ids = |document: &Document| -> Vec<_> {
+            document
+                .objects
+                .iter()
+                .filter(|(_, object)| object.type_name().ok() != Some(b"XRef".as_slice()))
+                .map(|(id, _)| *id)
+                .collect()
+        };
+        assert_eq!(ids(expected), ids(actual));
+        for id in ids(expected) {
+            let object = &expected.objects[&id];
+            match (object, &actual.objects[&id]) {
+                // Streams also remember their position in the file
+                (Object::Stream(expected), Object::Stream(actual)) => {
+                    assert_eq!(expected.dict, actual.dict, "{:?}", id);
+                    assert_eq!(expected.content, actual.content, "{:?}", id);
+                }
+                (expected, actual) => assert_eq!(expected, actual, "{:?}", id),
+            }
+        }
+    }
+
+    #[test]
+    fn written_file_loads_like_lopdf_save() {
+        let mut document = sample_document();
+        let written = Document::load_mem(&write(&document)).unwrap();
+
+        let mut saved = Vec::new();
+        document.save_to(&mut saved).unwrap();
+        let saved = Document::load_mem(&saved).unwrap();
+
+        assert_same_objects(&saved, &written);
+        assert_eq!(
+            written.trailer.get(b"Root").unwrap(),
+            &Object::Reference((1, 0))
+        );
+        assert_eq!(written.trailer.get(b"Size").unwrap(), &Object::Integer(21));
+    }
+
+    #[test]
+    fn cross_reference_sections_skip_missing_ids() {
+        let bytes = write(&sample_document());
+        let text = String::from_utf8_lossy(&bytes);
+        let xref = &text[text.rfind("\nxref\n").unwrap()..text.rfind("trailer").unwrap()];
+
+        let sections: Vec<&str> = xref
+            .lines()
+            .filter(|line| line.split(' ').count() == 2)
+            .collect();
+        assert_eq!(sections, ["0 3", "5 2", "9 4", "20 1"]);
+    }
+}
@@ -0,0 +1,613 @@
+use std::collections::{BTreeMap, HashSet};
+
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, dictionary};
+
+/// PDF/A conformance level of the archival output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PdfaLevel {
+    /// PDF/A-2u (ISO 19005-2, Unicode)
+    #[value(name = "2u")]
+    A2u,
+    /// PDF/A-3u (ISO 19005-3, Unicode)
+    #[value(name = "3u")]
+    A3u,
+}
+
+impl PdfaLevel {
+    pub fn part(self) -> u8 {
+        match self {
+            PdfaLevel::A2u => 2,
+            PdfaLevel::A3u => 3,
+        }
+    }
+
+    pub fn conformance(self) -> &'static str {
+        "U"
+    }
+}
+
+/// Objects fixed or left non-conformant by the PDF/A pass
+#[derive(Debug, Default)]
+pub struct PdfaReport {
+    pub fixed: Vec<String>,
+    pub unresolved: Vec<String>,
+}
+
+impl PdfaReport {
+    pub fn print(&self) {
+        if !self.fixed.is_empty() {
+            println!("  🔧 PDF/A: fixed {} object(s)", self.fixed.len());
+            for item in &self.fixed {
+                tracing::debug!("PDF/A fixed: {}", item);
+            }
+        }
+
+        if self.unresolved.is_empty() {
+            println!("  ✅ PDF/A: no unresolved objects");
+        } else {
+            println!(
+                "  ⚠️ PDF/A: {} object(s) could not be made conformant:",
+                self.unresolved.len()
+            );
+            for item in &self.unresolved {
+                println!("     - {}", item);
+            }
+        }
+    }
+}
+
+/// Actions which are not allowed in PDF/A-2 and PDF/A-3
+const FORBIDDEN_ACTIONS: [&[u8]; 11] = [
+    b"Launch",
+    b"Sound",
+    b"Movie",
+    b"ResetForm",
+    b"ImportData",
+    b"Hide",
+    b"SetOCGState",
+    b"Rendition",
+    b"Trans",
+    b"GoTo3DView",
+    b"JavaScript",
+];
+
+/// Keys which hold an action
+const ACTION_KEYS: [&[u8]; 3] = [b"A", b"OpenAction", b"Next"];
+
+/// Named actions allowed by PDF/A
+const ALLOWED_NAMED_ACTIONS: [&[u8]; 4] = [b"NextPage", b"PrevPage", b"FirstPage", b"LastPage"];
+
+///
+/// Make the document level parts of the merged document PDF/A conformant
+///
+/// The XMP identification is written by `apply_metadata`, this pass adds the OutputIntent,
+/// removes forbidden catalog entries and then fixes the objects of the document itself.
+/// Chapter objects are fixed by `fix_objects` before they are written.
+///
+pub fn apply_pdfa(
+    document: &mut Document,
+    catalog_id: ObjectId,
+    level: PdfaLevel,
+    report: &mut PdfaReport,
+) {
+    // 1. Encryption is forbidden
+    if document.trailer.remove(b"Encrypt").is_some() {
+        report
+            .fixed
+            .push("Removed Encrypt from trailer".to_string());
+    }
+
+    // 2. sRGB OutputIntent
+    let icc_id = document.add_object(Stream::new(dictionary! { "N" => 3 }, srgb_icc_profile()));
+    let output_intent = dictionary! {
+        "Type" => "OutputIntent",
+        "S" => "GTS_PDFA1",
+        "OutputConditionIdentifier" => Object::string_literal("sRGB IEC61966-2.1"),
+        "Info" => Object::string_literal("sRGB IEC61966-2.1"),
+        "DestOutputProfile" => icc_id,
+    };
+
+    if let Ok(catalog) = document.get_dictionary_mut(catalog_id) {
+        catalog.set("OutputIntents", vec![Object::Dictionary(output_intent)]);
+
+        // 3. Document level JavaScript and actions
+        if catalog.remove(b"AA").is_some() {
+            report
+                .fixed
+                .push("Removed additional actions from Catalog".to_string());
+        }
+        if catalog.remove(b"NeedsRendering").is_some() {
+            report
+                .fixed
+                .push("Removed NeedsRendering from Catalog".to_string());
+        }
+        if let Ok(Object::Dictionary(names)) = catalog.get_mut(b"Names")
+            && names.remove(b"JavaScript").is_some()
+        {
+            report
+                .fixed
+                .push("Removed JavaScript name tree".to_string());
+        }
+        if level == PdfaLevel::A2u
+            && let Ok(Object::Dictionary(names)) = catalog.get_mut(b"Names")
+            && names.remove(b"EmbeddedFiles").is_some()
+        {
+            report
+                .fixed
+                .push("Removed embedded files (not allowed in PDF/A-2u)".to_string());
+        }
+    }
+
+    fix_objects(document, report);
+
+    tracing::info!(
+        "PDF/A-{}{} pass: fixed {}, unresolved {}",
+        level.part(),
+        level.conformance().to_lowercase(),
+        report.fixed.len(),
+        report.unresolved.len()
+    );
+}
+
+///
+/// Remove forbidden constructs from all objects (actions, annotations, streams)
+/// and report what couldn't be fixed (e.g. non-embedded fonts)
+///
+pub fn fix_objects(document: &mut Document, report: &mut PdfaReport) {
+    let forbidden_actions = collect_forbidden_actions(document);
+    let ids: Vec<ObjectId> = document.objects.keys().copied().collect();
+    for id in ids {
+        let Some(object) = document.objects.get_mut(&id) else {
+            continue;
+        };
+        let (dict, is_stream) = match object {
+            Object::Dictionary(dict) => (dict, false),
+            Object::Stream(stream) => (&mut stream.dict, true),
+            _ => continue,
+        };
+
+        fix_dictionary(id, dict, is_stream, &forbidden_actions, report);
+    }
+
+    check_fonts(document, report);
+}
+
+fn fix_dictionary(
+    id: ObjectId,
+    dict: &mut Dictionary,
+    is_stream: bool,
+    forbidden_actions: &HashSet<ObjectId>,
+    report: &mut PdfaReport,
+) {
+    // Actions (/A, /OpenAction, chained /Next) either inline or as references
+    for key in ACTION_KEYS {
+        let forbidden = match dict.get(key) {
+            Ok(Object::Dictionary(action)) => is_forbidden_action(action),
+            Ok(Object::Reference(action_id)) => forbidden_actions.contains(action_id),
+            _ => false,
+        };
+        if forbidden {
+            dict.remove(key);
+            report.fixed.push(format!(
+                "Removed forbidden /{} action from {:?}",
+                String::from_utf8_lossy(key),
+                id
+            ));
+        }
+    }
+    if dict.remove(b"AA").is_some() {
+        report
+            .fixed
+            .push(format!("Removed additional actions from {:?}", id));
+    }
+
+    // Type is optional for annotations
+    if dict.has_type(b"Annot") || (dict.has(b"Subtype") && dict.has(b"Rect")) {
+        let subtype = dict
+            .get(b"Subtype")
+            .and_then(|o| o.as_name())
+            .map(|n| n.to_vec())
+            .unwrap_or_default();
+
+        // Annotations must be printable and visible: set Print, clear Invisible, Hidden, NoView
+        let flags = dict.get(b"F").and_then(|o| o.as_i64()).unwrap_or(0);
+        let new_flags = (flags | 4) & !(1 | 2 | 32);
+        if flags != new_flags {
+            dict.set("F", new_flags);
+            report
+                .fixed
+                .push(format!("Set print flag on annotation {:?}", id));
+        }
+
+        if !matches!(subtype.as_slice(), b"Link" | b"Popup") && !dict.has(b"AP") {
+            report.unresolved.push(format!(
+                "Annotation {:?} ({}) has no appearance stream",
+                id,
+                String::from_utf8_lossy(&subtype)
+            ));
+        }
+    }
+
+    if is_stream {
+        // External stream data is forbidden
+        for key in [b"F".as_slice(), b"FFilter", b"FDecodeParms"] {
+            if dict.remove(key).is_some() {
+                report
+                    .unresolved
+                    .push(format!("Stream {:?} references external data", id));
+            }
+        }
+
+        if dict.get(b"Filter").and_then(|o| o.as_name()).ok() == Some(b"LZWDecode") {
+            report
+                .unresolved
+                .push(format!("Stream {:?} uses forbidden LZW compression", id));
+        }
+
+        let subtype = dict
+            .get(b"Subtype")
+            .and_then(|o| o.as_name())
+            .map(<[u8]>::to_vec)
+            .ok();
+        if subtype.as_deref() == Some(b"Image") {
+            if dict
+                .get(b"Interpolate")
+                .and_then(|o| o.as_bool())
+                .unwrap_or(false)
+            {
+                dict.set("Interpolate", false);
+                report
+                    .fixed
+                    .push(format!("Disabled image interpolation on {:?}", id));
+            }
+            let alternates = dict.remove(b"Alternates").is_some();
+            let opi = dict.remove(b"OPI").is_some();
+            if alternates || opi {
+                report
+                    .fixed
+                    .push(format!("Removed image alternates/OPI from {:?}", id));
+            }
+        }
+        if subtype.as_deref() == Some(b"PS") {
+            report
+                .unresolved
+                .push(format!("PostScript XObject {:?} is not allowed", id));
+        }
+    }
+}
+
+///
+/// Find action objects referenced by reference which are not allowed
+///
+fn collect_forbidden_actions(document: &Document) -> HashSet<ObjectId> {
+    let mut forbidden = HashSet::new();
+
+    for object in document.objects.values() {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &stream.dict,
+            _ => continue,
+        };
+        for key in ACTION_KEYS {
+            if let Ok(action_id) = dict.get(key).and_then(|o| o.as_reference())
+                && document
+                    .get_dictionary(action_id)
+                    .is_ok_and(is_forbidden_action)
+            {
+                forbidden.insert(action_id);
+            }
+        }
+    }
+
+    forbidden
+}
+
+fn is_forbidden_action(action: &Dictionary) -> bool {
+    let Ok(kind) = action.get(b"S").and_then(|o| o.as_name()) else {
+        return false;
+    };
+
+    if kind == b"Named" {
+        let name = action.get(b"N").and_then(|o| o.as_name()).unwrap_or(b"");
+        return !ALLOWED_NAMED_ACTIONS.contains(&name);
+    }
+
+    FORBIDDEN_ACTIONS.contains(&kind)
+}
+
+///
+/// Fonts must be embedded and have a Unicode mapping (level U)
+///
+fn check_fonts(document: &Document, report: &mut PdfaReport) {
+    let mut fonts: BTreeMap<ObjectId, &Dictionary> = BTreeMap::new();
+    for (id, object) in &document.objects {
+        if let Ok(dict) = object.as_dict()
+            && dict.has_type(b"Font")
+        {
+            fonts.insert(*id, dict);
+        }
+    }
+
+    for (id, font) in fonts {
+        let subtype = font
+            .get(b"Subtype")
+            .and_then(|o| o.as_name())
+            .unwrap_or(b"");
+        let name = font
+            .get(b"BaseFont")
+            .and_then(|o| o.as_name())
+            .map(|n| String::from_utf8_lossy(n).to_string())
+            .unwrap_or_default();
+
+        match subtype {
+            // Glyphs of Type3 fonts are content streams, Type0 is checked through descendants
+            b"Type3" => continue,
+            b"Type0" => {
+                if !font.has(b"ToUnicode") {
+                    report
+                        .unresolved
+                        .push(format!("Font {:?} '{}' has no ToUnicode map", id, name));
+                }
+                continue;
+            }
+            b"CIDFontType0" | b"CIDFontType2" => {}
+            _ => {
+                if !font.has(b"ToUnicode") && !font.has(b"Encoding") {
+                    report
+                        .unresolved
+                        .push(format!("Font {:?} '{}' has no ToUnicode map", id, name));
+                }
+            }
+        }
+
+        let embedded = font
+            .get(b"FontDescriptor")
+            .and_then(|o| o.as_reference())
+            .and_then(|d| document.get_dictionary(d))
+            .map(|d| d.has(b"FontFile") || d.has(b"FontFile2") || d.has(b"FontFile3"))
+            .unwrap_or(false);
+        if !embedded {
+            report
+                .unresolved
+                .push(format!("Font {:?} '{}' is not embedded", id, name));
+        }
+    }
+}
+
+///
+/// Build a compact ICC v2 display profile for sRGB IEC61966-2.1
+///
+fn srgb_icc_profile() -> Vec<u8> {
+    fn s15_fixed16(v: f64) -> [u8; 4] {
+        ((v * 65536.0).round() as i32).to_be_bytes()
+    }
+    fn xyz_tag(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut tag = b"XYZ \0\0\0\0".to_vec();
+        for v in [x, y, z] {
+            tag.extend_from_slice(&s15_fixed16(v));
+        }
+        tag
+    }
+
+    let description = b"sRGB IEC61966-2.1";
+    let mut desc = b"desc\0\0\0\0".to_vec();
+    desc.extend_from_slice(&(description.len() as u32 + 1).to_be_bytes());
+    desc.extend_from_slice(description);
+    desc.push(0);
+    // Unicode (language code + count) and ScriptCode (code + count + 67 bytes) are empty
+    desc.extend_from_slice(&[0; 8]);
+    desc.extend_from_slice(&[0; 3]);
+    desc.extend_from_slice(&[0; 67]);
+
+    let mut cprt = b"text\0\0\0\0".to_vec();
+    cprt.extend_from_slice(b"No copyright, use freely\0");
+
+    // sRGB transfer function sampled into a curve table
+    let samples = 1024u32;
+    let mut trc = b"curv\0\0\0\0".to_vec();
+    trc.extend_from_slice(&samples.to_be_bytes());
+    for i in 0..samples {
+        let x = i as f64 / (samples - 1) as f64;
+        let y = if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        };
+        trc.extend_from_slice(&((y * 65535.0).round() as u16).to_be_bytes());
+    }
+
+    // Colorants are chromatically adapted to the D50 PCS illuminant
+    let data_tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"desc", desc),
+        (b"cprt", cprt),
+        (b"wtpt", xyz_tag(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz_tag(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz_tag(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz_tag(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", trc),
+    ];
+    // Green and blue share the red curve data
+    let shared_tags: [(&[u8; 4], &[u8; 4]); 2] = [(b"gTRC", b"rTRC"), (b"bTRC", b"rTRC")];
+
+    let tag_count = data_tags.len() + shared_tags.len();
+    let mut offset = 128 + 4 + 12 * tag_count;
+    let mut table = Vec::new();
+    let mut data = Vec::new();
+    let mut offsets = BTreeMap::new();
+
+    for (signature, tag) in &data_tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        offsets.insert(**signature, (offset, tag.len()));
+
+        data.extend_from_slice(tag);
+        // Tag data is 4-byte aligned
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        offset = 128 + 4 + 12 * tag_count + data.len();
+    }
+    for (signature, source) in shared_tags {
+        let (tag_offset, tag_len) = offsets[source];
+        table.extend_from_slice(signature);
+        table.extend_from_slice(&(tag_offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag_len as u32).to_be_bytes());
+    }
+
+    let size = 128 + 4 + table.len() + data.len();
+
+    let mut header = Vec::with_capacity(128);
+    header.extend_from_slice(&(size as u32).to_be_bytes());
+    header.extend_from_slice(&[0; 4]); // CMM
+    header.extend_from_slice(&[0x02, 0x10, 0, 0]); // Version 2.1
+    header.extend_from_slice(b"mntr");
+    header.extend_from_slice(b"RGB ");
+    header.extend_from_slice(b"XYZ ");
+    for v in [2000u16, 1, 1, 0, 0, 0] {
+        header.extend_from_slice(&v.to_be_bytes());
+    }
+    header.extend_from_slice(b"acsp");
+    header.extend_from_slice(&[0; 24]); // Platform, flags, manufacturer, model, attributes
+    header.extend_from_slice(&[0; 4]); // Perceptual rendering intent
+    for v in [0.9642, 1.0, 0.8249] {
+        header.extend_from_slice(&s15_fixed16(v));
+    }
+    header.resize(128, 0);
+
+    let mut profile = header;
+    profile.extend_from_slice(&(tag_count as u32).to_be_bytes());
+    profile.extend_from_slice(&table);
+    profile.extend_from_slice(&data);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::{Document, Object, ObjectId, dictionary};
+
+    use super::{PdfaReport, fix_objects, srgb_icc_profile};
+
+    fn be_u32(bytes: &[u8], at: usize) -> usize {
+        u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn icc_profile_header_and_tags_are_valid() {
+        let profile = srgb_icc_profile();
+
+        assert_eq!(be_u32(&profile, 0), profile.len());
+        assert_eq!(profile[8], 0x02);
+        assert_eq!(&profile[12..16], b"mntr");
+        assert_eq!(&profile[16..20], b"RGB ");
+        assert_eq!(&profile[20..24], b"XYZ ");
+        assert_eq!(&profile[36..40], b"acsp");
+
+        let tag_count = be_u32(&profile, 128);
+        let tags: Vec<(&[u8], usize, usize)> = (0..tag_count)
+            .map(|i| {
+                let entry = 132 + 12 * i;
+                (
+                    &profile[entry..entry + 4],
+                    be_u32(&profile, entry + 4),
+                    be_u32(&profile, entry + 8),
+                )
+            })
+            .collect();
+
+        // Tags required for an RGB display profile
+        for required in [
+            b"desc", b"cprt", b"wtpt", b"rXYZ", b"gXYZ", b"bXYZ", b"rTRC", b"gTRC", b"bTRC",
+        ] {
+            assert!(
+                tags.iter().any(|(signature, ..)| signature == required),
+                "{}",
+                String::from_utf8_lossy(required)
+            );
+        }
+        for (signature, offset, len) in tags {
+            assert_eq!(offset % 4, 0);
+            assert!(offset + len <= profile.len());
+            let kind: &[u8] = match signature {
+                b"desc" => b"desc",
+                b"cprt" => b"text",
+                b"rTRC" | b"gTRC" | b"bTRC" => b"curv",
+                _ => b"XYZ ",
+            };
+            assert_eq!(&profile[offset..offset + 4], kind);
+        }
+    }
+
+    #[test]
+    fn fix_objects_removes_actions_and_fixes_annotations() {
+        let javascript: ObjectId = (1, 0);
+        let link: ObjectId = (2, 0);
+        let widget: ObjectId = (3, 0);
+        let next_page: ObjectId = (4, 0);
+
+        let mut document = Document::with_version("1.7");
+        let objects = [
+            (
+                javascript,
+                Object::Dictionary(dictionary! {
+                    "S" => "JavaScript",
+                    "JS" => Object::string_literal("app.alert(1)"),
+                }),
+            ),
+            (
+                link,
+                Object::Dictionary(dictionary! {
+                    "Subtype" => "Link",
+                    "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                    "A" => javascript,
+                    "AA" => dictionary! { "E" => javascript },
+                }),
+            ),
+            (
+                widget,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Annot",
+                    "Subtype" => "Widget",
+                    "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                    "F" => 2,
+                    "A" => dictionary! { "S" => "Named", "N" => "Print" },
+                }),
+            ),
+            (
+                next_page,
+                Object::Dictionary(dictionary! {
+                    "Subtype" => "Link",
+                    "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                    "F" => 4,
+                    "A" => dictionary! { "S" => "Named", "N" => "NextPage" },
+                }),
+            ),
+        ];
+        for (id, object) in objects {
+            document.objects.insert(id, object);
+        }
+
+        let mut report = PdfaReport::default();
+        fix_objects(&mut document, &mut report);
+
+        let link = document.get_dictionary(link).unwrap();
+        assert!(!link.has(b"A"));
+        assert!(!link.has(b"AA"));
+        assert_eq!(link.get(b"F").unwrap(), &Object::Integer(4));
+
+        // Hidden is cleared, Print is set and the Print named action is removed
+        let widget = document.get_dictionary(widget).unwrap();
+        assert!(!widget.has(b"A"));
+        assert_eq!(widget.get(b"F").unwrap(), &Object::Integer(4));
+
+        // Allowed named actions and conformant flags are left alone
+        let next_page = document.get_dictionary(next_page).unwrap();
+        assert!(next_page.has(b"A"));
+
+        assert_eq!(report.fixed.len(), 5);
+        assert_eq!(
+            report.unresolved,
+            ["Annotation (3, 0) (Widget) has no appearance stream"]
+        );
+    }
+}
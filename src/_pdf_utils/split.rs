@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use lopdf::{Dictionary, Document, Object, ObjectId, StringFormat, dictionary};
+
+use crate::toc::TocNode;
+
+/// Split the output by the table of contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SplitBy {
+    /// One volume per top-level chapter with its children
+    TopLevel,
+}
+
+/// How the chapters are divided into volumes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    /// A new volume starts at every level-0 chapter
+    TopLevel,
+    /// A volume holds at most this many pages, unless a single chapter is longer
+    Pages(u32),
+    /// A volume holds chapters with at most this many bytes, unless a single chapter is larger
+    Size(u64),
+}
+
+/// A part of the output written to its own file
+#[derive(Debug, Clone)]
+pub struct Volume {
+    /// Number of the volume, starting from 1
+    pub number: usize,
+    pub path: PathBuf,
+    /// Title of the first chapter
+    pub title: String,
+    pub toc: Vec<TocNode>,
+}
+
+impl Volume {
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Links between the volumes of a split output
+#[derive(Debug, Clone, Default)]
+pub struct VolumeLinks {
+    /// Chapter key (see `chapter_key`) to the file name of the volume containing the chapter
+    pub remote: HashMap<String, String>,
+}
+
+///
+/// Divide the chapters into volumes, without a mode everything goes into a single output file
+///
+pub fn plan_volumes(toc: Vec<TocNode>, mode: Option<SplitMode>, output: &Path) -> Vec<Volume> {
+    let mut groups: Vec<Vec<TocNode>> = Vec::new();
+    let mut current_amount = 0u64;
+
+    for node in toc {
+        let amount = match mode {
+            Some(SplitMode::Pages(_)) => chapter_pages(&node),
+            Some(SplitMode::Size(_)) => chapter_size(&node),
+            _ => 0,
+        };
+        let starts_volume = match mode {
+            None => false,
+            Some(SplitMode::TopLevel) => node.level == 0,
+            Some(SplitMode::Pages(limit)) => current_amount + amount > limit as u64,
+            Some(SplitMode::Size(limit)) => current_amount + amount > limit,
+        };
+
+        match groups.last_mut() {
+            Some(group) if !starts_volume || group.is_empty() => group.push(node),
+            _ => {
+                groups.push(vec![node]);
+                current_amount = 0;
+            }
+        }
+        current_amount += amount;
+    }
+
+    let count = groups.len();
+    let width = count.to_string().len();
+    groups
+        .into_iter()
+        .enumerate()
+        .map(|(index, toc)| {
+            let path = if count == 1 {
+                output.to_path_buf()
+            } else {
+                volume_path(output, index + 1, width)
+            };
+            let title = toc
+                .first()
+                .map(|node| node.title.clone().unwrap_or_else(|| node.href.clone()))
+                .unwrap_or_default();
+            Volume {
+                number: index + 1,
+                path,
+                title,
+                toc,
+            }
+        })
+        .collect()
+}
+
+///
+/// Map chapters of all other volumes to the file they are written to
+///
+pub fn volume_links(volumes: &[Volume], current: usize) -> VolumeLinks {
+    let remote = volumes
+        .iter()
+        .filter(|volume| volume.number != current)
+        .flat_map(|volume| {
+            let file_name = volume.file_name();
+            volume
+                .toc
+                .iter()
+                .map(move |node| (chapter_key(&node.href), file_name.clone()))
+        })
+        .collect();
+
+    VolumeLinks { remote }
+}
+
+///
+/// Key of a chapter used for named destinations, the URL without fragment and trailing slash
+///
+pub fn chapter_key(href: &str) -> String {
+    let without_fragment = href.split('#').next().unwrap_or_default();
+    without_fragment.trim_end_matches('/').to_string()
+}
+
+///
+/// Turn URI links to chapters of other volumes into remote GoTo actions
+///
+pub fn rewrite_remote_links(doc: &mut Document, links: &VolumeLinks) -> usize {
+    if links.remote.is_empty() {
+        return 0;
+    }
+
+    let mut rewritten = 0;
+    for object in doc.objects.values_mut() {
+        let Ok(dict) = object.as_dict_mut() else {
+            continue;
+        };
+
+        // The action is either inline in the annotation or a separate object
+        let action = if dict.has(b"URI") {
+            Some(dict)
+        } else {
+            dict.get_mut(b"A").and_then(|a| a.as_dict_mut()).ok()
+        };
+
+        if let Some(action) = action
+            && let Some(remote) = remote_action(action, links)
+        {
+            *action = remote;
+            rewritten += 1;
+        }
+    }
+
+    rewritten
+}
+
+fn remote_action(action: &Dictionary, links: &VolumeLinks) -> Option<Dictionary> {
+    if action.get(b"S").and_then(|s| s.as_name()).ok() != Some(b"URI") {
+        return None;
+    }
+    let uri = action.get(b"URI").and_then(|u| u.as_str()).ok()?;
+    let key = chapter_key(&String::from_utf8_lossy(uri));
+    let file_name = links.remote.get(&key)?;
+
+    Some(dictionary! {
+        "S" => "GoToR",
+        "F" => Object::string_literal(file_name.as_str()),
+        "D" => Object::String(key.into_bytes(), StringFormat::Literal),
+        "NewWindow" => false,
+    })
+}
+
+///
+/// Register a named destination for the first page of every chapter,
+/// so other volumes can link to it with GoToR
+///
+pub fn add_chapter_destinations(
+    document: &mut Document,
+    catalog_id: ObjectId,
+    chapters: Vec<(String, ObjectId)>,
+) -> lopdf::Result<()> {
+    if chapters.is_empty() {
+        return Ok(());
+    }
+
+    // Names in a name tree must be sorted
+    let mut chapters = chapters;
+    chapters.sort_by(|a, b| a.0.cmp(&b.0));
+    chapters.dedup_by(|a, b| a.0 == b.0);
+
+    let names: Vec<Object> = chapters
+        .into_iter()
+        .flat_map(|(key, page)| {
+            [
+                Object::String(key.into_bytes(), StringFormat::Literal),
+                Object::Array(vec![page.into(), "Fit".into()]),
+            ]
+        })
+        .collect();
+    let dests_id = document.add_object(dictionary! { "Names" => names });
+
+    // A referenced Names dictionary belongs to an already written chapter, it is replaced
+    let catalog = document.get_dictionary_mut(catalog_id)?;
+    match catalog.get_mut(b"Names").and_then(|n| n.as_dict_mut()) {
+        Ok(catalog_names) => catalog_names.set("Dests", dests_id),
+        Err(_) => catalog.set("Names", dictionary! { "Dests" => dests_id }),
+    }
+
+    Ok(())
+}
+
+fn volume_path(output: &Path, number: usize, width: usize) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "output".to_string());
+    let extension = output
+        .extension()
+        .map(|e| e.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "pdf".to_string());
+
+    output.with_file_name(format!(
+        "{}-{:0width$}.{}",
+        stem,
+        number,
+        extension,
+        width = width
+    ))
+}
+
+fn chapter_pages(node: &TocNode) -> u64 {
+    node.file_path
+        .as_ref()
+        .and_then(|path| Document::load_metadata(path).ok())
+        .map_or(0, |metadata| metadata.page_count as u64)
+}
+
+fn chapter_size(node: &TocNode) -> u64 {
+    node.file_path
+        .as_ref()
+        .and_then(|path| std::fs::metadata(path).ok())
+        .map_or(0, |metadata| metadata.len())
+}
+
+///
+/// Parse a size such as `50MB`, `512KB`, `1.5GB` or a plain number of bytes
+///
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1u64,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        other => return Err(format!("unknown size unit '{}'", other)),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_size;
+
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("100B"), Ok(100));
+        assert_eq!(parse_size("512KB"), Ok(512 << 10));
+        assert_eq!(parse_size("50MB"), Ok(50 << 20));
+        assert_eq!(parse_size("50m"), Ok(50 << 20));
+        assert_eq!(parse_size(" 2 gb "), Ok(2 << 30));
+        assert_eq!(parse_size("1.5GB"), Ok(3 << 29));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!(parse_size("").unwrap_err().contains("invalid size"));
+        assert!(parse_size("MB").unwrap_err().contains("invalid size"));
+        assert!(parse_size("-5MB").unwrap_err().contains("invalid size"));
+        assert!(parse_size("1.2.3MB").unwrap_err().contains("invalid size"));
+        assert!(
+            parse_size("10TB")
+                .unwrap_err()
+                .contains("unknown size unit")
+        );
+    }
+}
@@ -0,0 +1,571 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use lopdf::{Dictionary, Document, Object, Stream, dictionary};
+use md5::{Digest, Md5};
+use ttf_parser::{Face, GlyphId, Tag, name_id};
+
+/// Sans-serif TrueType fonts looked up when no stamp font is given
+const SYSTEM_FONTS: [&str; 9] = [
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/liberation-sans/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSans-Regular.ttf",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+
+/// Tables of the embedded subset in tag order, text is shown by glyph ids so no cmap is needed
+const SUBSET_TABLES: [&[u8; 4]; 9] = [
+    b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep",
+];
+
+/// TrueType font embedded as a Type0 font with Identity-H encoding,
+/// only the glyphs used by the stamp are kept
+#[derive(Debug)]
+pub struct TrueTypeFont {
+    data: Vec<u8>,
+    name: String,
+    /// Used glyphs and the characters they show, for the ToUnicode map
+    used: BTreeMap<u16, char>,
+    /// Characters the font has no glyph for
+    pub missing: BTreeSet<char>,
+}
+
+impl TrueTypeFont {
+    ///
+    /// Load a TrueType font file, fonts with CFF outlines or without embedding permission
+    /// are rejected
+    ///
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| e.to_string())?;
+        let face = Face::parse(&data, 0).map_err(|e| e.to_string())?;
+
+        if face.raw_face().table(Tag::from_bytes(b"glyf")).is_none() {
+            return Err("only TrueType outlines are supported".to_string());
+        }
+        if !face.is_outline_embedding_allowed() {
+            return Err("the font license doesn't allow embedding".to_string());
+        }
+
+        let name = face
+            .names()
+            .into_iter()
+            .find(|n| n.name_id == name_id::POST_SCRIPT_NAME && n.is_unicode())
+            .and_then(|n| n.to_string())
+            .unwrap_or_else(|| "Stamp".to_string());
+
+        tracing::debug!("Stamp font {} loaded from {}", name, path.display());
+        Ok(Self {
+            data,
+            name,
+            used: BTreeMap::new(),
+            missing: BTreeSet::new(),
+        })
+    }
+
+    ///
+    /// First well-known system font which can be embedded
+    ///
+    pub fn find_system() -> Option<Self> {
+        SYSTEM_FONTS
+            .iter()
+            .map(Path::new)
+            .filter(|path| path.exists())
+            .find_map(|path| Self::load(path).ok())
+    }
+
+    fn face(&self) -> Face<'_> {
+        Face::parse(&self.data, 0).expect("font is validated on load")
+    }
+
+    ///
+    /// Glyph ids of the text as a two-byte string and its width in text space units
+    ///
+    pub fn encode(&mut self, text: &str) -> (Vec<u8>, u32) {
+        // The face borrows only the data, the used glyphs are updated while it is alive
+        let face = Face::parse(&self.data, 0).expect("font is validated on load");
+        let scale = 1000.0 / face.units_per_em() as f32;
+
+        let mut encoded = Vec::with_capacity(text.len() * 2);
+        let mut width = 0;
+        for c in text.chars() {
+            let glyph = match face.glyph_index(c) {
+                Some(glyph) => {
+                    self.used.entry(glyph.0).or_insert(c);
+                    glyph
+                }
+                None => {
+                    self.missing.insert(c);
+                    GlyphId(0)
+                }
+            };
+            encoded.extend_from_slice(&glyph.0.to_be_bytes());
+            width += (face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale).round() as u32;
+        }
+        (encoded, width)
+    }
+
+    ///
+    /// Add the descendant CIDFont, descriptor, subset font file and ToUnicode map,
+    /// the returned Type0 font dictionary refers to them
+    ///
+    pub fn font_dictionary(&self, document: &mut Document) -> Dictionary {
+        let face = self.face();
+        let scale =
+            |value: i16| (value as f32 * 1000.0 / face.units_per_em() as f32).round() as i64;
+
+        let base_font = format!("{}+{}", subset_tag(&self.used), self.name);
+
+        let font_file = subset(&face, self.used.keys().copied());
+        let font_file_id = document.add_object(Stream::new(
+            dictionary! { "Length1" => font_file.len() as i64 },
+            font_file,
+        ));
+
+        let bbox = face.global_bounding_box();
+        let descriptor_id = document.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => Object::Name(base_font.clone().into_bytes()),
+            "Flags" => 32,
+            "FontBBox" => vec![
+                scale(bbox.x_min).into(),
+                scale(bbox.y_min).into(),
+                scale(bbox.x_max).into(),
+                scale(bbox.y_max).into(),
+            ],
+            "ItalicAngle" => face.italic_angle(),
+            "Ascent" => scale(face.ascender()),
+            "Descent" => scale(face.descender()),
+            "CapHeight" => scale(face.capital_height().unwrap_or(face.ascender())),
+            "StemV" => 80,
+            "FontFile2" => font_file_id,
+        });
+
+        let glyphs: BTreeSet<u16> = self.used.keys().copied().chain([0]).collect();
+        let mut widths = Vec::new();
+        for glyph in glyphs {
+            let advance = face.glyph_hor_advance(GlyphId(glyph)).unwrap_or(0) as i16;
+            widths.push(Object::Integer(glyph as i64));
+            widths.push(Object::Array(vec![scale(advance).into()]));
+        }
+
+        let cid_font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "CIDFontType2",
+            "BaseFont" => Object::Name(base_font.clone().into_bytes()),
+            "CIDSystemInfo" => dictionary! {
+                "Registry" => Object::string_literal("Adobe"),
+                "Ordering" => Object::string_literal("Identity"),
+                "Supplement" => 0,
+            },
+            "FontDescriptor" => descriptor_id,
+            "W" => widths,
+            "CIDToGIDMap" => "Identity",
+        });
+
+        let to_unicode_id = document.add_object(Stream::new(
+            Dictionary::new(),
+            to_unicode_cmap(&self.used).into_bytes(),
+        ));
+
+        dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type0",
+            "BaseFont" => Object::Name(base_font.into_bytes()),
+            "Encoding" => "Identity-H",
+            "DescendantFonts" => vec![cid_font_id.into()],
+            "ToUnicode" => to_unicode_id,
+        }
+    }
+}
+
+///
+/// Six letter tag of a subset derived from its glyphs, so equal stamps get equal font names
+///
+fn subset_tag(used: &BTreeMap<u16, char>) -> String {
+    let mut digest = Md5::new();
+    for (glyph, c) in used {
+        digest.update(glyph.to_be_bytes());
+        digest.update((*c as u32).to_be_bytes());
+    }
+    digest
+        .finalize()
+        .iter()
+        .take(6)
+        .map(|byte| (b'A' + byte % 26) as char)
+        .collect()
+}
+
+///
+/// ToUnicode CMap of the used glyphs
+///
+fn to_unicode_cmap(used: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+
+    // At most 100 entries are allowed in a single bfchar block
+    let entries: Vec<_> = used.iter().collect();
+    for chunk in entries.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", chunk.len()));
+        for (glyph, c) in chunk {
+            let unicode: String = c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .map(|unit| format!("{:04X}", unit))
+                .collect();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", glyph, unicode));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+///
+/// Build a TrueType file with the outlines of the given glyphs only, glyph ids are kept
+/// so the other glyphs become empty
+///
+fn subset(face: &Face, glyphs: impl Iterator<Item = u16>) -> Vec<u8> {
+    let table = |tag: &[u8; 4]| face.raw_face().table(Tag::from_bytes(tag));
+    let glyf = table(b"glyf").unwrap_or_default();
+    let long_loca = table(b"head").is_some_and(|head| read_u16(head, 50) == Some(1));
+    let loca = table(b"loca").unwrap_or_default();
+
+    let glyph_data = |glyph: u16| -> &[u8] {
+        let offset = |index: usize| {
+            if long_loca {
+                read_u32(loca, index * 4).map(|o| o as usize)
+            } else {
+                read_u16(loca, index * 2).map(|o| o as usize * 2)
+            }
+        };
+        match (offset(glyph as usize), offset(glyph as usize + 1)) {
+            (Some(start), Some(end)) if start <= end && end <= glyf.len() => &glyf[start..end],
+            _ => &[],
+        }
+    };
+
+    // Composite glyphs are built from other glyphs, which must be kept as well
+    let mut kept: BTreeSet<u16> = BTreeSet::from([0]);
+    let mut pending: Vec<u16> = glyphs.collect();
+    while let Some(glyph) = pending.pop() {
+        if kept.insert(glyph) {
+            pending.extend(composite_components(glyph_data(glyph)));
+        }
+    }
+
+    let mut new_glyf = Vec::new();
+    let mut new_loca = Vec::new();
+    for glyph in 0..face.number_of_glyphs() {
+        new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+        if kept.contains(&glyph) {
+            new_glyf.extend_from_slice(glyph_data(glyph));
+            new_glyf.resize(new_glyf.len().next_multiple_of(4), 0);
+        }
+    }
+    new_loca.extend_from_slice(&(new_glyf.len() as u32).to_be_bytes());
+
+    let mut tables: Vec<(&[u8; 4], Vec<u8>)> = Vec::new();
+    for tag in SUBSET_TABLES {
+        let data = match tag {
+            b"glyf" => new_glyf.clone(),
+            b"loca" => new_loca.clone(),
+            b"head" => match table(tag) {
+                Some(head) if head.len() >= 54 => {
+                    let mut head = head.to_vec();
+                    // Checksum adjustment is recalculated below, loca is written in the long format
+                    head[8..12].fill(0);
+                    head[50..52].copy_from_slice(&1u16.to_be_bytes());
+                    head
+                }
+                _ => continue,
+            },
+            _ => match table(tag) {
+                Some(data) => data.to_vec(),
+                None => continue,
+            },
+        };
+        tables.push((tag, data));
+    }
+
+    let mut font = write_sfnt(&tables);
+    let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&font));
+    if let Some(head_offset) = table_offset(&font, b"head") {
+        font[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    }
+    font
+}
+
+///
+/// Glyph ids a composite glyph refers to, empty for a simple glyph
+///
+fn composite_components(data: &[u8]) -> Vec<u16> {
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+    let mut components = Vec::new();
+    if read_u16(data, 0).is_none_or(|contours| (contours as i16) >= 0) {
+        return components;
+    }
+
+    let mut offset = 10;
+    while let (Some(flags), Some(glyph)) = (read_u16(data, offset), read_u16(data, offset + 2)) {
+        components.push(glyph);
+        offset += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+            4
+        } else {
+            2
+        };
+        offset += if flags & WE_HAVE_A_SCALE != 0 {
+            2
+        } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            4
+        } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+            8
+        } else {
+            0
+        };
+        if flags & MORE_COMPONENTS == 0 {
+            break;
+        }
+    }
+    components
+}
+
+fn write_sfnt(tables: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.leading_zeros() as u16;
+    let search_range = (1u16 << entry_selector) * 16;
+
+    let mut font = Vec::new();
+    font.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    for value in [
+        count,
+        search_range,
+        entry_selector,
+        count * 16 - search_range,
+    ] {
+        font.extend_from_slice(&value.to_be_bytes());
+    }
+
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in tables {
+        font.extend_from_slice(*tag);
+        font.extend_from_slice(&checksum(data).to_be_bytes());
+        font.extend_from_slice(&(offset as u32).to_be_bytes());
+        font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        offset += data.len().next_multiple_of(4);
+    }
+    for (_, data) in tables {
+        font.extend_from_slice(data);
+        font.resize(font.len().next_multiple_of(4), 0);
+    }
+    font
+}
+
+fn table_offset(font: &[u8], tag: &[u8; 4]) -> Option<usize> {
+    let count = read_u16(font, 4)? as usize;
+    (0..count)
+        .map(|index| 12 + index * 16)
+        .find(|&record| font.get(record..record + 4) == Some(tag))
+        .and_then(|record| read_u32(font, record + 8))
+        .map(|offset| offset as usize)
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ttf_parser::{Face, GlyphId, OutlineBuilder, Rect};
+
+    use super::{checksum, read_u16, read_u32, subset, subset_tag, write_sfnt};
+
+    struct Points(usize);
+
+    impl OutlineBuilder for Points {
+        fn move_to(&mut self, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn line_to(&mut self, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn quad_to(&mut self, _: f32, _: f32, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn curve_to(&mut self, _: f32, _: f32, _: f32, _: f32, _: f32, _: f32) {
+            self.0 += 1;
+        }
+        fn close(&mut self) {}
+    }
+
+    fn outline(face: &Face, glyph: u16) -> Option<Rect> {
+        face.outline_glyph(GlyphId(glyph), &mut Points(0))
+    }
+
+    /// Triangle with on-curve points only
+    fn simple_glyph(size: i16) -> Vec<u8> {
+        let mut glyph = Vec::new();
+        for value in [1, 0, 0, size, size] {
+            glyph.extend_from_slice(&value.to_be_bytes());
+        }
+        glyph.extend_from_slice(&2u16.to_be_bytes()); // last point of the contour
+        glyph.extend_from_slice(&0u16.to_be_bytes()); // no instructions
+        glyph.extend_from_slice(&[1, 1, 1]); // on curve, x and y are words
+        for delta in [0, size, -size, 0, 0, size] {
+            glyph.extend_from_slice(&delta.to_be_bytes());
+        }
+        glyph
+    }
+
+    /// Glyph made of another one shifted by an offset
+    fn composite_glyph(component: u16, size: i16) -> Vec<u8> {
+        let mut glyph = Vec::new();
+        for value in [-1, 0, 0, size + 10, size + 10] {
+            glyph.extend_from_slice(&value.to_be_bytes());
+        }
+        glyph.extend_from_slice(&0x0003u16.to_be_bytes()); // word arguments, x/y offsets
+        glyph.extend_from_slice(&component.to_be_bytes());
+        glyph.extend_from_slice(&10i16.to_be_bytes());
+        glyph.extend_from_slice(&10i16.to_be_bytes());
+        glyph
+    }
+
+    /// Font with an empty .notdef, two triangles and a composite of the second one,
+    /// loca is in the short format
+    fn test_font() -> Vec<u8> {
+        let glyphs = [
+            Vec::new(),
+            simple_glyph(100),
+            simple_glyph(200),
+            composite_glyph(2, 200),
+        ];
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for glyph in &glyphs {
+            loca.extend_from_slice(&((glyf.len() / 2) as u16).to_be_bytes());
+            glyf.extend_from_slice(glyph);
+            glyf.resize(glyf.len().next_multiple_of(4), 0);
+        }
+        loca.extend_from_slice(&((glyf.len() / 2) as u16).to_be_bytes());
+
+        let mut head = vec![0; 54];
+        head[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        head[12..16].copy_from_slice(&0x5F0F_3CF5u32.to_be_bytes());
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[40..42].copy_from_slice(&210i16.to_be_bytes());
+        head[42..44].copy_from_slice(&210i16.to_be_bytes());
+
+        let mut hhea = vec![0; 36];
+        hhea[0..4].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34..36].copy_from_slice(&4u16.to_be_bytes());
+
+        let mut maxp = 0x0000_5000u32.to_be_bytes().to_vec();
+        maxp.extend_from_slice(&4u16.to_be_bytes());
+
+        let hmtx: Vec<u8> = (0..4u16)
+            .flat_map(|glyph| [(glyph * 300).to_be_bytes(), [0, 0]].concat())
+            .collect();
+
+        write_sfnt(&[
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ])
+    }
+
+    #[test]
+    fn subset_keeps_used_glyphs_and_their_components() {
+        let font = test_font();
+        let face = Face::parse(&font, 0).unwrap();
+
+        let subset_font = subset(&face, [3].into_iter());
+        let subset_face = Face::parse(&subset_font, 0).unwrap();
+
+        assert!(outline(&face, 1).is_some() && outline(&face, 2).is_some());
+        assert_eq!(subset_face.number_of_glyphs(), 4);
+        assert_eq!(outline(&subset_face, 1), None);
+        assert_eq!(outline(&subset_face, 2), outline(&face, 2));
+        assert_eq!(outline(&subset_face, 3), outline(&face, 3));
+        assert_eq!(
+            subset_face.glyph_hor_advance(GlyphId(3)),
+            face.glyph_hor_advance(GlyphId(3))
+        );
+    }
+
+    #[test]
+    fn subset_checksums_are_valid() {
+        let font = test_font();
+        let face = Face::parse(&font, 0).unwrap();
+        let subset_font = subset(&face, [1].into_iter());
+
+        assert_eq!(checksum(&subset_font), 0xB1B0_AFBA);
+
+        let count = read_u16(&subset_font, 4).unwrap() as usize;
+        for record in (0..count).map(|index| 12 + index * 16) {
+            let tag = &subset_font[record..record + 4];
+            let expected = read_u32(&subset_font, record + 4).unwrap();
+            let offset = read_u32(&subset_font, record + 8).unwrap() as usize;
+            let length = read_u32(&subset_font, record + 12).unwrap() as usize;
+
+            let mut table = subset_font[offset..offset + length].to_vec();
+            if tag == b"head" {
+                // The adjustment isn't part of the table checksum
+                table[8..12].fill(0);
+                assert_eq!(read_u16(&table, 50), Some(1), "loca must be long");
+            }
+            assert_eq!(
+                checksum(&table),
+                expected,
+                "{}",
+                String::from_utf8_lossy(tag)
+            );
+        }
+    }
+
+    #[test]
+    fn subset_tag_depends_only_on_glyphs() {
+        let used = BTreeMap::from([(3, 'a'), (7, 'b')]);
+        let tag = subset_tag(&used);
+
+        assert_eq!(tag.len(), 6);
+        assert!(tag.chars().all(|c| c.is_ascii_uppercase()));
+        assert_eq!(tag, subset_tag(&used.clone()));
+        assert_ne!(tag, subset_tag(&BTreeMap::from([(3, 'a')])));
+    }
+}
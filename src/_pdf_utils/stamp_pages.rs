@@ -0,0 +1,440 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream, StringFormat, dictionary};
+
+use super::stamp_font::TrueTypeFont;
+
+const FONT_NAME: &str = "W2PStamp";
+const FONT_SIZE: f32 = 8.0;
+/// Gray level of the stamped text
+const TEXT_GRAY: f32 = 0.4;
+const SIDE_MARGIN: f32 = 36.0;
+/// Baseline offsets from the page edges, they fit into Chrome's default 0.4in margins
+const HEADER_OFFSET: f32 = 18.0;
+const FOOTER_OFFSET: f32 = 14.0;
+/// A4 in points, used when a page has no MediaBox
+const DEFAULT_MEDIA_BOX: [f32; 4] = [0.0, 0.0, 595.0, 842.0];
+
+/// Running header and footer templates
+///
+/// A template may contain `{page}`, `{pages}`, `{chapter}` and `{title}` placeholders.
+/// `|` splits it into left, center and right parts, a template without `|` is centered.
+#[derive(Debug, Clone, Default)]
+pub struct StampOptions {
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    /// TrueType font of the stamped text, a system sans-serif font is used by default
+    pub font: Option<PathBuf>,
+}
+
+impl StampOptions {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_none() && self.footer.is_none()
+    }
+}
+
+/// Font of the stamped text
+#[derive(Debug)]
+pub enum StampFont {
+    /// TrueType font embedded as a Type0 font, it covers every script the font has glyphs for
+    TrueType(TrueTypeFont),
+    /// Standard Helvetica, it isn't embedded and covers WinAnsiEncoding only
+    Helvetica { replaced: BTreeSet<char> },
+}
+
+impl StampFont {
+    ///
+    /// The font given in the options, otherwise a well-known system font, otherwise Helvetica
+    ///
+    pub fn load(options: &StampOptions) -> Result<Self, String> {
+        if let Some(path) = &options.font {
+            return TrueTypeFont::load(path)
+                .map(Self::TrueType)
+                .map_err(|e| format!("{}: {}", path.display(), e));
+        }
+
+        match TrueTypeFont::find_system() {
+            Some(font) => Ok(Self::TrueType(font)),
+            None => {
+                tracing::warn!("No system TrueType font found, the stamp uses Helvetica");
+                Ok(Self::Helvetica {
+                    replaced: BTreeSet::new(),
+                })
+            }
+        }
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        matches!(self, Self::TrueType(_))
+    }
+
+    ///
+    /// Encoded text and its width in points
+    ///
+    fn encode(&mut self, text: &str) -> (Object, f32) {
+        match self {
+            Self::TrueType(font) => {
+                let (encoded, units) = font.encode(text);
+                (
+                    Object::String(encoded, StringFormat::Hexadecimal),
+                    units as f32 * FONT_SIZE / 1000.0,
+                )
+            }
+            Self::Helvetica { replaced } => {
+                let encoded = encode_win_ansi(text, replaced);
+                let width = text_width(&encoded);
+                (Object::String(encoded, StringFormat::Literal), width)
+            }
+        }
+    }
+
+    fn font_dictionary(&self, document: &mut Document) -> Dictionary {
+        match self {
+            Self::TrueType(font) => font.font_dictionary(document),
+            Self::Helvetica { .. } => dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type1",
+                "BaseFont" => "Helvetica",
+                "Encoding" => "WinAnsiEncoding",
+            },
+        }
+    }
+
+    ///
+    /// Characters which couldn't be stamped and were replaced
+    ///
+    fn missing(&self) -> &BTreeSet<char> {
+        match self {
+            Self::TrueType(font) => &font.missing,
+            Self::Helvetica { replaced } => replaced,
+        }
+    }
+}
+
+/// A chapter of the merged body
+#[derive(Debug, Clone)]
+pub struct ChapterStart {
+    /// Zero-based index of the first page in the merged document
+    pub page_index: u32,
+    pub title: String,
+}
+
+#[derive(Clone, Copy)]
+enum Slot {
+    Header,
+    Footer,
+}
+
+///
+/// Overlay running headers and footers on the body pages of the merged document
+///
+/// Pages before the first chapter (the cover) are left as is. Page numbers count body pages only,
+/// so they match the decimal page labels. The overlay is marked as a pagination `/Artifact`.
+///
+pub fn stamp_pages(
+    document: &mut Document,
+    book_title: &str,
+    chapters: &[ChapterStart],
+    options: &StampOptions,
+    font: &mut StampFont,
+) -> lopdf::Result<usize> {
+    let Some(body_start) = chapters.first().map(|c| c.page_index) else {
+        return Ok(0);
+    };
+
+    let pages: Vec<ObjectId> = document.get_pages().into_values().collect();
+    let body_pages = pages.len().saturating_sub(body_start as usize);
+
+    // The font is written after the pages, an embedded subset needs all the stamped text
+    let font_id = document.new_object_id();
+    // Isolate the graphics state of the original content from the overlay
+    let save_state_id = document.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+
+    let mut stamped = 0;
+    for (index, page_id) in pages.into_iter().enumerate().skip(body_start as usize) {
+        let chapter = chapters
+            .iter()
+            .rev()
+            .find(|c| c.page_index as usize <= index)
+            .map(|c| c.title.as_str())
+            .unwrap_or_default();
+        let values = [
+            ("{page}", (index + 1 - body_start as usize).to_string()),
+            ("{pages}", body_pages.to_string()),
+            ("{chapter}", chapter.to_string()),
+            ("{title}", book_title.to_string()),
+        ];
+
+        let media_box = media_box(document, page_id);
+        let mut operations = vec![Operation::new("Q", vec![])];
+        if let Some(template) = &options.header {
+            operations.extend(stamp_operations(
+                template,
+                &values,
+                media_box,
+                Slot::Header,
+                font,
+            ));
+        }
+        if let Some(template) = &options.footer {
+            operations.extend(stamp_operations(
+                template,
+                &values,
+                media_box,
+                Slot::Footer,
+                font,
+            ));
+        }
+
+        let content = Content { operations }.encode()?;
+        let stamp_id = document.add_object(Stream::new(Dictionary::new(), content));
+
+        add_font_resource(document, page_id, font_id)?;
+
+        let page = document.get_dictionary_mut(page_id)?;
+        let mut contents = vec![Object::Reference(save_state_id)];
+        match page.get(b"Contents") {
+            Ok(Object::Array(array)) => contents.extend(array.iter().cloned()),
+            Ok(other) => contents.push(other.clone()),
+            Err(_) => {}
+        }
+        contents.push(Object::Reference(stamp_id));
+        page.set("Contents", contents);
+
+        stamped += 1;
+    }
+
+    let font_dictionary = font.font_dictionary(document);
+    document
+        .objects
+        .insert(font_id, Object::Dictionary(font_dictionary));
+
+    if !font.missing().is_empty() {
+        let missing: String = font.missing().iter().collect();
+        println!(
+            "  ⚠️ The stamp font has no glyphs for \"{}\", they are replaced",
+            missing
+        );
+    }
+
+    tracing::debug!("Stamped headers/footers on {} pages", stamped);
+    Ok(stamped)
+}
+
+fn stamp_operations(
+    template: &str,
+    values: &[(&str, String)],
+    media_box: [f32; 4],
+    slot: Slot,
+    font: &mut StampFont,
+) -> Vec<Operation> {
+    let [left, bottom, right, top] = media_box;
+    let (subtype, y) = match slot {
+        Slot::Header => ("Header", top - HEADER_OFFSET),
+        Slot::Footer => ("Footer", bottom + FOOTER_OFFSET),
+    };
+
+    let mut text = template.to_string();
+    for (placeholder, value) in values {
+        text = text.replace(placeholder, value);
+    }
+    let parts: Vec<&str> = text.splitn(3, '|').collect();
+    let aligned: Vec<(&str, Align)> = match parts.as_slice() {
+        [center] => vec![(center, Align::Center)],
+        [l, r] => vec![(l, Align::Left), (r, Align::Right)],
+        [l, c, r] => vec![(l, Align::Left), (c, Align::Center), (r, Align::Right)],
+        _ => vec![],
+    };
+
+    let mut operations = vec![
+        Operation::new(
+            "BDC",
+            vec![
+                Object::Name(b"Artifact".to_vec()),
+                Object::Dictionary(dictionary! {
+                    "Type" => "Pagination",
+                    "Subtype" => subtype,
+                }),
+            ],
+        ),
+        Operation::new("q", vec![]),
+        Operation::new("g", vec![TEXT_GRAY.into()]),
+    ];
+
+    for (part, align) in aligned {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        let (encoded, width) = font.encode(part);
+        let x = match align {
+            Align::Left => left + SIDE_MARGIN,
+            Align::Center => (left + right - width) / 2.0,
+            Align::Right => right - SIDE_MARGIN - width,
+        };
+        operations.extend([
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![Object::Name(FONT_NAME.into()), FONT_SIZE.into()]),
+            Operation::new("Td", vec![x.into(), y.into()]),
+            Operation::new("Tj", vec![encoded]),
+            Operation::new("ET", vec![]),
+        ]);
+    }
+
+    operations.extend([Operation::new("Q", vec![]), Operation::new("EMC", vec![])]);
+    operations
+}
+
+#[derive(Clone, Copy)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+fn media_box(document: &Document, page_id: ObjectId) -> [f32; 4] {
+    let values: Option<Vec<f32>> = document
+        .get_dictionary(page_id)
+        .and_then(|page| page.get(b"MediaBox"))
+        .and_then(|o| match o {
+            Object::Reference(id) => document.get_object(*id),
+            other => Ok(other),
+        })
+        .and_then(|o| o.as_array())
+        .ok()
+        .and_then(|array| array.iter().map(|v| v.as_float().ok()).collect());
+
+    match values.as_deref() {
+        Some([x1, y1, x2, y2]) => [*x1, *y1, *x2, *y2],
+        _ => DEFAULT_MEDIA_BOX,
+    }
+}
+
+///
+/// Copy referenced (or inherited) page resources and their font dictionary into the page,
+/// so the stamp font can be added after the shared objects are written
+///
+pub fn inline_page_resources(doc: &mut Document, page_id: ObjectId) -> lopdf::Result<()> {
+    let page = doc.get_dictionary(page_id)?;
+    let resources = match page.get(b"Resources") {
+        Ok(resources) => resources,
+        // Inherited from the parent Pages node
+        Err(_) => match page
+            .get(b"Parent")
+            .and_then(|o| o.as_reference())
+            .and_then(|parent| doc.get_dictionary(parent))
+            .and_then(|parent| parent.get(b"Resources"))
+        {
+            Ok(resources) => resources,
+            Err(_) => return Ok(()),
+        },
+    };
+
+    let mut resources = match resources {
+        Object::Reference(id) => doc.get_dictionary(*id)?.clone(),
+        other => other.as_dict()?.clone(),
+    };
+    if let Ok(fonts_id) = resources.get(b"Font").and_then(|o| o.as_reference()) {
+        let fonts = doc.get_dictionary(fonts_id)?.clone();
+        resources.set("Font", fonts);
+    }
+
+    doc.get_dictionary_mut(page_id)?.set("Resources", resources);
+    Ok(())
+}
+
+///
+/// Register the stamp font in the page resources, resolving referenced dictionaries
+///
+fn add_font_resource(
+    document: &mut Document,
+    page_id: ObjectId,
+    font_id: ObjectId,
+) -> lopdf::Result<()> {
+    let page = document.get_dictionary_mut(page_id)?;
+    let resources_id = match page.get(b"Resources") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(Object::Dictionary(_)) => None,
+        _ => {
+            page.set("Resources", Dictionary::new());
+            None
+        }
+    };
+
+    let resources = match resources_id {
+        Some(id) => document.get_dictionary_mut(id)?,
+        None => document
+            .get_dictionary_mut(page_id)?
+            .get_mut(b"Resources")?
+            .as_dict_mut()?,
+    };
+
+    let fonts_id = match resources.get(b"Font") {
+        Ok(Object::Reference(id)) => Some(*id),
+        Ok(Object::Dictionary(_)) => None,
+        _ => {
+            resources.set("Font", Dictionary::new());
+            None
+        }
+    };
+
+    let fonts = match fonts_id {
+        Some(id) => document.get_dictionary_mut(id)?,
+        None => resources.get_mut(b"Font")?.as_dict_mut()?,
+    };
+    fonts.set(FONT_NAME, font_id);
+
+    Ok(())
+}
+
+///
+/// Encode text for the standard WinAnsiEncoding, unsupported characters become '?'
+///
+fn encode_win_ansi(text: &str, replaced: &mut BTreeSet<char>) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' => c as u8,
+            '\u{A0}'..='\u{FF}' => c as u32 as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => {
+                replaced.insert(c);
+                b'?'
+            }
+        })
+        .collect()
+}
+
+///
+/// Width of WinAnsi encoded text in points, using Helvetica metrics
+///
+fn text_width(encoded: &[u8]) -> f32 {
+    let units: u32 = encoded.iter().map(|&b| helvetica_width(b)).sum();
+    units as f32 * FONT_SIZE / 1000.0
+}
+
+fn helvetica_width(byte: u8) -> u32 {
+    #[rustfmt::skip]
+    const ASCII: [u16; 95] = [
+        278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+        556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+        1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+        667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+        333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+        556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+    ];
+
+    match byte {
+        32..=126 => ASCII[(byte - 32) as usize] as u32,
+        _ => 556,
+    }
+}
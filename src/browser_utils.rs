@@ -2,9 +2,25 @@ use anyhow::Result;
 use chromiumoxide::browser::BrowserConfig;
 use std::path::Path;
 
+/// Path of the browser when web2pdf is started as its wrapper, see `exec_browser_wrapper`
+const BROWSER_WRAPPER_ENV: &str = "WEB2PDF_BROWSER";
+
 pub fn build_browser_config(browser_path: &str) -> Result<BrowserConfig, String> {
-    BrowserConfig::builder()
-        .chrome_executable(browser_path)
+    let builder = BrowserConfig::builder();
+
+    // The browser is started through web2pdf itself, which moves it to its own process group
+    // so Ctrl-C in a terminal reaches only web2pdf
+    #[cfg(unix)]
+    let builder = match std::env::current_exe() {
+        Ok(exe) => builder
+            .chrome_executable(exe)
+            .env(BROWSER_WRAPPER_ENV, browser_path),
+        Err(_) => builder.chrome_executable(browser_path),
+    };
+    #[cfg(not(unix))]
+    let builder = builder.chrome_executable(browser_path);
+
+    builder
         .arg("--disable-web-security")
         .arg("--disable-features=VizDisplayCompositor")
         .arg("--disable-font-subpixel-positioning")
@@ -38,6 +54,46 @@ pub fn build_browser_config(browser_path: &str) -> Result<BrowserConfig, String>
         .build()
 }
 
+///
+/// Replace the process with the browser in a new process group when web2pdf is started as
+/// the browser wrapper, otherwise return
+///
+#[cfg(unix)]
+pub fn exec_browser_wrapper() {
+    use std::os::unix::process::CommandExt;
+
+    let Some(browser) = std::env::var_os(BROWSER_WRAPPER_ENV) else {
+        return;
+    };
+    let error = std::process::Command::new(browser)
+        .args(std::env::args_os().skip(1))
+        .env_remove(BROWSER_WRAPPER_ENV)
+        .process_group(0)
+        .exec();
+    eprintln!("Failed to start the browser: {}", error);
+    std::process::exit(1);
+}
+
+///
+/// Kill every process of a group at once. The browser leads its own group, so its renderer
+/// and helper processes go with it
+///
+#[cfg(unix)]
+pub fn kill_process_group(leader: u32) -> std::io::Result<()> {
+    // 0 and 1 would address the group of web2pdf itself and every process
+    let group = libc::pid_t::try_from(leader)
+        .ok()
+        .filter(|&group| group > 1)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+
+    // SAFETY: kill only sends a signal and touches no memory, a negative pid is a group
+    if unsafe { libc::kill(-group, libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
 /// Try to find browser binary.
 /// 1. Checks PATH (chromium, google-chrome, chrome).
 /// 2. Checks standard paths for macOS and Windows and Linux.
@@ -140,3 +196,50 @@ pub fn find_browser() -> Result<String> {
         std::env::consts::OS
     )
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+    use std::process::Command;
+
+    use super::kill_process_group;
+
+    #[test]
+    fn kills_the_whole_process_group() {
+        let mut leader = Command::new("sh")
+            .args(["-c", "sleep 30 & echo $!; wait"])
+            .stdout(std::process::Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        std::io::BufRead::read_line(
+            &mut std::io::BufReader::new(leader.stdout.take().unwrap()),
+            &mut line,
+        )
+        .unwrap();
+        let member: i32 = line.trim().parse().unwrap();
+
+        kill_process_group(leader.id()).unwrap();
+
+        assert_eq!(leader.wait().unwrap().signal(), Some(libc::SIGKILL));
+        // The member is reparented once the leader is gone, it may stay a zombie until reaped
+        let gone = (0..100).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            let state = Command::new("ps")
+                .args(["-o", "stat=", "-p", &member.to_string()])
+                .output()
+                .unwrap();
+            let state = String::from_utf8_lossy(&state.stdout);
+            state.trim().is_empty() || state.trim().starts_with('Z')
+        });
+        assert!(gone);
+    }
+
+    #[test]
+    fn refuses_own_and_every_process() {
+        assert!(kill_process_group(0).is_err());
+        assert!(kill_process_group(1).is_err());
+        assert!(kill_process_group(u32::MAX).is_err());
+    }
+}
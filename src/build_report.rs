@@ -0,0 +1,205 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// Something that went wrong while rendering a page without losing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageWarning {
+    /// Navigation didn't finish in time, loading was stopped and the page rendered as is
+    StoppedLoading,
+    /// The page didn't become ready in time
+    WaitTimeout,
+    /// The title couldn't be extracted, the URL is used instead
+    TitleFallback,
+    /// The page declares no language, `--default-lang` is used
+    LanguageUnknown,
+    /// HTTP 4xx/5xx response, the page was rendered by the `--on-http-error flag` option
+    HttpError,
+    /// Redirect to another host, the page was rendered by the `--on-offsite-redirect flag` option
+    OffsiteRedirect,
+}
+
+impl PageWarning {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::StoppedLoading => "loading stopped after timeout",
+            Self::WaitTimeout => "page wait timed out",
+            Self::TitleFallback => "title fallback to URL",
+            Self::LanguageUnknown => "language not declared, default used",
+            Self::HttpError => "HTTP error status",
+            Self::OffsiteRedirect => "redirected to another host",
+        }
+    }
+}
+
+/// A single try to render a page
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PageAttempt {
+    pub http_status: Option<u16>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Outcome of rendering a single page
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PageReport {
+    /// URL from the TOC
+    pub url: String,
+    /// URL of the loaded document after redirects
+    pub final_url: Option<String>,
+    /// HTTP status of the main document response
+    pub http_status: Option<u16>,
+    /// URLs which redirected to the final one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redirects: Vec<String>,
+    pub title: Option<String>,
+    pub adapter: String,
+    /// Navigation time in milliseconds
+    pub load_ms: u64,
+    /// Time spent waiting for the page to become ready, in milliseconds
+    pub wait_ms: u64,
+    /// Printing (or capturing) time in milliseconds
+    pub print_ms: u64,
+    /// Size of the rendered file in bytes
+    pub pdf_size: Option<u64>,
+    pub page_count: Option<u32>,
+    pub warnings: Vec<PageWarning>,
+    /// Why the page is missing from the output
+    pub error: Option<String>,
+    /// The failure is transient, e.g. a navigation error, HTTP 5xx or a PDF timeout
+    #[serde(skip)]
+    pub retryable: bool,
+    /// Every try to render the page, the fields above describe the last one
+    pub attempts: Vec<PageAttempt>,
+}
+
+impl PageReport {
+    pub fn new(url: &str, adapter: String) -> Self {
+        Self {
+            url: url.to_string(),
+            adapter,
+            ..Default::default()
+        }
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn warn(&mut self, warning: PageWarning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    pub fn fail(&mut self, error: impl ToString) {
+        self.error = Some(error.to_string());
+        self.retryable = false;
+    }
+
+    ///
+    /// Record a transient failure, the page is rendered again at the end of the run
+    ///
+    pub fn fail_retryable(&mut self, error: impl ToString) {
+        self.error = Some(error.to_string());
+        self.retryable = true;
+    }
+
+    ///
+    /// Forget the outcome of the previous attempt
+    ///
+    pub fn begin_attempt(&mut self) {
+        *self = Self {
+            url: std::mem::take(&mut self.url),
+            adapter: std::mem::take(&mut self.adapter),
+            attempts: std::mem::take(&mut self.attempts),
+            ..Default::default()
+        };
+    }
+
+    pub fn end_attempt(&mut self, started: Instant) {
+        self.attempts.push(PageAttempt {
+            http_status: self.http_status,
+            duration_ms: started.elapsed().as_millis() as u64,
+            error: self.error.clone(),
+        });
+    }
+
+    pub fn should_retry(&self) -> bool {
+        self.error.is_some() && self.retryable
+    }
+}
+
+/// Summary of a conversion run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BuildReport {
+    pub source: String,
+    /// Share of pages which made it into the output
+    pub success_ratio: f64,
+    pub pages: Vec<PageReport>,
+}
+
+impl BuildReport {
+    pub fn new(source: &str) -> Self {
+        Self {
+            source: source.to_string(),
+            success_ratio: 1.0,
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, page: PageReport) {
+        self.pages.push(page);
+        self.update_ratio();
+    }
+
+    ///
+    /// Replace the report of a page which was rendered again
+    ///
+    pub fn replace(&mut self, index: usize, page: PageReport) {
+        self.pages[index] = page;
+        self.update_ratio();
+    }
+
+    fn update_ratio(&mut self) {
+        self.success_ratio = self.succeeded() as f64 / self.pages.len() as f64;
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.pages.iter().filter(|p| p.succeeded()).count()
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "📊 Build report: {}/{} pages rendered ({:.1}%)",
+            self.succeeded(),
+            self.pages.len(),
+            self.success_ratio * 100.0
+        );
+
+        for page in &self.pages {
+            if let Some(error) = &page.error {
+                println!("  ❌ {}: {}", page.url, error);
+            } else if page.attempts.len() > 1 {
+                println!(
+                    "  🔁 {}: rendered after {} attempts",
+                    page.url,
+                    page.attempts.len()
+                );
+            } else if !page.warnings.is_empty() {
+                let warnings: Vec<&str> = page.warnings.iter().map(PageWarning::describe).collect();
+                println!("  ⚠️ {}: {}", page.url, warnings.join(", "));
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("🧾 Build report saved to {}", path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::browser::Browser;
+use chromiumoxide::cdp::browser_protocol::page::PrintToPdfParams;
+
+use crate::escape::{escape_attr, escape_text};
+use crate::readiness::Timeouts;
+
+const COVER_HTML: &str = include_str!("../html/cover.html");
+
+/// Values shown on the generated cover page
+#[derive(Debug, Clone)]
+pub struct CoverInfo {
+    pub title: String,
+    pub url: String,
+    pub date: String,
+    pub subtitle: Option<String>,
+    pub author: Option<String>,
+    pub icon_url: Option<String>,
+    /// Titles of all volumes of a split book, empty for a single file
+    pub volumes: Vec<String>,
+    /// Number of the volume the cover belongs to, starting from 1
+    pub volume: usize,
+}
+
+impl CoverInfo {
+    fn to_html(&self) -> String {
+        let logo = self
+            .icon_url
+            .as_ref()
+            .map(|u| format!(r#"<img src="{}" alt="" />"#, escape_attr(u)))
+            .unwrap_or_default();
+        let subtitle = self
+            .subtitle
+            .as_ref()
+            .map(|s| format!(r#"<p class="subtitle">{}</p>"#, escape_text(s)))
+            .unwrap_or_default();
+        let author = self
+            .author
+            .as_ref()
+            .map(|a| format!(r#"<p class="author">{}</p>"#, escape_text(a)))
+            .unwrap_or_default();
+        let volumes = if self.volumes.is_empty() {
+            String::new()
+        } else {
+            let items: String = self
+                .volumes
+                .iter()
+                .enumerate()
+                .map(|(index, title)| {
+                    let class = if index + 1 == self.volume {
+                        r#" class="current""#
+                    } else {
+                        ""
+                    };
+                    format!(
+                        "<li{}>Volume {}: {}</li>",
+                        class,
+                        index + 1,
+                        escape_text(title)
+                    )
+                })
+                .collect();
+            format!(r#"<ol class="volumes">{}</ol>"#, items)
+        };
+
+        COVER_HTML
+            .replace("{{logo}}", &logo)
+            .replace("{{subtitle}}", &subtitle)
+            .replace("{{author}}", &author)
+            .replace("{{volumes}}", &volumes)
+            .replace("{{title}}", &escape_text(&self.title))
+            .replace("{{url}}", &escape_text(&self.url))
+            .replace("{{date}}", &escape_text(&self.date))
+    }
+}
+
+///
+/// Render the cover page from the HTML template through the same Chromium pipeline,
+/// the tab is closed also when rendering fails
+///
+pub async fn render_cover(
+    browser: &Browser,
+    info: &CoverInfo,
+    pdf_path: &Path,
+    timeouts: &Timeouts,
+) -> Result<()> {
+    println!("  📔 Rendering cover page...");
+
+    let page = browser.new_page("about:blank").await?;
+    let result = print_cover(&page, info, pdf_path, timeouts).await;
+
+    if let Err(e) = page.close().await {
+        tracing::debug!("Failed to close cover page: {}", e);
+    }
+    result?;
+
+    println!("  ✅ Cover page saved to {}", pdf_path.display());
+
+    Ok(())
+}
+
+async fn print_cover(
+    page: &Page,
+    info: &CoverInfo,
+    pdf_path: &Path,
+    timeouts: &Timeouts,
+) -> Result<()> {
+    page.set_content(info.to_html()).await?;
+
+    // Wait for the logo to be loaded
+    let script = format!("({})({})", crate::PAGE_WAIT_JS, timeouts.image.as_millis());
+    let is_ready: bool = page
+        .evaluate_expression(script)
+        .await?
+        .into_value()
+        .unwrap_or(false);
+    tracing::debug!("Cover page ready: {}", is_ready);
+
+    let pdf_opts = PrintToPdfParams {
+        generate_tagged_pdf: Some(true),
+        print_background: Some(true),
+        prefer_css_page_size: Some(true),
+        ..Default::default()
+    };
+
+    tokio::time::timeout(timeouts.pdf, page.save_pdf(pdf_opts, pdf_path)).await??;
+
+    Ok(())
+}
@@ -0,0 +1,507 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+
+use crate::_pdf_utils::extract_text::extract_text;
+use crate::_pdf_utils::split::chapter_key;
+use crate::manifest::{Manifest, ManifestChapter};
+
+/// Longest line of a text diff, longer paragraphs are shortened
+const MAX_LINE_CHARS: usize = 120;
+
+/// Largest comparison table of a text diff, bigger chapters are only summarized
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Options of the change report
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Show a unified diff of the paragraphs of modified chapters
+    pub text_diff: bool,
+    /// Unchanged paragraphs around every change
+    pub context: usize,
+    /// Lines of the text diff shown per chapter
+    pub max_lines: usize,
+}
+
+/// What happened to a chapter between two builds
+#[derive(Debug, Clone)]
+pub enum ChapterChange {
+    Added(ManifestChapter),
+    Removed(ManifestChapter),
+    Modified {
+        old: ManifestChapter,
+        new: ManifestChapter,
+    },
+}
+
+/// Changes between two builds
+#[derive(Debug, Clone, Default)]
+pub struct DiffReport {
+    pub changes: Vec<ChapterChange>,
+    pub unchanged: usize,
+}
+
+impl DiffReport {
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    pub fn print(&self, options: &DiffOptions) {
+        let count = |f: fn(&ChapterChange) -> bool| self.changes.iter().filter(|c| f(c)).count();
+        println!(
+            "📋 Changes: {} added, {} removed, {} modified, {} unchanged",
+            count(|c| matches!(c, ChapterChange::Added(_))),
+            count(|c| matches!(c, ChapterChange::Removed(_))),
+            count(|c| matches!(c, ChapterChange::Modified { .. })),
+            self.unchanged
+        );
+
+        for change in &self.changes {
+            match change {
+                ChapterChange::Added(new) => {
+                    println!("  + {}{}", new.title, location(new, ""));
+                }
+                ChapterChange::Removed(old) => {
+                    println!("  - {}{}", old.title, location(old, "was "));
+                }
+                ChapterChange::Modified { old, new } => {
+                    let was = match (old.start_page, new.start_page) {
+                        (Some(old_page), Some(new_page)) if old_page != new_page => {
+                            format!(", was page {}", old_page)
+                        }
+                        _ => String::new(),
+                    };
+                    let renamed = if old.title != new.title {
+                        format!(" (was \"{}\")", old.title)
+                    } else {
+                        String::new()
+                    };
+                    println!("  ~ {}{}{}{}", new.title, renamed, location(new, ""), was);
+
+                    if options.text_diff {
+                        for line in text_diff(&old.paragraphs, &new.paragraphs, options) {
+                            println!("      {}", line);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn location(chapter: &ManifestChapter, prefix: &str) -> String {
+    match (&chapter.file, chapter.start_page) {
+        (Some(file), Some(page)) => format!(" ({}{}, page {})", prefix, file, page),
+        (None, Some(page)) => format!(" ({}page {})", prefix, page),
+        _ => String::new(),
+    }
+}
+
+///
+/// Load a build manifest (`.json`) or extract the chapters of a merged PDF
+///
+pub fn load_build(path: &Path) -> Result<Manifest> {
+    let is_manifest = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    if is_manifest {
+        return Manifest::load(path);
+    }
+
+    let extracted = extract_text(path)?;
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    Ok(Manifest {
+        title: extracted.title.clone(),
+        source: None,
+        chapters: extracted
+            .chapters
+            .iter()
+            .map(|chapter| ManifestChapter::from_text(chapter, file.clone()))
+            .collect(),
+    })
+}
+
+///
+/// Compare two builds chapter by chapter, chapters are matched by TOC href when both builds
+/// have them and by title otherwise
+///
+pub fn diff_builds(old: &Manifest, new: &Manifest) -> DiffReport {
+    let by_href = old
+        .chapters
+        .iter()
+        .chain(&new.chapters)
+        .all(|c| c.href.is_some());
+    let old_keys = chapter_keys(&old.chapters, by_href);
+    let new_keys = chapter_keys(&new.chapters, by_href);
+
+    let old_index: HashMap<&str, usize> = old_keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (key.as_str(), i))
+        .collect();
+    let mut matched = vec![false; old.chapters.len()];
+    let mut report = DiffReport::default();
+
+    for (new_chapter, key) in new.chapters.iter().zip(&new_keys) {
+        match old_index.get(key.as_str()) {
+            Some(&i) => {
+                matched[i] = true;
+                let old_chapter = &old.chapters[i];
+                if old_chapter.paragraphs == new_chapter.paragraphs
+                    && old_chapter.title == new_chapter.title
+                {
+                    report.unchanged += 1;
+                } else {
+                    report.changes.push(ChapterChange::Modified {
+                        old: old_chapter.clone(),
+                        new: new_chapter.clone(),
+                    });
+                }
+            }
+            None => report
+                .changes
+                .push(ChapterChange::Added(new_chapter.clone())),
+        }
+    }
+
+    for (chapter, matched) in old.chapters.iter().zip(matched) {
+        if !matched {
+            report.changes.push(ChapterChange::Removed(chapter.clone()));
+        }
+    }
+
+    report
+}
+
+///
+/// Keys matching chapters of two builds, repeated titles are numbered by occurrence
+///
+fn chapter_keys(chapters: &[ManifestChapter], by_href: bool) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    chapters
+        .iter()
+        .map(|chapter| {
+            let key = match (&chapter.href, by_href) {
+                (Some(href), true) => chapter_key(href),
+                _ => chapter.title.clone(),
+            };
+            let occurrence = seen.entry(key.clone()).or_default();
+            *occurrence += 1;
+            format!("{}#{}", key, occurrence)
+        })
+        .collect()
+}
+
+/// A step of the edit script between two lists of paragraphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+///
+/// Unified diff of paragraphs: `@@ -a,b +c,d @@` hunks with `-`, `+` and context lines
+///
+pub fn text_diff(old: &[String], new: &[String], options: &DiffOptions) -> Vec<String> {
+    let Some(edits) = edit_script(old, new) else {
+        return vec![format!(
+            "(too large to compare: {} paragraphs before, {} after)",
+            old.len(),
+            new.len()
+        )];
+    };
+
+    let changed: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| !matches!(e, Edit::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Group changes whose context overlaps into hunks
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(options.context);
+        let end = (i + options.context + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (start, end) in hunks {
+        let hunk = &edits[start..end];
+        // Lines of each side before the hunk
+        let old_before = edits[..start]
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_before = edits[..start]
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+        let old_count = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+        lines.push(format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_before, old_count),
+            hunk_range(new_before, new_count)
+        ));
+
+        for edit in hunk {
+            let (prefix, text) = match *edit {
+                Edit::Equal(i, _) => (' ', &old[i]),
+                Edit::Delete(i) => ('-', &old[i]),
+                Edit::Insert(j) => ('+', &new[j]),
+            };
+            lines.push(format!("{}{}", prefix, shorten(text)));
+        }
+    }
+
+    if options.max_lines > 0 && lines.len() > options.max_lines {
+        let hidden = lines.len() - options.max_lines;
+        lines.truncate(options.max_lines);
+        lines.push(format!("... {} more line(s)", hidden));
+    }
+    lines
+}
+
+///
+/// Range of a hunk side, an empty side starts at the line before it as in `diff -u`
+///
+fn hunk_range(before: usize, count: usize) -> String {
+    let start = if count == 0 { before } else { before + 1 };
+    format!("{},{}", start, count)
+}
+
+///
+/// Shortest edit script from the longest common subsequence, `None` if the lists are too long
+///
+fn edit_script(old: &[String], new: &[String]) -> Option<Vec<Edit>> {
+    // Common prefix and suffix don't need the table
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let (n, m) = (old_mid.len(), new_mid.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    // lcs[i][j] is the LCS length of old_mid[i..] and new_mid[j..]
+    let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+    let at = |i: usize, j: usize| i * (m + 1) + j;
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[at(i, j)] = if old_mid[i] == new_mid[j] {
+                lcs[at(i + 1, j + 1)] + 1
+            } else {
+                lcs[at(i + 1, j)].max(lcs[at(i, j + 1)])
+            };
+        }
+    }
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Equal(i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            edits.push(Edit::Equal(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lcs[at(i + 1, j)] >= lcs[at(i, j + 1)]) {
+            edits.push(Edit::Delete(prefix + i));
+            i += 1;
+        } else {
+            edits.push(Edit::Insert(prefix + j));
+            j += 1;
+        }
+    }
+    edits.extend((0..suffix).map(|k| Edit::Equal(old.len() - suffix + k, new.len() - suffix + k)));
+
+    Some(edits)
+}
+
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= MAX_LINE_CHARS {
+        return text.to_string();
+    }
+    let short: String = text.chars().take(MAX_LINE_CHARS - 1).collect();
+    format!("{}…", short)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{Manifest, ManifestChapter};
+
+    use super::{ChapterChange, DiffOptions, Edit, diff_builds, edit_script, text_diff};
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn options(context: usize) -> DiffOptions {
+        DiffOptions {
+            text_diff: true,
+            context,
+            max_lines: 0,
+        }
+    }
+
+    fn chapter(title: &str, href: Option<&str>, text: &str) -> ManifestChapter {
+        ManifestChapter {
+            title: title.to_string(),
+            href: href.map(str::to_string),
+            paragraphs: lines(text),
+            ..Default::default()
+        }
+    }
+
+    fn build(chapters: Vec<ManifestChapter>) -> Manifest {
+        Manifest {
+            title: None,
+            source: None,
+            chapters,
+        }
+    }
+
+    #[test]
+    fn edit_script_keeps_the_longest_common_subsequence() {
+        use Edit::*;
+
+        let edits = edit_script(&lines("a b c d e"), &lines("a x c e f")).unwrap();
+        assert_eq!(
+            edits,
+            [
+                Equal(0, 0),
+                Delete(1),
+                Insert(1),
+                Equal(2, 2),
+                Delete(3),
+                Equal(4, 3),
+                Insert(4)
+            ]
+        );
+        assert_eq!(edit_script(&[], &lines("a")).unwrap(), [Insert(0)]);
+        assert_eq!(edit_script(&lines("a"), &[]).unwrap(), [Delete(0)]);
+    }
+
+    #[test]
+    fn hunks_show_context_and_merge_when_it_overlaps() {
+        let old = lines("1 2 3 4 5 6 7 8 9");
+        let new = lines("1 2 three 4 5 6 7 8 nine");
+
+        assert_eq!(
+            text_diff(&old, &new, &options(1)),
+            [
+                "@@ -2,3 +2,3 @@",
+                " 2",
+                "-3",
+                "+three",
+                " 4",
+                "@@ -8,2 +8,2 @@",
+                " 8",
+                "-9",
+                "+nine",
+            ]
+        );
+        assert_eq!(text_diff(&old, &new, &options(3)).len(), 12);
+        assert_eq!(text_diff(&old, &new, &options(3))[0], "@@ -1,9 +1,9 @@");
+    }
+
+    #[test]
+    fn empty_hunk_sides_start_at_the_line_before() {
+        // Insert after line 2, as `diff -u` prints it
+        assert_eq!(
+            text_diff(&lines("a b c"), &lines("a b x c"), &options(0)),
+            ["@@ -2,0 +3,1 @@", "+x"]
+        );
+        assert_eq!(
+            text_diff(&lines("a b x c"), &lines("a b c"), &options(0)),
+            ["@@ -3,1 +2,0 @@", "-x"]
+        );
+        assert_eq!(
+            text_diff(&[], &lines("a"), &options(0)),
+            ["@@ -0,0 +1,1 @@", "+a"]
+        );
+    }
+
+    #[test]
+    fn long_diffs_are_truncated() {
+        let mut options = options(0);
+        options.max_lines = 2;
+        assert_eq!(
+            text_diff(&lines("a b"), &lines("c d"), &options),
+            ["@@ -1,2 +1,2 @@", "-a", "... 3 more line(s)"]
+        );
+    }
+
+    #[test]
+    fn chapters_are_matched_by_href() {
+        let old = build(vec![
+            chapter("Intro", Some("https://a.org/"), "hello"),
+            chapter("Setup", Some("https://a.org/setup/"), "install run"),
+            chapter("Old", Some("https://a.org/old"), "gone"),
+            chapter("Same", Some("https://a.org/same"), "text"),
+        ]);
+        let new = build(vec![
+            chapter("Intro", Some("https://a.org"), "hello"),
+            chapter(
+                "Installation",
+                Some("https://a.org/setup#top"),
+                "install run",
+            ),
+            chapter("Same", Some("https://a.org/same"), "changed text"),
+            chapter("New", Some("https://a.org/new"), "fresh"),
+        ]);
+
+        let report = diff_builds(&old, &new);
+        assert_eq!(report.unchanged, 1);
+
+        let changes: Vec<String> = report
+            .changes
+            .iter()
+            .map(|change| match change {
+                ChapterChange::Added(new) => format!("+{}", new.title),
+                ChapterChange::Removed(old) => format!("-{}", old.title),
+                ChapterChange::Modified { old, new } => format!("~{}>{}", old.title, new.title),
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            ["~Setup>Installation", "~Same>Same", "+New", "-Old"]
+        );
+    }
+
+    #[test]
+    fn chapters_without_href_are_matched_by_title_occurrence() {
+        let old = build(vec![
+            chapter("Notes", None, "one"),
+            chapter("Notes", None, "two"),
+        ]);
+        let new = build(vec![chapter("Notes", None, "one")]);
+
+        let report = diff_builds(&old, &new);
+        assert_eq!(report.unchanged, 1);
+        assert!(matches!(
+            report.changes.as_slice(),
+            [ChapterChange::Removed(old)] if old.paragraphs == ["two"]
+        ));
+        assert!(!diff_builds(&new, &new).has_changes());
+    }
+}
@@ -0,0 +1,16 @@
+///
+/// Escape text content of an HTML or XML element
+///
+pub fn escape_text(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+///
+/// Escape a double-quoted HTML or XML attribute value
+///
+pub fn escape_attr(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
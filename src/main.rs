@@ -1,15 +1,29 @@
 use anyhow::{Context, Result};
 use chromiumoxide::cdp::browser_protocol::page::{PrintToPdfParams, StopLoadingParams};
-use chromiumoxide::{browser::Browser, page::MediaTypeParams};
-use clap::Parser;
+use chromiumoxide::{Page, browser::Browser, page::MediaTypeParams};
 use clap::builder::styling::{AnsiColor, Styles};
+use clap::{Parser, Subcommand};
 use futures::StreamExt;
 
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use tempfile::{TempDir, tempdir};
+use tokio::task::JoinHandle;
+
+mod _export;
+use _export::OutputFormat;
+use _export::capture::capture_page;
+use _export::export;
 
 mod _pdf_utils;
-use _pdf_utils::merge_pdfs::merge_pdfs;
+use _pdf_utils::check_pdf::check_pdf;
+use _pdf_utils::extract_text::extract_text;
+use _pdf_utils::merge_pdfs::{MergeOptions, merge_pdfs};
+use _pdf_utils::metadata::{DocumentMetadata, source_date};
+use _pdf_utils::page_labels::PageLabelStyle;
+use _pdf_utils::pdfa::PdfaLevel;
+use _pdf_utils::split::{SplitBy, SplitMode, Volume, parse_size, plan_volumes, volume_links};
+use _pdf_utils::stamp_pages::StampOptions;
 
 mod browser_utils;
 use crate::_adapter_registry::traits::ResourceAdapter;
@@ -18,6 +32,35 @@ use crate::toc::TocNode;
 
 mod toc;
 
+mod diff;
+use diff::{DiffOptions, diff_builds, load_build};
+
+mod manifest;
+use manifest::Manifest;
+
+mod readiness;
+use readiness::{NetworkTracker, Readiness, Timeouts, parse_readiness, wait_until_ready};
+
+mod navigation;
+use navigation::{ResponsePolicy, ResponseRecorder};
+
+mod shutdown;
+use shutdown::{Shutdown, confirm_partial_output};
+
+mod build_report;
+use build_report::{BuildReport, PageReport, PageWarning};
+
+mod escape;
+
+mod cover;
+use cover::{CoverInfo, render_cover};
+
+mod print_templates;
+use print_templates::PrintTemplates;
+
+mod site_meta;
+use site_meta::SiteMeta;
+
 mod _adapter_registry;
 use _adapter_registry::registry::AdapterRegistry;
 
@@ -27,9 +70,13 @@ use _adapters::_mdbook::adapter::MdBookAdapter;
 // JavaScript scripts
 const PAGE_WAIT_JS: &str = include_str!("../js/page-wait.js");
 const TITLE_EXTRACT_JS: &str = include_str!("../js/title-extract.js");
+const LANG_SET_JS: &str = include_str!("../js/lang-set.js");
 const PREPARE_HABR: &str = include_str!("../js/prepare-habr.js");
 
-const LOAD_PAGE_TIMEOUT_SEC: u64 = 5;
+const MAX_RETRY_DELAY_MS: u64 = 60_000;
+
+/// Time for the browser to exit after it was asked to close
+const BROWSER_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 fn styles() -> Styles {
     Styles::styled()
@@ -44,31 +91,318 @@ fn styles() -> Styles {
 
 /// Convert web pages to a PDF document
 #[derive(Parser, Debug)]
-#[command(version, about, styles = styles())]
+#[command(
+    version,
+    about,
+    styles = styles(),
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    convert: ConvertArgs,
+
+    /// Turn debugging information on
+    #[arg(short, long, global = true)]
+    debug: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check PDF/UA conformance of a PDF document
+    Check(CheckArgs),
+    /// Merge existing PDF files into one tagged document
+    Merge(Box<MergeArgs>),
+    /// Extract reading-order text of a tagged PDF document chapter by chapter
+    Extract(ExtractArgs),
+    /// Compare two builds chapter by chapter: manifests (.json) or merged PDF files
+    Diff(DiffArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct ConvertArgs {
     /// Source URL address
-    url: String,
+    #[arg(required = true)]
+    url: Option<String>,
 
     /// Output file name
     #[arg(default_value = "output.pdf")]
     output: String,
 
-    /// Turn debugging information on
-    #[arg(short, long)]
-    debug: bool,
+    /// Output format, the extension of the default output file follows it
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = OutputFormat::Pdf)]
+    format: OutputFormat,
+
+    /// Add a generated cover page before the first chapter
+    #[arg(long)]
+    cover: bool,
+
+    /// Subtitle shown on the cover page
+    #[arg(long)]
+    subtitle: Option<String>,
+
+    /// Chromium header template for every page: inline HTML or a path to an HTML file.
+    /// Supports Chrome placeholders (title, url, pageNumber, totalPages, date) and {chapter}, {level}
+    #[arg(long, value_name = "HTML")]
+    header_template: Option<String>,
+
+    /// Chromium footer template for every page, same syntax as --header-template
+    #[arg(long, value_name = "HTML")]
+    footer_template: Option<String>,
+
+    /// Write the build report (JSON) with the status, timings and warnings of every page
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Retries of a page after a navigation error, HTTP 5xx/429 or a PDF timeout
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    retries: u32,
+
+    /// Delay before the first retry in milliseconds, doubled for every next one
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    retry_delay: u64,
+
+    /// Navigation timeout in seconds, loading is stopped after it and the page is printed as is
+    #[arg(long, value_name = "SEC", default_value_t = 5)]
+    load_timeout: u64,
+
+    /// Time to wait for a page to become ready, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    wait_timeout: u64,
+
+    /// Time to wait for every image of a page, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 3000)]
+    image_timeout: u64,
+
+    /// PDF printing timeout of a page in seconds
+    #[arg(long, value_name = "SEC", default_value_t = 60)]
+    pdf_timeout: u64,
+
+    /// When a page is ready: document, network-idle[:MS], selector:CSS, js:EXPRESSION or delay:MS
+    /// (defaults to the site adapter's strategy)
+    #[arg(long, value_name = "STRATEGY", value_parser = parse_readiness)]
+    wait_for: Option<Readiness>,
+
+    /// Language of pages which don't declare one (BCP 47, e.g. "de")
+    #[arg(long, value_name = "LANG", default_value = "en")]
+    default_lang: String,
+
+    /// What to do with a page responding with HTTP 4xx/5xx
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = ResponsePolicy::Skip)]
+    on_http_error: ResponsePolicy,
+
+    /// What to do with a page redirecting to another host, e.g. to a login page
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = ResponsePolicy::Skip)]
+    on_offsite_redirect: ResponsePolicy,
+
+    /// Build a document from the rendered pages of an interrupted run without asking
+    #[arg(long)]
+    keep_partial: bool,
+
+    #[command(flatten)]
+    document: DocumentArgs,
+}
+
+/// Options of the merged document shared by `convert` and `merge`
+#[derive(clap::Args, Debug)]
+struct DocumentArgs {
+    /// Document title (defaults to the site title or the first bookmark of merged files)
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Document author
+    #[arg(long)]
+    author: Option<String>,
+
+    /// Document subject
+    #[arg(long)]
+    subject: Option<String>,
+
+    /// Document keywords
+    #[arg(long)]
+    keywords: Option<String>,
+
+    /// Produce archival PDF/A output of the given level
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    pdfa: Option<PdfaLevel>,
+
+    /// Page numbering shown by PDF viewers
+    #[arg(long, value_enum, value_name = "STYLE", default_value_t = PageLabelStyle::Decimal)]
+    page_labels: PageLabelStyle,
+
+    /// Running header stamped on every page after the merge.
+    /// Placeholders: {page}, {pages}, {chapter}, {title}; `|` separates left, center and right parts
+    #[arg(long, value_name = "TEMPLATE")]
+    stamp_header: Option<String>,
+
+    /// Running footer stamped on every page after the merge, same syntax as --stamp-header
+    #[arg(long, value_name = "TEMPLATE")]
+    stamp_footer: Option<String>,
+
+    /// TrueType font embedded for the stamped text (defaults to a system sans-serif font)
+    #[arg(long, value_name = "FILE")]
+    stamp_font: Option<PathBuf>,
+
+    /// Depth of heading bookmarks added under every chapter bookmark, 0 disables them
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    outline_depth: usize,
+
+    /// Fail instead of producing a partially tagged document when a chapter is untagged,
+    /// can't be loaded or its structure can't be sanitized
+    #[arg(long)]
+    strict: bool,
+
+    /// Produce byte-identical output for unchanged content: dates come from SOURCE_DATE_EPOCH
+    /// (or the Unix epoch), the file identifier is a digest of the content anyway
+    #[arg(long)]
+    reproducible: bool,
+
+    /// Split the output into one PDF per top-level chapter with its children
+    #[arg(long, value_enum, value_name = "MODE", conflicts_with_all = ["split_pages", "split_size"])]
+    split_by: Option<SplitBy>,
+
+    /// Split the output into volumes of at most N pages, chapters are never divided
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "split_size")]
+    split_pages: Option<u32>,
+
+    /// Split the output into volumes of at most this size, e.g. 50MB, chapters are never divided
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    split_size: Option<u64>,
+
+    /// Write a build manifest (JSON) with the chapters, their pages and text, used by `diff`
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<PathBuf>,
+}
+
+impl ConvertArgs {
+    fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            load: Duration::from_secs(self.load_timeout),
+            wait: Duration::from_millis(self.wait_timeout),
+            image: Duration::from_millis(self.image_timeout),
+            pdf: Duration::from_secs(self.pdf_timeout),
+        }
+    }
+}
+
+impl DocumentArgs {
+    fn split_mode(&self) -> Option<SplitMode> {
+        match (self.split_by, self.split_pages, self.split_size) {
+            (Some(SplitBy::TopLevel), _, _) => Some(SplitMode::TopLevel),
+            (_, Some(pages), _) => Some(SplitMode::Pages(pages)),
+            (_, _, Some(size)) => Some(SplitMode::Size(size)),
+            _ => None,
+        }
+    }
+
+    fn merge_options(&self, title: String) -> Result<MergeOptions> {
+        Ok(MergeOptions {
+            metadata: DocumentMetadata {
+                title: Some(title),
+                author: self.author.clone(),
+                subject: self.subject.clone(),
+                keywords: self.keywords.clone(),
+                date: source_date(self.reproducible)?,
+                ..Default::default()
+            },
+            pdfa: self.pdfa,
+            page_labels: self.page_labels,
+            outline_depth: self.outline_depth,
+            strict: self.strict,
+            stamp: StampOptions {
+                header: self.stamp_header.clone(),
+                footer: self.stamp_footer.clone(),
+                font: self.stamp_font.clone(),
+            },
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct MergeArgs {
+    /// PDF files to merge, in reading order
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Output file name
+    #[arg(short, long, default_value = "output.pdf")]
+    output: PathBuf,
+
+    /// TOC file with a bookmark title for every input file, indented by two spaces per level
+    #[arg(long, value_name = "FILE")]
+    toc: Option<PathBuf>,
+
+    #[command(flatten)]
+    document: DocumentArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct CheckArgs {
+    /// PDF file to check
+    file: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct DiffArgs {
+    /// Manifest or PDF of the previous build
+    old: PathBuf,
+
+    /// Manifest or PDF of the new build
+    new: PathBuf,
+
+    /// Show a unified diff of the paragraphs of modified chapters
+    #[arg(long)]
+    text_diff: bool,
+
+    /// Unchanged paragraphs shown around every change of the text diff
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    context: usize,
+
+    /// Lines of the text diff shown per chapter, 0 shows all
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    max_lines: usize,
+
+    /// Exit with code 1 if any chapter was added, removed or modified
+    #[arg(long)]
+    exit_code: bool,
+}
+
+/// Output of the `extract` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ExtractFormat {
+    /// Chapter titles followed by headings and paragraphs
+    Text,
+    /// Title, headings, paragraphs and page numbers of every chapter
+    Json,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExtractArgs {
+    /// Tagged PDF file to extract the text from
+    file: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = ExtractFormat::Text)]
+    format: ExtractFormat,
+
+    /// Write to a file instead of the standard output
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    #[cfg(unix)]
+    browser_utils::exec_browser_wrapper();
+
     let args = Args::parse();
 
-    let url = &args.url;
-    let output = &args.output;
     let debug_mode = args.debug;
 
-    let browser_path = find_browser().context("Browser not found!")?;
-    println!("Use browser: {}", browser_path);
-
     use tracing_subscriber::EnvFilter;
 
     if debug_mode {
@@ -87,6 +421,128 @@ async fn main() -> Result<()> {
             .init();
     }
 
+    match args.command {
+        Some(Command::Check(check_args)) => check(check_args),
+        Some(Command::Merge(merge_args)) => merge(*merge_args),
+        Some(Command::Extract(extract_args)) => extract(extract_args),
+        Some(Command::Diff(diff_args)) => diff(diff_args),
+        None => convert(args.convert, debug_mode).await,
+    }
+}
+
+///
+/// Check PDF/UA conformance and fail if any errors are found
+///
+fn check(args: CheckArgs) -> Result<()> {
+    println!("🔍 Checking {}", args.file.display());
+
+    let report = check_pdf(&args.file)?;
+    report.print();
+
+    if report.error_count() > 0 {
+        anyhow::bail!("PDF/UA check failed with {} error(s)", report.error_count());
+    }
+
+    Ok(())
+}
+
+///
+/// Extract the text of a tagged PDF following its structure tree
+///
+fn extract(args: ExtractArgs) -> Result<()> {
+    let text = extract_text(&args.file)?;
+
+    let content = match args.format {
+        ExtractFormat::Text => text.to_plain_text(),
+        ExtractFormat::Json => serde_json::to_string_pretty(&text)? + "\n",
+    };
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "✅ Extracted {} chapter(s) from {} to {}",
+                text.chapters.len(),
+                args.file.display(),
+                path.display()
+            );
+        }
+        None => print!("{}", content),
+    }
+
+    Ok(())
+}
+
+///
+/// Report chapters added, removed or modified between two builds
+///
+fn diff(args: DiffArgs) -> Result<()> {
+    let old = load_build(&args.old)?;
+    let new = load_build(&args.new)?;
+
+    println!(
+        "🔍 Comparing {} with {}",
+        args.old.display(),
+        args.new.display()
+    );
+    let report = diff_builds(&old, &new);
+    report.print(&DiffOptions {
+        text_diff: args.text_diff,
+        context: args.context,
+        max_lines: args.max_lines,
+    });
+
+    if args.exit_code && report.has_changes() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+///
+/// Merge existing PDF files, bookmark titles and levels come from the TOC file or the file names
+///
+fn merge(args: MergeArgs) -> Result<()> {
+    let toc = match &args.toc {
+        Some(path) => toc::toc_from_file(path, &args.files)?,
+        None => toc::toc_from_files(&args.files),
+    };
+
+    let title = args.document.title.clone().unwrap_or_else(|| {
+        toc.first()
+            .and_then(|node| node.title.clone())
+            .unwrap_or_default()
+    });
+    let merge_options = args.document.merge_options(title)?;
+
+    let volumes = plan_volumes(toc, args.document.split_mode(), &args.output);
+    merge_volumes(&volumes, &merge_options, &[])?;
+
+    if let Some(path) = &args.document.manifest {
+        Manifest::from_volumes(merge_options.metadata.title.clone(), None, &volumes).save(path)?;
+    }
+
+    Ok(())
+}
+
+///
+/// Convert a website into a PDF document
+///
+async fn convert(args: ConvertArgs, debug_mode: bool) -> Result<()> {
+    let url = &args.url.clone().context("URL is required")?;
+    let output = &args.output;
+
+    if args.format != OutputFormat::Pdf && args.document.split_mode().is_some() {
+        anyhow::bail!("Splitting into volumes is only supported for PDF output");
+    }
+    if args.format != OutputFormat::Pdf && args.document.manifest.is_some() {
+        anyhow::bail!("Build manifests are only supported for PDF output");
+    }
+
+    let browser_path = find_browser().context("Browser not found!")?;
+    println!("Use browser: {}", browser_path);
+
     let mut toc = toc::generate_toc(url).await?;
 
     // Limit in debug dev mode
@@ -135,64 +591,385 @@ async fn main() -> Result<()> {
     let adapter = registry.detect(&html, &browser, url).await;
     tracing::info!("Detected adapter {:?}", adapter);
 
+    let site_meta = SiteMeta::from_html(&html, url);
+
+    let templates = PrintTemplates::load(
+        args.header_template.as_deref(),
+        args.footer_template.as_deref(),
+    )?;
+
     // 📂 2. Temporary folder for individual PDFs
     let dir = tempdir()?;
 
+    let browser_pid = browser
+        .get_mut_child()
+        .and_then(|child| child.as_mut_inner().id());
+    let shutdown = Shutdown::listen(browser_pid);
     let toc_len = toc.len();
+    let adapter_name = format!("{:?}", adapter);
+    let mut build_report = BuildReport::new(url);
+    let context = RenderContext {
+        browser: &browser,
+        dir: &dir,
+        adapter,
+        templates: &templates,
+        format: args.format,
+        readiness: args.wait_for.clone().unwrap_or_else(|| adapter.readiness()),
+        timeouts: args.timeouts(),
+        http_errors: args.on_http_error,
+        offsite_redirects: args.on_offsite_redirect,
+        default_lang: &args.default_lang,
+    };
     // 🌀 3. Process each page
     for (i, node) in toc.iter_mut().enumerate() {
+        let mut page_report = PageReport::new(&node.href, adapter_name.clone());
+        if shutdown.is_requested() {
+            page_report.fail("Not rendered: interrupted");
+            build_report.push(page_report);
+            continue;
+        }
+
         println!("→ [{}/{}] Processing {}", i + 1, toc_len, node.href);
+        render_attempt(i, node, &context, &mut page_report, &shutdown).await;
+        build_report.push(page_report);
+    }
 
-        process_page(i, node, &browser, &dir, adapter).await?;
+    // 🔁 Transient failures are retried at the end of the run, with a growing delay
+    for round in 1..=args.retries {
+        if shutdown.is_requested() {
+            break;
+        }
+        let pending: Vec<usize> = build_report
+            .pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| page.should_retry())
+            .map(|(i, _)| i)
+            .collect();
+        if pending.is_empty() {
+            break;
+        }
+
+        let delay = retry_delay(args.retry_delay, round);
+        println!(
+            "🔁 Retrying {} page(s) in {} ms, round {}/{}",
+            pending.len(),
+            delay.as_millis(),
+            round,
+            args.retries
+        );
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.requested() => break,
+        }
+
+        for i in pending {
+            if shutdown.is_requested() {
+                break;
+            }
+            let node = &mut toc[i];
+            println!("→ [{}/{}] Retrying {}", i + 1, toc_len, node.href);
+
+            let mut page_report = std::mem::take(&mut build_report.pages[i]);
+            render_attempt(i, node, &context, &mut page_report, &shutdown).await;
+            build_report.replace(i, page_report);
+        }
     }
 
-    browser.close().await?;
-    handle.await?;
+    build_report.print_summary();
+    if let Some(path) = &args.report {
+        build_report.save(path)?;
+    }
+
+    // 🛑 An interrupted run can still produce a document of the rendered pages
+    let interrupted = shutdown.is_requested();
+    let rendered = build_report.succeeded();
+    if interrupted && !confirm_partial_output(rendered, toc_len, args.keep_partial) {
+        close_browser(browser, handle).await?;
+        anyhow::bail!("Interrupted, no output written");
+    }
+
+    let doc_title = args
+        .document
+        .title
+        .clone()
+        .or_else(|| site_meta.title.clone())
+        .unwrap_or_else(|| url.to_string());
+
+    let merge_options = args.document.merge_options(doc_title.clone())?;
 
     // Filter only TOC with file_path
     toc.retain(|it| it.file_path.is_some());
 
-    if toc.is_empty() {
+    if args.format != OutputFormat::Pdf {
+        close_browser(browser, handle).await?;
+
+        if toc.is_empty() {
+            println!("ERROR: No pages captured");
+
+            return Ok(());
+        }
+
+        let output = args.format.output_path(&PathBuf::from(output));
+        println!("📚 Packaging {} pages into {}", toc.len(), output.display());
+        export(args.format, &toc, &output, &merge_options.metadata).await?;
+
+        if interrupted {
+            anyhow::bail!(
+                "Interrupted, the output has {} of {} pages",
+                rendered,
+                toc_len
+            );
+        }
+
+        return Ok(());
+    }
+
+    let volumes = plan_volumes(toc, args.document.split_mode(), &PathBuf::from(output));
+
+    // Every volume gets its own cover, it lists all volumes of a split book
+    let mut covers = Vec::new();
+    if args.cover {
+        let volume_titles: Vec<String> = if volumes.len() > 1 {
+            volumes.iter().map(|v| v.title.clone()).collect()
+        } else {
+            Vec::new()
+        };
+
+        for volume in &volumes {
+            let cover_info = CoverInfo {
+                title: doc_title.clone(),
+                url: url.to_string(),
+                date: match merge_options.metadata.date {
+                    Some(date) => date.strftime("%Y-%m-%d").to_string(),
+                    None => jiff::Zoned::now().strftime("%Y-%m-%d").to_string(),
+                },
+                subtitle: args.subtitle.clone(),
+                author: args.document.author.clone(),
+                icon_url: site_meta.icon_url.clone(),
+                volumes: volume_titles.clone(),
+                volume: volume.number,
+            };
+            let cover_path = dir.path().join(format!("cover-{}.pdf", volume.number));
+
+            match render_cover(&browser, &cover_info, &cover_path, &context.timeouts).await {
+                Ok(()) => covers.push(Some(cover_path)),
+                Err(e) => {
+                    tracing::error!("Failed to render cover page: {}", e);
+                    covers.push(None);
+                }
+            }
+        }
+    }
+
+    close_browser(browser, handle).await?;
+
+    if volumes.is_empty() {
         println!("ERROR: No files for merging");
 
         return Ok(());
     }
 
     // 🧩 4. Merge PDFs
-    let output_path = PathBuf::from(output);
-    println!("📚 Merging {} PDFs into {}", toc.len(), output);
-    merge_pdfs(toc, output_path)?;
+    merge_volumes(&volumes, &merge_options, &covers)?;
+
+    if let Some(path) = &args.document.manifest {
+        let manifest = Manifest::from_volumes(
+            merge_options.metadata.title.clone(),
+            Some(url.to_string()),
+            &volumes,
+        );
+        manifest.save(path)?;
+    }
+
+    if interrupted {
+        anyhow::bail!(
+            "Interrupted, the output has {} of {} pages",
+            rendered,
+            toc_len
+        );
+    }
 
     Ok(())
 }
 
 ///
-/// Processing a web page
+/// Close the browser and wait for its process to exit
+///
+async fn close_browser(mut browser: Browser, handle: JoinHandle<()>) -> Result<()> {
+    if let Err(e) = browser.close().await {
+        tracing::warn!("Failed to close browser: {}", e);
+    }
+    handle.await?;
+    if tokio::time::timeout(BROWSER_CLOSE_TIMEOUT, browser.wait())
+        .await
+        .is_err()
+    {
+        tracing::warn!("Browser didn't exit in time, killing it");
+        browser.kill().await;
+    }
+
+    Ok(())
+}
+
+///
+/// Merge every volume into its own file, links between volumes point to the other files
+///
+fn merge_volumes(
+    volumes: &[Volume],
+    options: &MergeOptions,
+    covers: &[Option<PathBuf>],
+) -> Result<()> {
+    let split = volumes.len() > 1;
+    let mut degraded = false;
+    let mut chapters = 0;
+
+    for (index, volume) in volumes.iter().enumerate() {
+        let mut volume_options = options.clone();
+        volume_options.cover = covers.get(index).cloned().flatten();
+        volume_options.first_chapter = chapters;
+        chapters += volume.toc.iter().filter(|node| node.level == 0).count() as u32;
+        if split {
+            volume_options.volume = Some(volume_links(volumes, volume.number));
+            if let Some(title) = &options.metadata.title {
+                volume_options.metadata.title =
+                    Some(format!("{} - Volume {}", title, volume.number));
+            }
+        }
+
+        println!(
+            "📚 Merging {} PDFs into {}",
+            volume.toc.len(),
+            volume.path.display()
+        );
+        let report = merge_pdfs(volume.toc.clone(), &volume.path, &volume_options)?;
+        degraded |= report.is_degraded();
+    }
+
+    if degraded {
+        println!("⚠️ Some chapters were merged without full tagging, use --strict to fail instead");
+    }
+
+    Ok(())
+}
+
+///
+/// Render a page once in a fresh tab and record the attempt in the report
+///
+async fn render_attempt(
+    index: usize,
+    node: &mut TocNode,
+    context: &RenderContext<'_>,
+    report: &mut PageReport,
+    shutdown: &Shutdown,
+) {
+    report.begin_attempt();
+    let started = Instant::now();
+
+    let result = process_page(index, node, context, report, shutdown).await;
+    if shutdown.is_requested() && result.is_err() {
+        report.fail("Not rendered: interrupted");
+    } else if let Err(e) = result {
+        tracing::error!("Failed to process {}: {}", node.href, e);
+        report.fail_retryable(e);
+    }
+
+    report.end_attempt(started);
+}
+
+///
+/// Delay before a retry round: the base delay doubled every round, at most a minute
+///
+fn retry_delay(base_ms: u64, round: u32) -> Duration {
+    let factor = 1u64 << round.saturating_sub(1).min(16);
+    Duration::from_millis(base_ms.saturating_mul(factor).min(MAX_RETRY_DELAY_MS))
+}
+
+/// Everything shared by the pages of one run
+struct RenderContext<'a> {
+    browser: &'a Browser,
+    /// Temporary folder for rendered pages
+    dir: &'a TempDir,
+    adapter: &'a dyn ResourceAdapter,
+    templates: &'a PrintTemplates,
+    format: OutputFormat,
+    /// Strategy of waiting for a page, chosen by the user or the adapter
+    readiness: Readiness,
+    timeouts: Timeouts,
+    /// What to do with pages responding with HTTP 4xx/5xx
+    http_errors: ResponsePolicy,
+    /// What to do with pages redirecting to another host
+    offsite_redirects: ResponsePolicy,
+    /// Language set on pages which don't declare one
+    default_lang: &'a str,
+}
+
+///
+/// Processing a web page in a new tab, the tab is closed afterwards, also when rendering is
+/// aborted by a second signal
 ///
 async fn process_page(
     index: usize,
     node: &mut TocNode,
-    browser: &Browser,
-    dir: &TempDir,
-    adapter: &dyn ResourceAdapter,
+    context: &RenderContext<'_>,
+    report: &mut PageReport,
+    shutdown: &Shutdown,
 ) -> Result<()> {
     println!("  🌐 Creating new page...");
 
-    let page = browser.new_page("about:blank").await?;
+    let page = tokio::select! {
+        page = context.browser.new_page("about:blank") => page?,
+        _ = shutdown.aborted() => anyhow::bail!("Aborted"),
+    };
     tracing::debug!("Page created");
 
-    adapter.before_page(&page).await?;
+    let result = tokio::select! {
+        result = render_page(&page, index, node, context, report) => result,
+        _ = shutdown.aborted() => Err(anyhow::anyhow!("Aborted")),
+    };
+
+    if let Err(e) = page.close().await {
+        tracing::debug!("Failed to close page: {}", e);
+    }
+
+    result
+}
+
+async fn render_page(
+    page: &Page,
+    index: usize,
+    node: &mut TocNode,
+    context: &RenderContext<'_>,
+    report: &mut PageReport,
+) -> Result<()> {
+    let RenderContext {
+        dir,
+        adapter,
+        templates,
+        format,
+        ref readiness,
+        timeouts,
+        http_errors,
+        offsite_redirects,
+        default_lang,
+        ..
+    } = *context;
+
+    adapter.before_page(page).await?;
     tracing::debug!("adapter.before_page completed");
 
     let link = &node.href;
     tracing::debug!("Navigating to: {}", link);
 
+    let recorder = ResponseRecorder::start(page).await?;
+    let network = match readiness {
+        Readiness::NetworkIdle(_) => Some(NetworkTracker::start(page).await?),
+        _ => None,
+    };
+
     // Navigate with timeout, but continue even if timeout occurs
-    let timeout_result = tokio::time::timeout(
-        std::time::Duration::from_secs(LOAD_PAGE_TIMEOUT_SEC),
-        page.goto(link),
-    )
-    .await;
+    let started = Instant::now();
+    let timeout_result = tokio::time::timeout(timeouts.load, page.goto(link)).await;
 
     match &timeout_result {
         Ok(Ok(_)) => {
@@ -200,42 +977,95 @@ async fn process_page(
         }
         Ok(Err(e)) => {
             tracing::error!("Navigation failed: {}", e);
+            report.fail_retryable(format!("Navigation failed: {}", e));
             return Ok(());
         }
         Err(_) => {
-            tracing::warn!("Timeout after {LOAD_PAGE_TIMEOUT_SEC} seconds, stopping page load");
+            tracing::warn!(
+                "Timeout after {} seconds, stopping page load",
+                timeouts.load.as_secs()
+            );
             page.execute(StopLoadingParams::default()).await?;
+            report.warn(PageWarning::StoppedLoading);
         }
     }
+    report.load_ms = elapsed_ms(started);
 
-    page.emulate_media_type(MediaTypeParams::Print).await?;
+    // Main document response: status and the URL after redirects
+    let response = recorder.finish(page).await?;
+    report.http_status = response.status;
+    report.redirects = response.redirects.clone();
+    report.final_url = match &response.url {
+        Some(url) => Some(url.clone()),
+        None => page.url().await.ok().flatten(),
+    };
+    node.final_url = report.final_url.clone();
 
-    // Wait for document to be ready
-    let wait_js = PAGE_WAIT_JS;
+    if let Some(status) = response.status.filter(|_| response.is_error()) {
+        tracing::warn!("Server responded with HTTP {}", status);
+        match http_errors {
+            ResponsePolicy::Skip if status == 429 || status >= 500 => {
+                report.fail_retryable(format!("HTTP {}", status));
+                return Ok(());
+            }
+            ResponsePolicy::Skip => {
+                report.fail(format!("HTTP {}", status));
+                return Ok(());
+            }
+            ResponsePolicy::Flag => report.warn(PageWarning::HttpError),
+        }
+    }
 
-    // Wait for page to be ready
-    let mut wait_attempts = 0;
-    let max_attempts = 50; // 5 seconds total
+    if response.is_offsite(link) {
+        let final_url = response.url.as_deref().unwrap_or_default();
+        tracing::warn!("Redirected to another site: {}", final_url);
+        match offsite_redirects {
+            ResponsePolicy::Skip => {
+                report.fail(format!("Redirected to another site: {}", final_url));
+                return Ok(());
+            }
+            ResponsePolicy::Flag => report.warn(PageWarning::OffsiteRedirect),
+        }
+    }
 
-    loop {
-        let wait_result = page.evaluate_function(wait_js).await?;
-        let is_ready: bool = wait_result.into_value()?;
+    page.emulate_media_type(MediaTypeParams::Print).await?;
 
-        if is_ready {
-            tracing::debug!("Page document is ready");
-            break;
-        } else if wait_attempts >= max_attempts {
-            tracing::warn!("Page wait timed out after 5 seconds, proceeding anyway");
-            break;
-        } else {
-            wait_attempts += 1;
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
+    // Wait for page to be ready
+    let started = Instant::now();
+    if !wait_until_ready(page, readiness, &timeouts, network).await? {
+        tracing::warn!(
+            "Page wait timed out after {} ms, proceeding anyway",
+            timeouts.wait.as_millis()
+        );
+        report.warn(PageWarning::WaitTimeout);
     }
 
+    report.wait_ms = elapsed_ms(started);
     println!("  ✅ Page created successfully");
 
-    adapter.after_page(&page).await?;
+    adapter.after_page(page).await?;
+
+    // Pages without a language get the default one, so every chapter has /Lang
+    let declared = page
+        .evaluate_expression("document.documentElement.lang")
+        .await?
+        .into_value::<String>()
+        .unwrap_or_default();
+    if declared.is_empty() {
+        tracing::warn!("Page declares no language, using {}", default_lang);
+        report.warn(PageWarning::LanguageUnknown);
+    }
+    let script = format!(
+        "({})({})",
+        LANG_SET_JS,
+        serde_json::to_string(default_lang)?
+    );
+    let lang = page
+        .evaluate_expression(script)
+        .await?
+        .into_value::<String>()?;
+    tracing::debug!("Page language: {}", lang);
+    node.lang = Some(lang);
 
     // TODO: collect title inside TocNode
     let title = if let Some(ref t) = node.title {
@@ -253,6 +1083,7 @@ async fn process_page(
             Ok(title) => title,
             Err(_) => {
                 tracing::warn!("Failed to extract title, using URL fallback");
+                report.warn(PageWarning::TitleFallback);
                 link.to_string()
             }
         };
@@ -268,6 +1099,10 @@ async fn process_page(
         title
     };
     println!("  ✅ Title extracted: {}", title);
+    if node.title.is_none() {
+        node.title = Some(title);
+    }
+    report.title = node.title.clone();
 
     // TODO: create HabrAdapter
     if link.starts_with("https://habr.com") {
@@ -280,16 +1115,35 @@ async fn process_page(
     // DEBUG
     // tokio::time::sleep(std::time::Duration::from_mins(10)).await;
 
+    if format != OutputFormat::Pdf {
+        let html_path = dir.path().join(format!("page_{:04}.html", index));
+        println!("  📄 Capturing page content...");
+        let started = Instant::now();
+        let captured = capture_page(page, &html_path).await;
+        report.print_ms = elapsed_ms(started);
+        if let Err(e) = captured {
+            tracing::error!("Failed to capture page: {}", e);
+            report.fail_retryable(format!("Failed to capture page: {}", e));
+            return Ok(());
+        }
+
+        node.file_path = Some(html_path);
+        println!("  ✅ Page processing complete\n");
+
+        return Ok(());
+    }
+
     println!("  🖨️ Generating PDF...");
     tracing::debug!("Configuring PDF generation options");
     // let pdf_opts = PrintToPdfParams::default();
-    let pdf_opts = PrintToPdfParams {
+    let mut pdf_opts = PrintToPdfParams {
         generate_tagged_pdf: Some(true),
         scale: Some(1.0),
         print_background: Some(false),
         prefer_css_page_size: Some(true),
         ..Default::default()
     };
+    templates.apply(&mut pdf_opts, node);
     tracing::debug!(
         "PDF options: tagged={}, scale={}, background={}, css_size={}",
         pdf_opts.generate_tagged_pdf.unwrap_or(false),
@@ -297,16 +1151,15 @@ async fn process_page(
         pdf_opts.print_background.unwrap_or(false),
         pdf_opts.prefer_css_page_size.unwrap_or(false)
     );
+    // The outline becomes heading bookmarks under the chapter
+    pdf_opts.generate_document_outline = Some(true);
     let pdf_path = dir.path().join(format!("page_{:04}.pdf", index));
-    // pdf_opts.generate_document_outline = Some(true);
 
     println!("  💾 Saving PDF to {}...", pdf_path.display());
     tracing::debug!("Starting PDF save operation");
-    let save_result = tokio::time::timeout(
-        std::time::Duration::from_secs(60),
-        page.save_pdf(pdf_opts, &pdf_path),
-    )
-    .await;
+    let started = Instant::now();
+    let save_result = tokio::time::timeout(timeouts.pdf, page.save_pdf(pdf_opts, &pdf_path)).await;
+    report.print_ms = elapsed_ms(started);
 
     tracing::debug!("PDF save operation completed");
     match save_result {
@@ -316,10 +1169,19 @@ async fn process_page(
         }
         Ok(Err(e)) => {
             tracing::error!("Failed to save PDF: {}", e);
+            report.fail_retryable(format!("Failed to save PDF: {}", e));
             return Ok(());
         }
         Err(e) => {
-            tracing::error!("Timeout saving PDF after 60 seconds. Error: {}", e);
+            tracing::error!(
+                "Timeout saving PDF after {} seconds. Error: {}",
+                timeouts.pdf.as_secs(),
+                e
+            );
+            report.fail_retryable(format!(
+                "Timeout saving PDF after {} seconds",
+                timeouts.pdf.as_secs()
+            ));
             return Ok(());
         }
     }
@@ -327,15 +1189,25 @@ async fn process_page(
     match std::fs::metadata(&pdf_path) {
         Ok(metadata) => {
             println!("  📊 PDF size: {} bytes", metadata.len());
+            report.pdf_size = Some(metadata.len());
         }
         Err(e) => {
             println!("  ❌ Failed to get PDF metadata: {}", e);
+            report.fail(format!("Failed to get PDF metadata: {}", e));
             return Ok(());
         }
     }
+    match lopdf::Document::load_metadata(&pdf_path) {
+        Ok(metadata) => report.page_count = Some(metadata.page_count),
+        Err(e) => tracing::warn!("Failed to count pages of {}: {}", pdf_path.display(), e),
+    }
 
     node.file_path = Some(pdf_path);
     println!("  ✅ Page processing complete\n");
 
     Ok(())
 }
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
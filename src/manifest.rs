@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::_pdf_utils::extract_text::{ChapterText, extract_text};
+use crate::_pdf_utils::split::Volume;
+
+/// Description of a finished build, compared by the `diff` command
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub title: Option<String>,
+    /// Source URL of the site, empty for merged files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub chapters: Vec<ManifestChapter>,
+}
+
+/// A chapter of the build and its text
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestChapter {
+    pub title: String,
+    /// Page URL or input file of the chapter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    #[serde(default)]
+    pub level: u8,
+    /// Output file containing the chapter, differs between the volumes of a split build
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Page where the chapter starts in its output file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_page: Option<u32>,
+    /// Headings and paragraphs with normalized whitespace, in reading order
+    #[serde(default)]
+    pub paragraphs: Vec<String>,
+}
+
+impl ManifestChapter {
+    pub fn from_text(chapter: &ChapterText, file: Option<String>) -> Self {
+        Self {
+            title: chapter.title.clone(),
+            href: None,
+            level: chapter.level,
+            file,
+            start_page: chapter.start_page,
+            paragraphs: chapter.block_texts(),
+        }
+    }
+}
+
+impl Manifest {
+    ///
+    /// Describe the merged volumes, text and pages come from the structure tree of the output
+    ///
+    pub fn from_volumes(title: Option<String>, source: Option<String>, volumes: &[Volume]) -> Self {
+        let mut chapters = Vec::new();
+
+        for volume in volumes {
+            let extracted = match extract_text(&volume.path) {
+                Ok(extracted) => extracted.chapters,
+                Err(e) => {
+                    tracing::warn!("Failed to extract text of {}: {}", volume.path.display(), e);
+                    Vec::new()
+                }
+            };
+
+            // Extracted chapters follow the TOC, untagged chapters have no section of their own
+            let mut next = 0;
+            for node in &volume.toc {
+                let title = node.title.clone().unwrap_or_else(|| {
+                    node.file_path
+                        .as_ref()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_default()
+                });
+                let position = extracted[next..].iter().position(|c| c.title == title);
+                let text = position.map(|position| {
+                    next += position + 1;
+                    &extracted[next - 1]
+                });
+
+                chapters.push(ManifestChapter {
+                    title,
+                    href: Some(node.href.clone()),
+                    level: node.level,
+                    file: Some(volume.file_name()),
+                    start_page: text.and_then(|t| t.start_page),
+                    paragraphs: text.map(ChapterText::block_texts).unwrap_or_default(),
+                });
+            }
+        }
+
+        Self {
+            title,
+            source,
+            chapters,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_slice(&data)
+            .with_context(|| format!("{} is not a build manifest", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("🧾 Manifest saved to {}", path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventRequestWillBeSent, EventResponseReceived, ResourceType,
+};
+use chromiumoxide::cdp::browser_protocol::page::FrameId;
+use chromiumoxide::listeners::EventStream;
+use futures::{FutureExt, StreamExt};
+use url::Url;
+
+/// What to do with a page whose response looks wrong
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ResponsePolicy {
+    /// Leave the page out of the output, it is listed in the build report
+    #[default]
+    Skip,
+    /// Render the page anyway with a warning in the build report
+    Flag,
+}
+
+/// Main document response of a navigation
+#[derive(Debug, Clone, Default)]
+pub struct DocumentResponse {
+    pub status: Option<u16>,
+    /// URL of the document after redirects
+    pub url: Option<String>,
+    /// URLs which redirected to the next one, in order
+    pub redirects: Vec<String>,
+}
+
+impl DocumentResponse {
+    pub fn is_error(&self) -> bool {
+        self.status.is_some_and(|status| status >= 400)
+    }
+
+    ///
+    /// The final URL is on another host than the requested one, e.g. a login page.
+    /// A leading `www.` is ignored
+    ///
+    pub fn is_offsite(&self, requested: &str) -> bool {
+        let host = |url: &str| {
+            Url::parse(url).ok().and_then(|url| {
+                url.host_str().map(|h| {
+                    let h = h.trim_end_matches('.').to_lowercase();
+                    h.strip_prefix("www.").map(str::to_string).unwrap_or(h)
+                })
+            })
+        };
+        match (&self.url, host(requested)) {
+            (Some(url), Some(requested)) => host(url).is_some_and(|host| host != requested),
+            _ => false,
+        }
+    }
+}
+
+/// Records Network events of a page while it navigates
+pub struct ResponseRecorder {
+    requests: EventStream<EventRequestWillBeSent>,
+    responses: EventStream<EventResponseReceived>,
+}
+
+impl ResponseRecorder {
+    ///
+    /// Start recording, must be called before the navigation
+    ///
+    pub async fn start(page: &Page) -> Result<Self> {
+        Ok(Self {
+            requests: page.event_listener::<EventRequestWillBeSent>().await?,
+            responses: page.event_listener::<EventResponseReceived>().await?,
+        })
+    }
+
+    ///
+    /// Main frame document response among the events received so far
+    ///
+    pub async fn finish(mut self, page: &Page) -> Result<DocumentResponse> {
+        let main_frame = page.mainframe().await?;
+        let main_frame = main_frame.as_ref();
+
+        let mut document = DocumentResponse::default();
+        while let Some(Some(event)) = self.requests.next().now_or_never() {
+            if let Some(redirect) = &event.redirect_response
+                && is_main_document(event.r#type.as_ref(), event.frame_id.as_ref(), main_frame)
+            {
+                document.redirects.push(redirect.url.clone());
+            }
+        }
+        while let Some(Some(event)) = self.responses.next().now_or_never() {
+            if is_main_document(Some(&event.r#type), event.frame_id.as_ref(), main_frame) {
+                document.status = u16::try_from(event.response.status).ok();
+                document.url = Some(event.response.url.clone());
+            }
+        }
+
+        Ok(document)
+    }
+}
+
+fn is_main_document(
+    kind: Option<&ResourceType>,
+    frame: Option<&FrameId>,
+    main_frame: Option<&FrameId>,
+) -> bool {
+    kind == Some(&ResourceType::Document) && (main_frame.is_none() || frame == main_frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DocumentResponse;
+
+    fn redirected_to(url: &str) -> DocumentResponse {
+        DocumentResponse {
+            url: Some(url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn www_prefix_is_the_same_host() {
+        let requested = "https://example.com/guide/";
+        assert!(!redirected_to("https://www.example.com/guide/").is_offsite(requested));
+        assert!(!redirected_to("https://example.com/other/").is_offsite(requested));
+        assert!(!redirected_to("https://example.com/").is_offsite("https://www.example.com/"));
+    }
+
+    #[test]
+    fn other_hosts_are_offsite() {
+        let requested = "https://docs.example.com/guide/";
+        assert!(redirected_to("https://login.example.com/").is_offsite(requested));
+        assert!(redirected_to("https://example.com/login").is_offsite(requested));
+        assert!(redirected_to("https://b.github.io/").is_offsite("https://a.github.io/"));
+        assert!(redirected_to("http://127.0.0.2/").is_offsite("http://127.0.0.1/"));
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use chromiumoxide::cdp::browser_protocol::page::PrintToPdfParams;
+
+use crate::escape::escape_attr;
+use crate::toc::TocNode;
+
+/// Chrome's default vertical margin in inches
+const DEFAULT_MARGIN_IN: f64 = 0.4;
+/// Height of a single template line in inches
+const LINE_HEIGHT_IN: f64 = 0.2;
+/// Space between a template and the page content in inches
+const GAP_IN: f64 = 0.25;
+/// Chrome shows its own default header/footer when one of the templates is empty
+const EMPTY_TEMPLATE: &str = "<span></span>";
+
+/// Chromium header and footer templates applied to every printed page
+///
+/// Besides Chrome placeholders (`<span class="pageNumber"></span>` etc.) templates may contain
+/// `{chapter}` and `{level}` which are filled from the TOC node, the same syntax as the stamp.
+#[derive(Debug, Clone, Default)]
+pub struct PrintTemplates {
+    pub header: Option<String>,
+    pub footer: Option<String>,
+}
+
+impl PrintTemplates {
+    ///
+    /// Build templates from CLI values, each value is either a path to an HTML file or inline HTML
+    ///
+    pub fn load(header: Option<&str>, footer: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            header: header.map(read_template).transpose()?,
+            footer: footer.map(read_template).transpose()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.header.is_none() && self.footer.is_none()
+    }
+
+    ///
+    /// Enable header/footer printing and reserve enough margin for the templates
+    ///
+    pub fn apply(&self, params: &mut PrintToPdfParams, node: &TocNode) {
+        if self.is_empty() {
+            return;
+        }
+
+        let render = |template: &Option<String>| {
+            template
+                .as_deref()
+                .map(|t| fill_node_values(t, node))
+                .unwrap_or_else(|| EMPTY_TEMPLATE.to_string())
+        };
+
+        params.display_header_footer = Some(true);
+        params.header_template = Some(render(&self.header));
+        params.footer_template = Some(render(&self.footer));
+        params.margin_top = Some(margin_for(self.header.as_deref()));
+        params.margin_bottom = Some(margin_for(self.footer.as_deref()));
+    }
+}
+
+fn read_template(value: &str) -> Result<String> {
+    let path = std::path::Path::new(value);
+    if path.is_file() {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {}", path.display()))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+fn fill_node_values(template: &str, node: &TocNode) -> String {
+    let chapter = node.title.as_deref().unwrap_or(&node.href);
+    template
+        .replace("{chapter}", &escape_attr(chapter))
+        .replace("{level}", &node.level.to_string())
+}
+
+///
+/// Estimate the margin needed by a template from the number of its lines
+///
+fn margin_for(template: Option<&str>) -> f64 {
+    let Some(template) = template else {
+        return DEFAULT_MARGIN_IN;
+    };
+
+    let lower = template.to_lowercase();
+    let breaks = ["<br", "<p", "<div", "<li", "<tr"]
+        .iter()
+        .map(|tag| lower.matches(tag).count())
+        .sum::<usize>();
+    let lines = breaks.max(1) as f64;
+
+    (GAP_IN + lines * LINE_HEIGHT_IN).max(DEFAULT_MARGIN_IN)
+}
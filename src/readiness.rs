@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use chromiumoxide::Page;
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent, RequestId,
+};
+use chromiumoxide::listeners::EventStream;
+use futures::StreamExt;
+
+use crate::PAGE_WAIT_JS;
+
+/// Interval of polling a selector or a predicate
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Quiet period of `network-idle` without a value
+const DEFAULT_NETWORK_IDLE: Duration = Duration::from_millis(500);
+
+/// How to tell that a page is ready to be printed, every strategy waits for the document
+/// and its images first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Readiness {
+    /// The document is loaded and its images are decoded
+    Document,
+    /// No network request was in flight for the given time
+    NetworkIdle(Duration),
+    /// An element matching the CSS selector exists
+    Selector(String),
+    /// The JavaScript expression is truthy, a promise is awaited
+    Predicate(String),
+    /// A fixed delay passed
+    Delay(Duration),
+}
+
+/// Timeouts of rendering a single page
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// Navigation, loading is stopped after it and the page is printed as is
+    pub load: Duration,
+    /// Waiting for the page to become ready
+    pub wait: Duration,
+    /// Waiting for a single image
+    pub image: Duration,
+    /// Printing the page to PDF
+    pub pdf: Duration,
+}
+
+/// Network events of a page, recorded from the navigation on so requests which are already
+/// in flight when the wait starts are counted
+pub struct NetworkTracker {
+    started: EventStream<EventRequestWillBeSent>,
+    finished: EventStream<EventLoadingFinished>,
+    failed: EventStream<EventLoadingFailed>,
+}
+
+impl NetworkTracker {
+    ///
+    /// Start recording, must be called before the navigation
+    ///
+    pub async fn start(page: &Page) -> Result<Self> {
+        Ok(Self {
+            started: page.event_listener::<EventRequestWillBeSent>().await?,
+            finished: page.event_listener::<EventLoadingFinished>().await?,
+            failed: page.event_listener::<EventLoadingFailed>().await?,
+        })
+    }
+}
+
+///
+/// Parse a readiness strategy: `document`, `network-idle[:MS]`, `selector:CSS`, `js:EXPRESSION`
+/// or `delay:MS`
+///
+pub fn parse_readiness(value: &str) -> Result<Readiness, String> {
+    let (kind, argument) = match value.split_once(':') {
+        Some((kind, argument)) => (kind.trim(), Some(argument.trim())),
+        None => (value.trim(), None),
+    };
+    let millis = |argument: &str| {
+        argument
+            .parse::<u64>()
+            .map(Duration::from_millis)
+            .map_err(|_| format!("invalid duration '{}', expected milliseconds", argument))
+    };
+    let required = |argument: Option<&str>| match argument {
+        Some(argument) if !argument.is_empty() => Ok(argument.to_string()),
+        _ => Err(format!("'{}' needs a value, e.g. '{}:...'", kind, kind)),
+    };
+
+    match kind {
+        "document" => Ok(Readiness::Document),
+        "network-idle" => match argument {
+            Some(argument) => millis(argument).map(Readiness::NetworkIdle),
+            None => Ok(Readiness::NetworkIdle(DEFAULT_NETWORK_IDLE)),
+        },
+        "selector" => required(argument).map(Readiness::Selector),
+        "js" => required(argument).map(Readiness::Predicate),
+        "delay" => millis(&required(argument)?).map(Readiness::Delay),
+        other => Err(format!(
+            "unknown readiness strategy '{}', expected document, network-idle, selector, js or delay",
+            other
+        )),
+    }
+}
+
+///
+/// Wait until the page is ready, `false` if the wait timed out. `network` is the tracker
+/// started before the navigation, it is needed by `network-idle`
+///
+pub async fn wait_until_ready(
+    page: &Page,
+    readiness: &Readiness,
+    timeouts: &Timeouts,
+    network: Option<NetworkTracker>,
+) -> Result<bool> {
+    let ready = async {
+        wait_for_document(page, timeouts.image).await?;
+
+        match readiness {
+            Readiness::Document => {}
+            Readiness::NetworkIdle(idle) => {
+                let network = match network {
+                    Some(network) => network,
+                    None => NetworkTracker::start(page).await?,
+                };
+                wait_for_network_idle(network, *idle).await?
+            }
+            Readiness::Selector(selector) => {
+                let predicate = format!(
+                    "document.querySelector({}) !== null",
+                    serde_json::to_string(selector)?
+                );
+                wait_for_predicate(page, &predicate).await?;
+            }
+            Readiness::Predicate(predicate) => wait_for_predicate(page, predicate).await?,
+            Readiness::Delay(delay) => tokio::time::sleep(*delay).await,
+        }
+
+        anyhow::Ok(())
+    };
+
+    match tokio::time::timeout(timeouts.wait, ready).await {
+        Ok(result) => result.map(|_| true),
+        Err(_) => Ok(false),
+    }
+}
+
+async fn wait_for_document(page: &Page, image_timeout: Duration) -> Result<()> {
+    let script = format!("({})({})", PAGE_WAIT_JS, image_timeout.as_millis());
+
+    loop {
+        let is_ready: bool = page
+            .evaluate_expression(script.as_str())
+            .await?
+            .into_value()?;
+        if is_ready {
+            tracing::debug!("Page document is ready");
+            return Ok(());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+async fn wait_for_predicate(page: &Page, predicate: &str) -> Result<()> {
+    let script = format!("(async () => !!(await ({})))()", predicate);
+
+    loop {
+        let is_ready: bool = page
+            .evaluate_expression(script.as_str())
+            .await?
+            .into_value()?;
+        if is_ready {
+            tracing::debug!("Page predicate is true: {}", predicate);
+            return Ok(());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+///
+/// Wait until no request is in flight for the `idle` time
+///
+async fn wait_for_network_idle(network: NetworkTracker, idle: Duration) -> Result<()> {
+    enum Change {
+        Started(RequestId),
+        Ended(RequestId),
+    }
+
+    let NetworkTracker {
+        started,
+        finished,
+        failed,
+    } = network;
+    let started = started.map(|e| Change::Started(e.request_id.clone()));
+    let finished = finished.map(|e| Change::Ended(e.request_id.clone()));
+    let failed = failed.map(|e| Change::Ended(e.request_id.clone()));
+    let mut events = futures::stream::select(started, futures::stream::select(finished, failed));
+
+    // Events recorded during the navigation are read first, without waiting
+    let mut in_flight: HashSet<RequestId> = HashSet::new();
+    loop {
+        match tokio::time::timeout(idle, events.next()).await {
+            Ok(Some(Change::Started(id))) => {
+                in_flight.insert(id);
+            }
+            Ok(Some(Change::Ended(id))) => {
+                in_flight.remove(&id);
+            }
+            Ok(None) => return Ok(()),
+            Err(_) if in_flight.is_empty() => {
+                tracing::debug!("Network is idle");
+                return Ok(());
+            }
+            Err(_) => {
+                tracing::debug!("Waiting for {} request(s)", in_flight.len());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Readiness, parse_readiness};
+
+    #[test]
+    fn parses_strategies() {
+        assert_eq!(parse_readiness("document"), Ok(Readiness::Document));
+        assert_eq!(
+            parse_readiness("network-idle"),
+            Ok(Readiness::NetworkIdle(Duration::from_millis(500)))
+        );
+        assert_eq!(
+            parse_readiness("network-idle:1200"),
+            Ok(Readiness::NetworkIdle(Duration::from_millis(1200)))
+        );
+        assert_eq!(
+            parse_readiness("delay: 250 "),
+            Ok(Readiness::Delay(Duration::from_millis(250)))
+        );
+    }
+
+    #[test]
+    fn keeps_colons_of_the_value() {
+        assert_eq!(
+            parse_readiness("selector:main article:not(.draft)"),
+            Ok(Readiness::Selector("main article:not(.draft)".to_string()))
+        );
+        assert_eq!(
+            parse_readiness("js:window.ready === true ? 1 : 0"),
+            Ok(Readiness::Predicate(
+                "window.ready === true ? 1 : 0".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(parse_readiness("load").unwrap_err().contains("unknown"));
+        assert!(
+            parse_readiness("selector")
+                .unwrap_err()
+                .contains("needs a value")
+        );
+        assert!(
+            parse_readiness("js:")
+                .unwrap_err()
+                .contains("needs a value")
+        );
+        assert!(
+            parse_readiness("delay")
+                .unwrap_err()
+                .contains("needs a value")
+        );
+        assert!(
+            parse_readiness("delay:1s")
+                .unwrap_err()
+                .contains("invalid duration")
+        );
+        assert!(
+            parse_readiness("network-idle:-5")
+                .unwrap_err()
+                .contains("invalid duration")
+        );
+    }
+}
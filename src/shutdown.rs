@@ -0,0 +1,138 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use tokio::sync::watch;
+
+#[cfg(unix)]
+use crate::browser_utils::kill_process_group;
+
+/// Exit code of a process stopped by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Counts SIGINT/SIGTERM signals: the first one stops scheduling new pages, the second one
+/// aborts the current page and the third one exits at once
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    signals: watch::Receiver<u32>,
+}
+
+impl Shutdown {
+    ///
+    /// Listen for Ctrl-C and SIGTERM in the background, the browser process is killed when
+    /// web2pdf exits on the third signal
+    ///
+    pub fn listen(browser: Option<u32>) -> Self {
+        let (sender, signals) = watch::channel(0);
+        #[cfg(not(unix))]
+        let _ = browser;
+
+        tokio::spawn(async move {
+            loop {
+                wait_for_signal().await;
+
+                let count = *sender.borrow() + 1;
+                match count {
+                    1 => println!(
+                        "\n🛑 Interrupted: finishing the current page, press Ctrl-C again to abort it"
+                    ),
+                    2 => println!("\n🛑 Aborting the current page, press Ctrl-C again to exit"),
+                    _ => {
+                        // The browser runs in its own process group and doesn't exit with web2pdf
+                        #[cfg(unix)]
+                        if let Some(pid) = browser
+                            && let Err(e) = kill_process_group(pid)
+                        {
+                            tracing::warn!("Failed to kill browser: {}", e);
+                        }
+                        std::process::exit(INTERRUPTED_EXIT_CODE)
+                    }
+                }
+                sender.send_replace(count);
+            }
+        });
+
+        Self { signals }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.signals.borrow() > 0
+    }
+
+    ///
+    /// Resolves on the first signal
+    ///
+    pub async fn requested(&self) {
+        self.wait_for(1).await;
+    }
+
+    ///
+    /// Resolves on the second signal, the current page should be dropped
+    ///
+    pub async fn aborted(&self) {
+        self.wait_for(2).await;
+    }
+
+    async fn wait_for(&self, count: u32) {
+        let mut signals = self.signals.clone();
+        if signals
+            .wait_for(|received| *received >= count)
+            .await
+            .is_err()
+        {
+            // The listener is gone, no signal will come
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(e) => {
+            tracing::warn!("Failed to listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+///
+/// Ask whether the rendered pages of an interrupted run should be merged. Without a terminal
+/// they are only merged with `keep_partial`, which also skips the question
+///
+pub fn confirm_partial_output(rendered: usize, total: usize, keep_partial: bool) -> bool {
+    if rendered == 0 {
+        return false;
+    }
+    if keep_partial {
+        return true;
+    }
+
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return false;
+    }
+
+    print!(
+        "❓ Build a partial document from {} of {} pages? [Y/n] ",
+        rendered, total
+    );
+    let _ = std::io::stdout().flush();
+
+    let mut answer = String::new();
+    if stdin.lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
+}
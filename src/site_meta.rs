@@ -0,0 +1,67 @@
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Site-wide information collected from the start page
+#[derive(Debug, Clone, Default)]
+pub struct SiteMeta {
+    pub title: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+impl SiteMeta {
+    ///
+    /// Collect the site title and favicon/logo from the start page HTML
+    ///
+    pub fn from_html(html: &str, base_url: &str) -> Self {
+        let document = Html::parse_document(html);
+        let base_url = Url::parse(base_url).ok();
+
+        let title = first_attr(
+            &document,
+            &[
+                r#"meta[property="og:site_name"]"#,
+                r#"meta[name="application-name"]"#,
+            ],
+            "content",
+        )
+        .or_else(|| {
+            let selector = Selector::parse("head > title").expect("valid selector");
+            document
+                .select(&selector)
+                .next()
+                .map(|el| el.text().collect::<String>().trim().to_string())
+                .filter(|t| !t.is_empty())
+        });
+
+        // Prefer bigger icons, they look better on a printed page
+        let icon_url = first_attr(
+            &document,
+            &[
+                r#"link[rel="apple-touch-icon"]"#,
+                r#"link[rel~="icon"][type="image/svg+xml"]"#,
+                r#"link[rel~="icon"]"#,
+            ],
+            "href",
+        )
+        .or_else(|| first_attr(&document, &[r#"meta[property="og:logo"]"#], "content"))
+        .and_then(|href| match &base_url {
+            Some(base) => base.join(&href).ok().map(|u| u.to_string()),
+            None => Some(href),
+        });
+
+        tracing::debug!("Site meta: title={:?}, icon={:?}", title, icon_url);
+
+        SiteMeta { title, icon_url }
+    }
+}
+
+fn first_attr(document: &Html, selectors: &[&str], attr: &str) -> Option<String> {
+    selectors.iter().find_map(|s| {
+        let selector = Selector::parse(s).expect("valid selector");
+        document
+            .select(&selector)
+            .filter_map(|el| el.value().attr(attr))
+            .map(|v| v.trim().to_string())
+            .find(|v| !v.is_empty())
+    })
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use scraper::{ElementRef, Html, Selector};
@@ -10,6 +10,10 @@ pub struct TocNode {
     pub title: Option<String>,
     pub href: String,
     pub level: u8,
+    /// Page language detected while rendering
+    pub lang: Option<String>,
+    /// URL of the rendered document after redirects
+    pub final_url: Option<String>,
 }
 
 // TODO: move to adapters
@@ -34,9 +38,75 @@ pub async fn generate_toc(url: &String) -> Result<Vec<TocNode>> {
         title: None,
         href: url.to_string(),
         level: 0,
+        lang: None,
+        final_url: None,
     }])
 }
 
+///
+/// Build a flat TOC for existing PDF files, titles are taken from the file names
+///
+pub fn toc_from_files(files: &[PathBuf]) -> Vec<TocNode> {
+    files
+        .iter()
+        .map(|file| TocNode {
+            file_path: Some(file.clone()),
+            title: file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned()),
+            href: file.display().to_string(),
+            level: 0,
+            lang: None,
+            final_url: None,
+        })
+        .collect()
+}
+
+///
+/// Build a TOC for existing PDF files from a text file with one line per input file
+///
+/// Each line is the bookmark title indented by two spaces (or a tab) per nesting level.
+/// Empty lines and lines starting with `#` are ignored.
+///
+pub fn toc_from_file(path: &Path, files: &[PathBuf]) -> Result<Vec<TocNode>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read TOC file {}", path.display()))?;
+
+    let entries: Vec<(u8, &str)> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| {
+            let title = line.trim_start();
+            let indent = line[..line.len() - title.len()]
+                .chars()
+                .map(|c| if c == '\t' { 2 } else { 1 })
+                .sum::<usize>();
+            ((indent / 2).min(u8::MAX as usize) as u8, title.trim_end())
+        })
+        .collect();
+
+    if entries.len() != files.len() {
+        anyhow::bail!(
+            "TOC file {} has {} entries, but {} files are given",
+            path.display(),
+            entries.len(),
+            files.len()
+        );
+    }
+
+    let mut nodes = toc_from_files(files);
+    let mut previous_level = None;
+    for (node, (level, title)) in nodes.iter_mut().zip(entries) {
+        // A chapter can only be nested one level deeper than the previous one
+        let max_level = previous_level.map_or(0, |l: u8| l.saturating_add(1));
+        node.level = level.min(max_level);
+        node.title = Some(title.to_string());
+        previous_level = Some(node.level);
+    }
+
+    Ok(nodes)
+}
+
 async fn toc_from_navbar(url: &String) -> Result<Option<Vec<TocNode>>> {
     let html = reqwest::get(url).await?.text().await?;
 
@@ -107,6 +177,8 @@ fn parse_li(nodes: &mut Vec<TocNode>, li: ElementRef, base_url: &Url, level: u8)
             title: Some(title),
             href,
             level,
+            lang: None,
+            final_url: None,
         })
     }
 
@@ -143,6 +215,8 @@ async fn toc_from_sitemap(url: &String) -> Result<Option<Vec<TocNode>>> {
             title: None,
             href,
             level: 0,
+            lang: None,
+            final_url: None,
         });
     }
 